@@ -57,6 +57,11 @@ pub struct CreationContext<'s> {
     #[cfg(feature = "glow")]
     pub gl: Option<std::sync::Arc<glow::Context>>,
 
+    /// A second OpenGL context for uploading textures on background threads,
+    /// if [`NativeOptions::upload_context`] is set and it could be created.
+    #[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+    pub upload_context: Option<crate::UploadContext>,
+
     /// The underlying WGPU render state.
     ///
     /// Only available when compiling with the `wgpu` feature and using [`Renderer::Wgpu`].
@@ -224,6 +229,20 @@ pub enum HardwareAcceleration {
     Off,
 }
 
+/// Where to keep egui's compiled shader program between runs, see [`NativeOptions::program_cache`].
+#[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ProgramCacheLocation {
+    /// In a `program_cache` directory next to the app's [`Storage`] file,
+    /// rather than in the file itself, since program binaries are often hundreds of kilobytes.
+    ///
+    /// Requires the `persistence` feature; without it nothing is cached.
+    Storage,
+
+    /// As files in this directory.
+    Directory(std::path::PathBuf),
+}
+
 /// Options controlling the behavior of a native window.
 ///
 /// Only a single native window is currently supported.
@@ -371,8 +390,132 @@ pub struct NativeOptions {
     /// See <https://github.com/emilk/egui/pull/1993>.
     ///
     /// For OpenGL ES 2.0: set this to [`egui_glow::ShaderVersion::Es100`] to solve blank texture problem (by using the "fallback shader").
+    ///
+    /// When `None` (default), the version is detected from the driver.
+    /// The `EGUI_GLOW_SHADER_VERSION` environment variable (e.g. `"330"` or `"300 es"`) overrides both,
+    /// see [`egui_glow::ShaderVersion::ENV_VAR`].
     pub shader_version: Option<egui_glow::ShaderVersion>,
 
+    /// Blend in gamma space (default), or in linear space for predictable color.
+    ///
+    /// See [`egui_glow::OutputMode`]. With [`egui_glow::OutputMode::Linear`],
+    /// eframe asks for an sRGB-capable window surface.
+    #[cfg(feature = "glow")]
+    pub output_mode: egui_glow::OutputMode,
+
+    /// Create an OpenGL debug context and send the driver's `KHR_debug` messages to [`log`],
+    /// even in release builds.
+    ///
+    /// This also labels egui's OpenGL objects and groups its draw calls,
+    /// which helps when inspecting frames with tools like `RenderDoc` and `apitrace`.
+    /// See [`egui_glow::PainterOptions::debug`].
+    ///
+    /// Default: `false`.
+    #[cfg(feature = "glow")]
+    pub gl_debug: bool,
+
+    /// Cache egui's linked shader program, to skip compiling shaders on later launches.
+    ///
+    /// This can speed up startup considerably on some (embedded) OpenGL ES drivers.
+    /// See [`egui_glow::ProgramCache`].
+    ///
+    /// Default: `None`.
+    #[cfg(feature = "glow")]
+    pub program_cache: Option<ProgramCacheLocation>,
+
+    /// Upload at most about this many bytes of texture data per frame, spreading large images
+    /// over several frames instead of stalling.
+    ///
+    /// While an image is uploading, [`Frame::texture_upload_progress`] tells you to show a placeholder.
+    /// See [`egui_glow::Painter::set_upload_budget`].
+    ///
+    /// Default: `None` (upload everything right away).
+    #[cfg(feature = "glow")]
+    pub texture_upload_budget: Option<usize>,
+
+    /// Measure how long the GPU spends on each frame with timer queries,
+    /// and report it in [`IntegrationInfo::gpu_timings`].
+    ///
+    /// Ignored if the OpenGL context doesn't support timer queries.
+    /// See [`egui_glow::Painter::set_gpu_timing`].
+    ///
+    /// Default: `false`.
+    #[cfg(feature = "glow")]
+    pub gpu_timing: bool,
+
+    /// Create a second OpenGL context that shares textures with the one eframe paints with,
+    /// so apps can decode and upload big images on a background thread without blocking the UI.
+    /// It is made current with a 1×1 pbuffer, so it is not created if the OpenGL config doesn't support pbuffers.
+    ///
+    /// See [`crate::UploadContext`].
+    ///
+    /// Default: `false`.
+    #[cfg(feature = "glow")]
+    pub upload_context: bool,
+
+    /// Tessellate, paint and present on a render thread that owns the OpenGL context,
+    /// while the event loop thread handles input and runs the next [`App::update`].
+    ///
+    /// `Some(n)` lets at most `n` frames wait for the render thread besides the one it is painting;
+    /// `Some(0)` gives the lowest latency. A slow `swap_buffers` under vsync then no longer delays input handling.
+    ///
+    /// The OpenGL context is not current on the event loop thread while this is on,
+    /// so only use [`Frame::gl`] from paint callbacks, which run on the render thread.
+    /// [`IntegrationInfo::painter_stats`], [`IntegrationInfo::gpu_timings`] and
+    /// [`Frame::texture_upload_progress`] describe an earlier frame.
+    /// [`App::post_rendering`] is called once the render thread has painted the frame,
+    /// which is usually at the start of a later frame.
+    ///
+    /// Ignored on macOS, where the OpenGL context must be updated on the main thread when the window resizes.
+    ///
+    /// Default: `None` (paint on the event loop thread).
+    #[cfg(feature = "glow")]
+    pub pipelined_rendering: Option<usize>,
+
+    /// Only repaint the parts of the window that changed since the previous frame,
+    /// and don't present frames identical to the previous one at all.
+    ///
+    /// Where supported (`EGL_KHR_swap_buffers_with_damage`), the compositor is told which regions changed.
+    /// Saves power when little changes, e.g. when only a text cursor blinks.
+    /// See [`egui_glow::Painter::set_damage_tracking`].
+    ///
+    /// Default: `false`.
+    #[cfg(feature = "glow")]
+    pub damage_tracking: bool,
+
+    /// Start each frame as late as possible before the vblank it will be shown at,
+    /// rather than as soon as a repaint is requested, so it includes the newest input.
+    ///
+    /// eframe estimates the display's refresh timing from when swapping buffers returns,
+    /// so this needs [`Self::vsync`]. Until it has an estimate, frames start right away.
+    /// Lowers the latency of e.g. pen input by up to a refresh interval.
+    /// See also [`IntegrationInfo::predicted_present_time`].
+    ///
+    /// Ignored with [`Self::pipelined_rendering`].
+    ///
+    /// Default: `false`.
+    #[cfg(feature = "glow")]
+    pub low_latency: bool,
+
+    /// Write the input of every frame to this file, e.g. to attach to a bug report.
+    ///
+    /// Replay it with [`Self::replay_input`], or load it with [`crate::InputRecording::load`].
+    /// Failing to create the file is logged, and the app runs without recording.
+    ///
+    /// Default: `None`.
+    #[cfg(feature = "input_recording")]
+    pub record_input: Option<std::path::PathBuf>,
+
+    /// Feed the app the input of a recording made with [`Self::record_input`] in place of live input,
+    /// driving it exactly as the user did.
+    ///
+    /// The window is resized to the recorded size. Failing to load the recording is logged,
+    /// and the app runs with live input.
+    ///
+    /// Default: `None`.
+    #[cfg(feature = "input_recording")]
+    pub replay_input: Option<crate::InputReplay>,
+
     /// On desktop: make the window position to be centered at initialization.
     ///
     /// Platform specific:
@@ -442,6 +585,15 @@ impl Clone for NativeOptions {
 
             app_id: self.app_id.clone(),
 
+            #[cfg(feature = "glow")]
+            program_cache: self.program_cache.clone(),
+
+            #[cfg(feature = "input_recording")]
+            record_input: self.record_input.clone(),
+
+            #[cfg(feature = "input_recording")]
+            replay_input: self.replay_input.clone(),
+
             ..*self
         }
     }
@@ -493,6 +645,39 @@ impl Default for NativeOptions {
             #[cfg(feature = "glow")]
             shader_version: None,
 
+            #[cfg(feature = "glow")]
+            output_mode: egui_glow::OutputMode::Gamma,
+
+            #[cfg(feature = "glow")]
+            gl_debug: false,
+
+            #[cfg(feature = "glow")]
+            program_cache: None,
+
+            #[cfg(feature = "glow")]
+            texture_upload_budget: None,
+
+            #[cfg(feature = "glow")]
+            gpu_timing: false,
+
+            #[cfg(feature = "glow")]
+            upload_context: false,
+
+            #[cfg(feature = "glow")]
+            pipelined_rendering: None,
+
+            #[cfg(feature = "glow")]
+            damage_tracking: false,
+
+            #[cfg(feature = "glow")]
+            low_latency: false,
+
+            #[cfg(feature = "input_recording")]
+            record_input: None,
+
+            #[cfg(feature = "input_recording")]
+            replay_input: None,
+
             centered: false,
 
             #[cfg(feature = "wgpu")]
@@ -692,9 +877,53 @@ pub struct Frame {
     /// such that it can be retrieved during [`App::post_rendering`] with [`Frame::screenshot`]
     #[cfg(not(target_arch = "wasm32"))]
     pub(crate) screenshot: std::cell::Cell<Option<egui::ColorImage>>,
+
+    /// Regions to capture once this frame is painted, see [`Frame::request_screenshot_async`].
+    #[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+    pub(crate) screenshot_requests: Vec<(Option<egui::Rect>, ScreenshotCallback)>,
+
+    /// Textures that were still uploading after the previous frame, see [`Frame::texture_upload_progress`].
+    #[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+    pub(crate) texture_upload_progress: std::collections::HashMap<egui::TextureId, f32>,
+
+    /// See [`Frame::upload_context`].
+    #[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+    pub(crate) upload_context: Option<crate::UploadContext>,
+
+    /// See [`Frame::start_recording`].
+    #[cfg(all(feature = "recording", not(target_arch = "wasm32")))]
+    pub(crate) recorder: Option<crate::native::recording::Recorder>,
 }
 
 impl Frame {
+    /// A frame for a native integration, before the app has asked anything of it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn new(
+        info: IntegrationInfo,
+        storage: Option<Box<dyn Storage>>,
+        #[cfg(feature = "glow")] gl: Option<std::sync::Arc<glow::Context>>,
+        #[cfg(feature = "wgpu")] wgpu_render_state: Option<egui_wgpu::RenderState>,
+    ) -> Self {
+        Self {
+            info,
+            output: Default::default(),
+            storage,
+            #[cfg(feature = "glow")]
+            gl,
+            #[cfg(feature = "wgpu")]
+            wgpu_render_state,
+            screenshot: std::cell::Cell::new(None),
+            #[cfg(feature = "glow")]
+            screenshot_requests: Vec::new(),
+            #[cfg(feature = "glow")]
+            texture_upload_progress: Default::default(),
+            #[cfg(feature = "glow")]
+            upload_context: None,
+            #[cfg(feature = "recording")]
+            recorder: None,
+        }
+    }
+
     /// True if you are in a web environment.
     ///
     /// Equivalent to `cfg!(target_arch = "wasm32")`
@@ -720,12 +949,92 @@ impl Frame {
         self.output.screenshot_requested = true;
     }
 
+    /// Capture `region` of the window (in points), or all of it if `None`, once this frame is painted.
+    ///
+    /// Unlike [`Frame::request_screenshot`], this doesn't stall the GPU:
+    /// `callback` gets the image a frame or two later, once the GPU has finished drawing.
+    /// To capture a window or panel, pass the `rect` of its [`egui::Response`].
+    ///
+    /// Only supported by the glow renderer; with wgpu the request is dropped with a warning,
+    /// and `callback` is never called.
+    #[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+    pub fn request_screenshot_async(
+        &mut self,
+        region: Option<egui::Rect>,
+        callback: impl FnOnce(egui::ColorImage) + Send + 'static,
+    ) {
+        self.screenshot_requests.push((region, Box::new(callback)));
+    }
+
+    /// Record every frame painted from now on (or at [`crate::RecordingOptions::fps`])
+    /// until [`Frame::stop_recording`].
+    ///
+    /// Frames are read back like with [`Frame::request_screenshot_async`] and encoded on a background thread,
+    /// skipping frames when it falls behind. Any recording already in progress is stopped.
+    /// Fails if the output file or directory can't be created.
+    ///
+    /// Only supported by the glow renderer, with the `recording` feature.
+    #[cfg(all(feature = "recording", not(target_arch = "wasm32")))]
+    pub fn start_recording(&mut self, options: crate::RecordingOptions) -> crate::Result<()> {
+        self.recorder = Some(crate::native::recording::Recorder::start(options)?);
+        Ok(())
+    }
+
+    /// Stop recording. The returned [`crate::Recording`] tells you when the file is completely written.
+    ///
+    /// `None` if we weren't recording.
+    #[cfg(all(feature = "recording", not(target_arch = "wasm32")))]
+    pub fn stop_recording(&mut self) -> Option<crate::Recording> {
+        self.recorder.take().map(|recorder| recorder.stop())
+    }
+
+    /// Are we recording, see [`Frame::start_recording`]?
+    #[cfg(all(feature = "recording", not(target_arch = "wasm32")))]
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Capture the frame about to be painted, if recording.
+    #[cfg(all(feature = "recording", not(target_arch = "wasm32")))]
+    pub(crate) fn capture_recording_frame(&mut self) {
+        if let Some(request) = self
+            .recorder
+            .as_mut()
+            .and_then(|recorder| recorder.capture())
+        {
+            self.screenshot_requests.push(request);
+        }
+    }
+
     /// Cancel a request made with [`Frame::request_screenshot`].
     #[cfg(not(target_arch = "wasm32"))]
     pub fn cancel_screenshot_request(&mut self) {
         self.output.screenshot_requested = false;
     }
 
+    /// Preview how your app looks to people with color vision deficiencies.
+    ///
+    /// `None` turns the simulation off.
+    /// Only supported by the glow renderer; ignored when using wgpu.
+    #[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+    pub fn set_colorblind_simulation(
+        &mut self,
+        simulation: Option<egui_glow::ColorblindSimulation>,
+    ) {
+        self.output.colorblind_simulation = Some(simulation);
+    }
+
+    /// How much of a texture has been uploaded, from 0 to 1,
+    /// when [`NativeOptions::texture_upload_budget`] spreads uploads over several frames.
+    ///
+    /// `None` once the texture is ready. Until then, show a placeholder instead of the image.
+    /// This is the state after the previous frame was painted,
+    /// so textures you create during this frame are not included yet.
+    #[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+    pub fn texture_upload_progress(&self, texture_id: egui::TextureId) -> Option<f32> {
+        self.texture_upload_progress.get(&texture_id).copied()
+    }
+
     /// During [`App::post_rendering`], use this to retrieve the pixel data that was requested during
     /// [`App::update`] via [`Frame::request_screenshot`].
     ///
@@ -795,6 +1104,13 @@ impl Frame {
         self.gl.as_ref()
     }
 
+    /// A second OpenGL context for uploading textures on background threads,
+    /// if [`NativeOptions::upload_context`] is set and it could be created.
+    #[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+    pub fn upload_context(&self) -> Option<&crate::UploadContext> {
+        self.upload_context.as_ref()
+    }
+
     /// The underlying WGPU render state.
     ///
     /// Only available when compiling with the `wgpu` feature and using [`Renderer::Wgpu`].
@@ -941,6 +1257,9 @@ impl Frame {
     }
 }
 
+#[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+pub(crate) type ScreenshotCallback = Box<dyn FnOnce(egui::ColorImage) + Send>;
+
 /// Information about the web environment (if applicable).
 #[derive(Clone, Debug)]
 #[cfg(target_arch = "wasm32")]
@@ -1061,6 +1380,29 @@ pub struct IntegrationInfo {
     /// The position and size of the native window.
     #[cfg(not(target_arch = "wasm32"))]
     pub window_info: WindowInfo,
+
+    /// What the glow renderer did on the previous frame: draw calls, triangles, texture uploads, …
+    ///
+    /// `None` on the first frame, and when using wgpu.
+    #[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+    pub painter_stats: Option<egui_glow::PainterStats>,
+
+    /// How long the GPU spent on a recent frame, when [`NativeOptions::gpu_timing`] is on.
+    ///
+    /// The results arrive a few frames late. Unlike [`Self::cpu_usage`], this includes the time of your paint callbacks.
+    /// `None` until the first results are in, and when using wgpu.
+    #[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+    pub gpu_timings: Option<egui_glow::GpuTimings>,
+
+    /// When the frame being built will likely be shown on screen, estimated from recent frames.
+    ///
+    /// Animate to this time rather than to the start of the frame, e.g. to draw a pen stroke
+    /// where the pen will be: `ctx.input(|i| i.time) + predicted.saturating_duration_since(Instant::now()).as_secs_f64()`.
+    ///
+    /// `None` until eframe has an estimate of the display's refresh timing, which needs vsync,
+    /// with [`NativeOptions::pipelined_rendering`], and when using wgpu.
+    #[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+    pub predicted_present_time: Option<std::time::Instant>,
 }
 
 // ----------------------------------------------------------------------------
@@ -1184,5 +1526,9 @@ pub(crate) mod backend {
 
         #[cfg(not(target_arch = "wasm32"))]
         pub screenshot_requested: bool,
+
+        /// Set to some value to change the colorblind simulation of the glow renderer.
+        #[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+        pub colorblind_simulation: Option<Option<egui_glow::ColorblindSimulation>>,
     }
 }
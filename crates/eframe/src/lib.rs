@@ -159,6 +159,21 @@ pub use web::{WebLogger, WebRunner};
 #[cfg(any(feature = "glow", feature = "wgpu"))]
 pub mod native;
 
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(feature = "glow")]
+pub use native::UploadContext;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod testing;
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(feature = "input_recording")]
+pub use native::input_recording::{InputRecording, InputReplay};
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(feature = "recording")]
+pub use native::recording::{Recording, RecordingFormat, RecordingOptions, RecordingSummary};
+
 /// This is how you start a native (desktop) app.
 ///
 /// The first argument is name of your app, used for the title bar of the native window
@@ -302,6 +317,29 @@ pub enum Error {
     #[error("Found no glutin configs matching the template: {0:?}. error: {1:?}")]
     NoGlutinConfigs(glutin::config::ConfigTemplate, Box<dyn std::error::Error>),
 
+    #[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+    #[error("the OpenGL upload context was lost in an earlier error")]
+    UploadContextLost,
+
+    #[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+    #[error("the render thread panicked or lost the OpenGL context")]
+    RenderThreadLost,
+
+    #[cfg(all(
+        any(feature = "recording", feature = "input_recording"),
+        not(target_arch = "wasm32")
+    ))]
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[cfg(all(feature = "recording", not(target_arch = "wasm32")))]
+    #[error("image error: {0}")]
+    Image(#[from] image::ImageError),
+
+    #[cfg(all(feature = "input_recording", not(target_arch = "wasm32")))]
+    #[error("invalid input recording: {0}")]
+    InvalidInputRecording(String),
+
     #[cfg(feature = "wgpu")]
     #[error("WGPU error: {0}")]
     Wgpu(#[from] egui_wgpu::WgpuError),
@@ -247,12 +247,17 @@ pub fn handle_app_output(
         visible: _, // handled in post_present
         always_on_top,
         screenshot_requested: _, // handled by the rendering backend,
+        #[cfg(feature = "glow")]
+        colorblind_simulation,
         minimized,
         maximized,
         focus,
         attention,
     } = app_output;
 
+    #[cfg(feature = "glow")]
+    let _ = colorblind_simulation; // handled by the rendering backend
+
     if let Some(decorated) = decorated {
         window.set_decorations(decorated);
     }
@@ -344,6 +349,12 @@ pub struct EpiIntegration {
     window_state: WindowState,
     follow_system_theme: bool,
     app_icon_setter: super::app_icon::AppTitleIconSetter,
+    /// See [`crate::NativeOptions::record_input`].
+    #[cfg(feature = "input_recording")]
+    input_recorder: Option<super::input_recording::InputRecorder>,
+    /// See [`crate::NativeOptions::replay_input`].
+    #[cfg(feature = "input_recording")]
+    input_replayer: Option<super::input_recording::InputReplayer>,
 }
 
 impl EpiIntegration {
@@ -371,24 +382,26 @@ impl EpiIntegration {
             maximized: window.is_maximized(),
         };
 
-        let frame = epi::Frame {
-            info: epi::IntegrationInfo {
+        let mut frame = epi::Frame::new(
+            epi::IntegrationInfo {
                 system_theme,
                 cpu_usage: None,
                 native_pixels_per_point: Some(native_pixels_per_point),
                 window_info: read_window_info(window, egui_ctx.pixels_per_point(), &window_state),
-            },
-            output: epi::backend::AppOutput {
-                visible: Some(true),
-                ..Default::default()
+                #[cfg(feature = "glow")]
+                painter_stats: None,
+                #[cfg(feature = "glow")]
+                gpu_timings: None,
+                #[cfg(feature = "glow")]
+                predicted_present_time: None,
             },
             storage,
             #[cfg(feature = "glow")]
             gl,
             #[cfg(feature = "wgpu")]
             wgpu_render_state,
-            screenshot: std::cell::Cell::new(None),
-        };
+        );
+        frame.output.visible = Some(true);
 
         let mut egui_winit = egui_winit::State::new(event_loop);
         egui_winit.set_max_texture_side(max_texture_side);
@@ -399,6 +412,23 @@ impl EpiIntegration {
             native_options.icon_data.clone(),
         );
 
+        #[cfg(feature = "input_recording")]
+        let input_recorder = native_options.record_input.as_ref().and_then(|path| {
+            super::input_recording::InputRecorder::create(path)
+                .map_err(|err| log::error!("Failed to record input to {path:?}: {err}"))
+                .ok()
+        });
+        #[cfg(feature = "input_recording")]
+        let input_replayer = native_options.replay_input.as_ref().and_then(|replay| {
+            let replayer = super::input_recording::InputReplayer::load(replay)
+                .map_err(|err| log::error!("Failed to replay input from {:?}: {err}", replay.path))
+                .ok()?;
+            if let Some(size) = replayer.screen_size() {
+                window.set_inner_size(winit::dpi::LogicalSize::new(size.x, size.y));
+            }
+            Some(replayer)
+        });
+
         Self {
             frame,
             last_auto_save: std::time::Instant::now(),
@@ -410,6 +440,10 @@ impl EpiIntegration {
             window_state,
             follow_system_theme: native_options.follow_system_theme,
             app_icon_setter,
+            #[cfg(feature = "input_recording")]
+            input_recorder,
+            #[cfg(feature = "input_recording")]
+            input_replayer,
         }
     }
 
@@ -489,6 +523,32 @@ impl EpiIntegration {
         self.egui_winit.on_accesskit_action_request(request);
     }
 
+    /// Use the replayed input instead of `raw_input` and record it,
+    /// see [`crate::NativeOptions::replay_input`] and [`crate::NativeOptions::record_input`].
+    #[cfg(feature = "input_recording")]
+    fn replay_and_record_input(&mut self, mut raw_input: egui::RawInput) -> egui::RawInput {
+        if let Some(replayer) = &mut self.input_replayer {
+            if let Some(replayed) = replayer.next_frame(&raw_input) {
+                raw_input = replayed;
+            }
+            if replayer.is_done() {
+                log::info!("Finished replaying input");
+                if replayer.exit_at_end {
+                    self.close = true;
+                }
+                self.input_replayer = None;
+            }
+        }
+
+        if let Some(recorder) = &mut self.input_recorder {
+            if let Err(err) = recorder.record(&raw_input) {
+                log::error!("Failed to record input, stopping: {err}");
+                self.input_recorder = None;
+            }
+        }
+        raw_input
+    }
+
     pub fn update(
         &mut self,
         app: &mut dyn epi::App,
@@ -501,6 +561,8 @@ impl EpiIntegration {
         self.frame.info.window_info =
             read_window_info(window, self.egui_ctx.pixels_per_point(), &self.window_state);
         let raw_input = self.egui_winit.take_egui_input(window);
+        #[cfg(feature = "input_recording")]
+        let raw_input = self.replay_and_record_input(raw_input);
 
         // Run user code:
         let full_output = self.egui_ctx.run(raw_input, |egui_ctx| {
@@ -509,6 +571,11 @@ impl EpiIntegration {
         });
 
         self.pending_full_output.append(full_output);
+        #[cfg(feature = "input_recording")]
+        if self.input_replayer.is_some() {
+            // Replay the next frame right away:
+            self.pending_full_output.repaint_after = std::time::Duration::ZERO;
+        }
         let full_output = std::mem::take(&mut self.pending_full_output);
 
         {
@@ -521,6 +588,10 @@ impl EpiIntegration {
             }
             self.frame.output.visible = app_output.visible; // this is handled by post_present
             self.frame.output.screenshot_requested = app_output.screenshot_requested;
+            #[cfg(feature = "glow")]
+            {
+                self.frame.output.colorblind_simulation = app_output.colorblind_simulation;
+            }
             if self.frame.output.attention.is_some() {
                 self.frame.output.attention = None;
             }
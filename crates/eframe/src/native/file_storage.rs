@@ -37,8 +37,7 @@ impl FileStorage {
 
     /// Find a good place to put the files that the OS likes.
     pub fn from_app_name(app_name: &str) -> Option<Self> {
-        if let Some(proj_dirs) = directories_next::ProjectDirs::from("", "", app_name) {
-            let data_dir = proj_dirs.data_dir().to_path_buf();
+        if let Some(data_dir) = data_dir(app_name) {
             if let Err(err) = std::fs::create_dir_all(&data_dir) {
                 log::warn!(
                     "Saving disabled: Failed to create app path at {:?}: {}",
@@ -56,6 +55,12 @@ impl FileStorage {
     }
 }
 
+/// Where [`FileStorage::from_app_name`] keeps its files.
+pub(crate) fn data_dir(app_name: &str) -> Option<PathBuf> {
+    directories_next::ProjectDirs::from("", "", app_name)
+        .map(|proj_dirs| proj_dirs.data_dir().to_path_buf())
+}
+
 impl crate::Storage for FileStorage {
     fn get_string(&self, key: &str) -> Option<String> {
         self.kv.get(key).cloned()
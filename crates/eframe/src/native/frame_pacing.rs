@@ -0,0 +1,206 @@
+//! Estimating when frames reach the screen from when swapping buffers returns,
+//! see [`crate::NativeOptions::low_latency`].
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How many recent frames the estimates are based on.
+const HISTORY: usize = 32;
+
+/// Fewer refresh intervals than this are too few to estimate the refresh rate from.
+const MIN_INTERVALS: usize = 8;
+
+/// Presents further apart than this were idle, not consecutive vblanks.
+const MAX_REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Presents closer together than this didn't wait for a vblank.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_millis(2);
+
+/// How far past the last present we trust the vblank phase; clocks drift apart.
+const MAX_EXTRAPOLATION: Duration = Duration::from_secs(1);
+
+/// Extra time to leave before the vblank, since frames don't always take the same time.
+const SAFETY_MARGIN: Duration = Duration::from_micros(1500);
+
+/// Estimates the vblank timing of the display from the times swapping buffers returned,
+/// which under vsync is right after a vblank.
+#[derive(Default)]
+pub(crate) struct FramePacer {
+    last_present: Option<Instant>,
+
+    /// Recent intervals between consecutive presents.
+    intervals: VecDeque<Duration>,
+
+    /// How long recent frames took until they were ready to swap.
+    render_times: VecDeque<Duration>,
+
+    /// The median of [`Self::intervals`], once there are enough.
+    refresh_interval: Option<Duration>,
+}
+
+impl FramePacer {
+    /// A frame that took `render_time` to get ready to swap was presented when swapping returned at `presented`.
+    pub(crate) fn record_frame(&mut self, render_time: Duration, presented: Instant) {
+        push_bounded(&mut self.render_times, render_time);
+
+        if let Some(last_present) = self.last_present.replace(presented) {
+            let interval = presented.saturating_duration_since(last_present);
+            if (MIN_REFRESH_INTERVAL..MAX_REFRESH_INTERVAL).contains(&interval) {
+                push_bounded(&mut self.intervals, interval);
+            }
+        }
+
+        // The median ignores the occasional missed vblank:
+        self.refresh_interval = (self.intervals.len() >= MIN_INTERVALS).then(|| {
+            let mut intervals: Vec<Duration> = self.intervals.iter().copied().collect();
+            intervals.sort_unstable();
+            intervals[intervals.len() / 2]
+        });
+    }
+
+    /// The first estimated vblank at or after `time`, if we have a good enough estimate.
+    fn next_vblank(&self, time: Instant) -> Option<Instant> {
+        let refresh_interval = self.refresh_interval?.as_nanos();
+        let last_present = self.last_present?;
+        let since = time.saturating_duration_since(last_present);
+        if since > MAX_EXTRAPOLATION {
+            return None;
+        }
+        let vblanks = (since.as_nanos() + refresh_interval - 1) / refresh_interval;
+        Some(last_present + Duration::from_nanos((vblanks * refresh_interval) as u64))
+    }
+
+    /// How long to allow for a frame: the slowest recent one, and then some.
+    fn render_budget(&self) -> Option<Duration> {
+        let slowest = self.render_times.iter().max()?;
+        Some(*slowest + SAFETY_MARGIN)
+    }
+
+    /// When to start a frame that was requested for `requested`,
+    /// so it is ready just before the first vblank it can make.
+    ///
+    /// Returns `requested` if we can't tell.
+    pub(crate) fn start_time(&self, requested: Instant) -> Instant {
+        let Some(render_budget) = self.render_budget() else {
+            return requested;
+        };
+        let Some(ready) = requested.checked_add(render_budget) else {
+            return requested;
+        };
+        match self.next_vblank(ready) {
+            Some(vblank) => (vblank - render_budget).max(requested),
+            None => requested,
+        }
+    }
+
+    /// When a frame starting at `start` will likely be shown.
+    pub(crate) fn predicted_present_time(&self, start: Instant) -> Option<Instant> {
+        self.next_vblank(start.checked_add(self.render_budget()?)?)
+    }
+}
+
+fn push_bounded(queue: &mut VecDeque<Duration>, value: Duration) {
+    if queue.len() == HISTORY {
+        queue.pop_front();
+    }
+    queue.push_back(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REFRESH: Duration = Duration::from_micros(16_667);
+
+    /// A pacer that has seen `frames` frames presented at every vblank since `start`.
+    fn paced(start: Instant, frames: u32, render_time: Duration) -> FramePacer {
+        let mut pacer = FramePacer::default();
+        for i in 0..frames {
+            pacer.record_frame(render_time, start + REFRESH * i);
+        }
+        pacer
+    }
+
+    #[test]
+    fn no_estimate_from_too_few_frames() {
+        let start = Instant::now();
+        let pacer = paced(start, MIN_INTERVALS as u32, Duration::from_millis(2));
+        assert_eq!(pacer.refresh_interval, None);
+        let requested = start + REFRESH * 10;
+        assert_eq!(pacer.start_time(requested), requested);
+        assert_eq!(pacer.predicted_present_time(requested), None);
+    }
+
+    #[test]
+    fn next_vblank_rounds_up() {
+        let start = Instant::now();
+        let pacer = paced(start, 10, Duration::from_millis(2));
+        let last = start + REFRESH * 9;
+        assert_eq!(pacer.next_vblank(last), Some(last));
+        assert_eq!(
+            pacer.next_vblank(last + Duration::from_nanos(1)),
+            Some(last + REFRESH)
+        );
+        assert_eq!(pacer.next_vblank(last + REFRESH), Some(last + REFRESH));
+        assert_eq!(
+            pacer.next_vblank(last + REFRESH * 3 - Duration::from_nanos(1)),
+            Some(last + REFRESH * 3)
+        );
+        // Before the last present:
+        assert_eq!(pacer.next_vblank(start), Some(last));
+        // Too far to trust:
+        assert_eq!(pacer.next_vblank(last + MAX_EXTRAPOLATION + REFRESH), None);
+    }
+
+    #[test]
+    fn missed_vblanks_do_not_change_the_refresh_interval() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::default();
+        let mut presented = start;
+        for i in 0..20 {
+            // Every third frame misses a vblank:
+            presented += if i % 3 == 0 { REFRESH * 2 } else { REFRESH };
+            pacer.record_frame(Duration::from_millis(2), presented);
+        }
+        assert_eq!(pacer.refresh_interval, Some(REFRESH));
+
+        // Idle gaps and frames that didn't wait for a vblank are ignored:
+        pacer.record_frame(Duration::from_millis(2), presented + Duration::from_secs(5));
+        pacer.record_frame(
+            Duration::from_millis(2),
+            presented + Duration::from_secs(5) + Duration::from_micros(100),
+        );
+        assert_eq!(pacer.refresh_interval, Some(REFRESH));
+    }
+
+    #[test]
+    fn start_time_leaves_the_render_budget_before_the_vblank() {
+        let start = Instant::now();
+        let render_time = Duration::from_millis(4);
+        let pacer = paced(start, 10, render_time);
+        let last = start + REFRESH * 9;
+        let start_time = pacer.start_time(last + Duration::from_millis(1));
+        assert_eq!(start_time, last + REFRESH - render_time - SAFETY_MARGIN);
+        assert_eq!(
+            pacer.predicted_present_time(start_time),
+            Some(last + REFRESH)
+        );
+    }
+
+    #[test]
+    fn start_time_is_never_earlier_than_requested() {
+        let start = Instant::now();
+        for render_time in [
+            Duration::ZERO,
+            Duration::from_millis(4),
+            REFRESH - SAFETY_MARGIN,
+            REFRESH * 3,
+        ] {
+            let pacer = paced(start, 10, render_time);
+            for i in 0..1000 {
+                let requested = start + Duration::from_micros(397 * i);
+                assert!(pacer.start_time(requested) >= requested);
+            }
+        }
+    }
+}
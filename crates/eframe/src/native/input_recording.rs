@@ -0,0 +1,280 @@
+//! Recording the input of every frame and replaying it later,
+//! see [`crate::NativeOptions::record_input`] and [`crate::NativeOptions::replay_input`].
+
+use std::fs::File;
+use std::io::{BufWriter, Write as _};
+use std::path::{Path, PathBuf};
+
+/// The first line of a recording.
+const HEADER: &str = "# eframe input recording 1: one egui::RawInput per line, in RON";
+
+/// The input eframe gave egui, frame by frame.
+///
+/// Record it with [`crate::NativeOptions::record_input`] and replay it with [`crate::NativeOptions::replay_input`],
+/// or feed the frames to [`egui::Context::run`] yourself, e.g. in a regression test.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputRecording {
+    pub frames: Vec<egui::RawInput>,
+}
+
+impl InputRecording {
+    pub fn load(path: impl AsRef<Path>) -> crate::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parse the contents of a recording.
+    ///
+    /// A broken last line is ignored, since it was probably cut short by a crash.
+    pub fn parse(text: &str) -> crate::Result<Self> {
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(crate::Error::InvalidInputRecording(
+                "not an eframe input recording".to_owned(),
+            ));
+        }
+
+        let lines: Vec<(usize, &str)> = lines
+            .enumerate()
+            .map(|(i, line)| (i + 2, line))
+            .filter(|(_, line)| !line.trim().is_empty())
+            .collect();
+        let mut frames = Vec::with_capacity(lines.len());
+        for (i, &(line_nr, line)) in lines.iter().enumerate() {
+            match ron::from_str(line) {
+                Ok(raw_input) => frames.push(raw_input),
+                Err(err) if i + 1 == lines.len() => {
+                    log::warn!(
+                        "Ignoring the broken last line {line_nr} of the input recording: {err}"
+                    );
+                }
+                Err(err) => {
+                    return Err(crate::Error::InvalidInputRecording(format!(
+                        "line {line_nr}: {err}"
+                    )));
+                }
+            }
+        }
+        Ok(Self { frames })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> crate::Result<()> {
+        let mut recorder = InputRecorder::create(path.as_ref())?;
+        for raw_input in &self.frames {
+            recorder.record(raw_input)?;
+        }
+        Ok(())
+    }
+}
+
+/// How to replay an [`InputRecording`], see [`crate::NativeOptions::replay_input`].
+#[derive(Clone, Debug)]
+pub struct InputReplay {
+    /// A file written with [`crate::NativeOptions::record_input`].
+    pub path: PathBuf,
+
+    /// How many seconds the clock advances each frame.
+    ///
+    /// The app sees this clock rather than the recorded times,
+    /// so animations replay the same way however fast the computer is.
+    ///
+    /// Default: `1.0 / 60.0`.
+    pub dt: f32,
+
+    /// Close the app after the last recorded frame, rather than going on with live input.
+    ///
+    /// Default: `false`.
+    pub exit_at_end: bool,
+}
+
+impl InputReplay {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            dt: 1.0 / 60.0,
+            exit_at_end: false,
+        }
+    }
+}
+
+/// Writes the input of each frame as it happens.
+pub(crate) struct InputRecorder {
+    file: BufWriter<File>,
+}
+
+impl InputRecorder {
+    pub(crate) fn create(path: &Path) -> crate::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "{HEADER}")?;
+        Ok(Self { file })
+    }
+
+    /// Write the input of one frame, flushing right away so the recording survives a crash.
+    pub(crate) fn record(&mut self, raw_input: &egui::RawInput) -> crate::Result<()> {
+        let line = ron::to_string(raw_input)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        writeln!(self.file, "{line}")?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Hands out the recorded frames, on a fixed-step clock.
+pub(crate) struct InputReplayer {
+    frames: std::vec::IntoIter<egui::RawInput>,
+    frame_nr: u64,
+    dt: f32,
+    pub exit_at_end: bool,
+}
+
+impl InputReplayer {
+    pub(crate) fn load(replay: &InputReplay) -> crate::Result<Self> {
+        let InputRecording { frames } = InputRecording::load(&replay.path)?;
+        Ok(Self {
+            frames: frames.into_iter(),
+            frame_nr: 0,
+            dt: replay.dt,
+            exit_at_end: replay.exit_at_end,
+        })
+    }
+
+    /// The size of the recorded screen, in points.
+    pub(crate) fn screen_size(&self) -> Option<egui::Vec2> {
+        let first = self.frames.as_slice().first()?;
+        Some(first.screen_rect?.size())
+    }
+
+    /// The next recorded frame, to use instead of the `live` one. `None` at the end.
+    pub(crate) fn next_frame(&mut self, live: &egui::RawInput) -> Option<egui::RawInput> {
+        let mut raw_input = self.frames.next()?;
+        raw_input.time = Some(self.frame_nr as f64 * self.dt as f64);
+        raw_input.predicted_dt = self.dt;
+        // What this GPU supports:
+        raw_input.max_texture_side = live.max_texture_side;
+        self.frame_nr += 1;
+        Some(raw_input)
+    }
+
+    pub(crate) fn is_done(&self) -> bool {
+        self.frames.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames() -> Vec<egui::RawInput> {
+        let first = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                egui::Pos2::ZERO,
+                egui::vec2(800.0, 600.0),
+            )),
+            pixels_per_point: Some(1.5),
+            time: Some(12.5),
+            events: vec![
+                egui::Event::Text("a \"quoted\"\nline".to_owned()),
+                egui::Event::Key {
+                    key: egui::Key::A,
+                    pressed: true,
+                    repeat: false,
+                    modifiers: egui::Modifiers::CTRL,
+                },
+                egui::Event::PointerMoved(egui::pos2(1.5, 2.0)),
+            ],
+            ..Default::default()
+        };
+        let second = egui::RawInput {
+            time: Some(12.6),
+            ..Default::default()
+        };
+        vec![first, second]
+    }
+
+    fn text(frames: &[egui::RawInput]) -> String {
+        let mut text = format!("{HEADER}\n");
+        for raw_input in frames {
+            text += &ron::to_string(raw_input).unwrap();
+            text += "\n";
+        }
+        text
+    }
+
+    fn replayer(frames: Vec<egui::RawInput>, dt: f32) -> InputReplayer {
+        InputReplayer {
+            frames: frames.into_iter(),
+            frame_nr: 0,
+            dt,
+            exit_at_end: false,
+        }
+    }
+
+    #[test]
+    fn parse_needs_the_header() {
+        let text = text(&frames());
+        assert_eq!(InputRecording::parse(&text).unwrap().frames, frames());
+        let without_header = text.split_once('\n').unwrap().1;
+        assert!(InputRecording::parse(without_header).is_err());
+        assert!(InputRecording::parse("").is_err());
+    }
+
+    #[test]
+    fn parse_skips_empty_lines() {
+        let text = text(&frames()).replace('\n', "\n\n");
+        assert_eq!(InputRecording::parse(&text).unwrap().frames, frames());
+    }
+
+    #[test]
+    fn parse_ignores_a_broken_last_line() {
+        let text = text(&frames());
+        let cut_short = &text[..text.len() - 10];
+        assert_eq!(
+            InputRecording::parse(cut_short).unwrap().frames,
+            frames()[..1]
+        );
+    }
+
+    #[test]
+    fn parse_rejects_a_broken_line_in_the_middle() {
+        let text = text(&frames());
+        let (header, rest) = text.split_once('\n').unwrap();
+        let broken = format!("{header}\n(broken\n{rest}");
+        let Err(crate::Error::InvalidInputRecording(err)) = InputRecording::parse(&broken) else {
+            panic!("expected an error");
+        };
+        assert!(err.starts_with("line 2:"), "{err}");
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("eframe_input_recording_{}.ron", std::process::id()));
+        let recording = InputRecording { frames: frames() };
+        recording.save(&path).unwrap();
+        let loaded = InputRecording::load(&path);
+        std::fs::remove_file(&path).ok();
+        assert_eq!(loaded.unwrap(), recording);
+    }
+
+    #[test]
+    fn replay_uses_a_fixed_step_clock() {
+        let mut replayer = replayer(frames(), 0.25);
+        assert_eq!(replayer.screen_size(), Some(egui::vec2(800.0, 600.0)));
+        let live = egui::RawInput {
+            max_texture_side: Some(4096),
+            time: Some(1000.0),
+            ..Default::default()
+        };
+
+        let first = replayer.next_frame(&live).unwrap();
+        assert_eq!(first.time, Some(0.0));
+        assert_eq!(first.predicted_dt, 0.25);
+        assert_eq!(first.max_texture_side, Some(4096));
+        assert_eq!(first.events, frames()[0].events);
+        assert!(!replayer.is_done());
+
+        let second = replayer.next_frame(&live).unwrap();
+        assert_eq!(second.time, Some(0.25));
+        assert!(replayer.is_done());
+        assert_eq!(replayer.next_frame(&live), None);
+    }
+}
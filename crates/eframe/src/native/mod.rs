@@ -2,6 +2,21 @@ mod app_icon;
 pub mod epi_integration;
 pub mod run;
 
+#[cfg(feature = "glow")]
+mod frame_pacing;
+#[cfg(feature = "input_recording")]
+pub(crate) mod input_recording;
+#[cfg(feature = "glow")]
+mod present;
+#[cfg(feature = "recording")]
+pub(crate) mod recording;
+#[cfg(feature = "glow")]
+mod render_thread;
+#[cfg(feature = "glow")]
+mod upload_context;
+#[cfg(feature = "glow")]
+pub use upload_context::UploadContext;
+
 /// File storage which can be used by native backends.
 #[cfg(feature = "persistence")]
 pub mod file_storage;
@@ -0,0 +1,47 @@
+//! Presenting only the regions of a frame that changed, see [`crate::NativeOptions::damage_tracking`].
+
+use glutin::prelude::*;
+
+/// Swap buffers, telling the compositor that only `damage` changed, where supported.
+///
+/// `damage` is in physical pixels from the top left of the surface, which is `height` pixels high,
+/// like [`egui_glow::Painter::damage`]. It must cover everything that differs from the previous frame;
+/// the painter copies at least that to the back buffer, and more if the back buffer is older
+/// (see [`egui_glow::Painter::set_target_buffer_age`]).
+pub(crate) fn swap_buffers_with_damage(
+    surface: &glutin::surface::Surface<glutin::surface::WindowSurface>,
+    context: &glutin::context::PossiblyCurrentContext,
+    damage: &[egui::Rect],
+    height: u32,
+) -> glutin::error::Result<()> {
+    #[cfg(all(any(windows, unix), not(any(target_os = "macos", target_os = "ios"))))]
+    if let (
+        glutin::surface::Surface::Egl(surface),
+        glutin::context::PossiblyCurrentContext::Egl(context),
+    ) = (surface, context)
+    {
+        // EGL counts from the bottom left:
+        let rects: Vec<glutin::surface::Rect> = damage
+            .iter()
+            .map(|rect| {
+                glutin::surface::Rect::new(
+                    rect.min.x as i32,
+                    height as i32 - rect.max.y as i32,
+                    rect.width() as i32,
+                    rect.height() as i32,
+                )
+            })
+            .collect();
+        match surface.swap_buffers_with_damage(context, &rects) {
+            Ok(()) => return Ok(()),
+            Err(err) => {
+                // e.g. the extension is missing; the whole frame is in the back buffer anyway.
+                log::trace!("swap_buffers_with_damage failed, swapping the whole surface: {err}");
+            }
+        }
+    }
+    #[cfg(not(all(any(windows, unix), not(any(target_os = "macos", target_os = "ios")))))]
+    let _ = (damage, height);
+
+    surface.swap_buffers(context)
+}
@@ -0,0 +1,449 @@
+//! Recording the app to a PNG sequence, APNG or GIF, see [`crate::Frame::start_recording`].
+
+use std::fs::File;
+use std::io::{BufWriter, Read as _, Seek as _, SeekFrom, Write as _};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use crate::epi::ScreenshotCallback;
+
+/// How a recording is stored, see [`RecordingOptions`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordingFormat {
+    /// One PNG file per frame, `frame_00000.png`, `frame_00001.png`, …, in the directory [`RecordingOptions::path`].
+    PngSequence,
+
+    /// An animated PNG: lossless, and plays in browsers.
+    Apng,
+
+    /// An animated GIF, with at most 256 colors per frame. Encoding is slow.
+    Gif,
+}
+
+/// What and how to record, see [`crate::Frame::start_recording`].
+#[derive(Clone, Debug)]
+pub struct RecordingOptions {
+    /// The file to write, or for [`RecordingFormat::PngSequence`] the directory to write the frames to.
+    pub path: PathBuf,
+
+    pub format: RecordingFormat,
+
+    /// Record at most this many frames per second.
+    ///
+    /// Default: `None` (record every presented frame).
+    pub fps: Option<f32>,
+
+    /// Only record this part of the window, in points.
+    ///
+    /// Default: `None` (the whole window).
+    pub region: Option<egui::Rect>,
+
+    /// How many captured frames may wait for the encoder. When the encoder falls behind,
+    /// further frames are skipped, rather than using ever more memory.
+    ///
+    /// Default: `8`.
+    pub max_queued_frames: usize,
+}
+
+impl RecordingOptions {
+    pub fn new(path: impl Into<PathBuf>, format: RecordingFormat) -> Self {
+        Self {
+            path: path.into(),
+            format,
+            fps: None,
+            region: None,
+            max_queued_frames: 8,
+        }
+    }
+}
+
+/// What ended up in a finished recording.
+#[derive(Clone, Debug)]
+pub struct RecordingSummary {
+    /// How many frames were written.
+    pub frames: usize,
+
+    /// How many frames were skipped because the encoder fell behind.
+    pub skipped_frames: usize,
+
+    /// From the first to the end of the last frame.
+    pub duration: Duration,
+}
+
+/// A recording that is being finished on a background thread, see [`crate::Frame::stop_recording`].
+pub struct Recording {
+    thread: std::thread::JoinHandle<crate::Result<RecordingSummary>>,
+}
+
+impl Recording {
+    /// Has the encoder written everything?
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Block until the encoder has written everything.
+    pub fn wait(self) -> crate::Result<RecordingSummary> {
+        match self.thread.join() {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+struct CapturedFrame {
+    image: egui::ColorImage,
+    time: Instant,
+}
+
+/// Captures frames for a recording in progress, and sends them to the encoder thread.
+pub(crate) struct Recorder {
+    frames: mpsc::SyncSender<CapturedFrame>,
+    region: Option<egui::Rect>,
+    min_interval: Duration,
+    last_capture: Option<Instant>,
+    skipped_frames: Arc<AtomicUsize>,
+    thread: std::thread::JoinHandle<crate::Result<RecordingSummary>>,
+}
+
+impl Recorder {
+    /// Create the output right away, so that problems with the path show up here.
+    pub(crate) fn start(options: RecordingOptions) -> crate::Result<Self> {
+        let RecordingOptions {
+            path,
+            format,
+            fps,
+            region,
+            max_queued_frames,
+        } = options;
+
+        let sink = match format {
+            RecordingFormat::PngSequence => {
+                std::fs::create_dir_all(&path)?;
+                Sink::PngSequence(path)
+            }
+            RecordingFormat::Apng => Sink::Apng {
+                // Readable too, to update the frame count at the end:
+                file: std::fs::OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(&path)?,
+                writer: None,
+            },
+            RecordingFormat::Gif => Sink::Gif {
+                file: Some(File::create(&path)?),
+                encoder: None,
+            },
+        };
+
+        let (sender, receiver) = mpsc::sync_channel(max_queued_frames);
+        let skipped_frames = Arc::new(AtomicUsize::new(0));
+        let thread = std::thread::Builder::new()
+            .name("eframe_recording".to_owned())
+            .spawn({
+                let skipped_frames = skipped_frames.clone();
+                move || encode(&receiver, sink, &skipped_frames)
+            })?;
+
+        Ok(Self {
+            frames: sender,
+            region,
+            min_interval: fps.map_or(Duration::ZERO, |fps| {
+                Duration::from_secs_f32(1.0 / fps.max(f32::EPSILON))
+            }),
+            last_capture: None,
+            skipped_frames,
+            thread,
+        })
+    }
+
+    /// A screenshot request for the frame being painted now, if it is time to capture one.
+    pub(crate) fn capture(&mut self) -> Option<(Option<egui::Rect>, ScreenshotCallback)> {
+        let time = Instant::now();
+        if let Some(last_capture) = self.last_capture {
+            if time.saturating_duration_since(last_capture) < self.min_interval {
+                return None;
+            }
+        }
+        self.last_capture = Some(time);
+
+        let frames = self.frames.clone();
+        let skipped_frames = self.skipped_frames.clone();
+        let callback = move |image| {
+            if frames.try_send(CapturedFrame { image, time }).is_err() {
+                skipped_frames.fetch_add(1, Ordering::Relaxed);
+            }
+        };
+        Some((self.region, Box::new(callback)))
+    }
+
+    /// Let the encoder finish once the frames still being read back have arrived.
+    pub(crate) fn stop(self) -> Recording {
+        Recording {
+            thread: self.thread,
+        }
+    }
+}
+
+enum Sink {
+    PngSequence(PathBuf),
+    Apng {
+        file: File,
+        /// Created with the first frame, which decides the size.
+        writer: Option<png::Writer<BufWriter<File>>>,
+    },
+    Gif {
+        file: Option<File>,
+        encoder: Option<image::codecs::gif::GifEncoder<BufWriter<File>>>,
+    },
+}
+
+/// The frame count we tell the APNG encoder, before we know it.
+const APNG_FRAMES_PLACEHOLDER: u32 = u32::MAX >> 1;
+
+impl Sink {
+    fn write(
+        &mut self,
+        index: usize,
+        image: &image::RgbaImage,
+        delay: Duration,
+    ) -> crate::Result<()> {
+        crate::profile_function!();
+        match self {
+            Self::PngSequence(directory) => {
+                image.save_with_format(
+                    directory.join(format!("frame_{index:05}.png")),
+                    image::ImageFormat::Png,
+                )?;
+            }
+            Self::Apng { file, writer } => {
+                let writer = match writer {
+                    Some(writer) => writer,
+                    None => {
+                        let mut encoder = png::Encoder::new(
+                            BufWriter::new(file.try_clone()?),
+                            image.width(),
+                            image.height(),
+                        );
+                        encoder.set_color(png::ColorType::Rgba);
+                        encoder.set_depth(png::BitDepth::Eight);
+                        encoder
+                            .set_animated(APNG_FRAMES_PLACEHOLDER, 0)
+                            .map_err(std::io::Error::from)?;
+                        writer.insert(encoder.write_header().map_err(std::io::Error::from)?)
+                    }
+                };
+                let delay_ms = delay.as_millis().min(u16::MAX as u128) as u16;
+                writer
+                    .set_frame_delay(delay_ms, 1000)
+                    .map_err(std::io::Error::from)?;
+                writer
+                    .write_image_data(image.as_raw())
+                    .map_err(std::io::Error::from)?;
+            }
+            Self::Gif { file, encoder } => {
+                let encoder = match encoder {
+                    Some(encoder) => encoder,
+                    None => {
+                        let file = file.take().expect("GIF file already taken");
+                        let mut gif = image::codecs::gif::GifEncoder::new_with_speed(
+                            BufWriter::new(file),
+                            10,
+                        );
+                        gif.set_repeat(image::codecs::gif::Repeat::Infinite)?;
+                        encoder.insert(gif)
+                    }
+                };
+                encoder.encode_frame(image::Frame::from_parts(
+                    image.clone(),
+                    0,
+                    0,
+                    image::Delay::from_saturating_duration(delay),
+                ))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self, frames: usize) -> crate::Result<()> {
+        match self {
+            Self::PngSequence(_) => {}
+            Self::Apng { mut file, writer } => {
+                if let Some(writer) = writer {
+                    writer.finish().map_err(std::io::Error::from)?;
+                    set_apng_frame_count(&mut file, frames as u32)?;
+                }
+            }
+            Self::Gif { encoder, .. } => {
+                // Writes the trailer:
+                drop(encoder);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Replace [`APNG_FRAMES_PLACEHOLDER`] with the actual number of frames.
+fn set_apng_frame_count(file: &mut File, frames: u32) -> std::io::Result<()> {
+    // The `acTL` chunk comes right after `IHDR`, near the start:
+    let mut header = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    std::io::Read::by_ref(file)
+        .take(1024)
+        .read_to_end(&mut header)?;
+    let Some(chunk_type) = header.windows(4).position(|bytes| bytes == b"acTL") else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "no acTL chunk in APNG",
+        ));
+    };
+
+    // Type, frame count, play count, CRC of type and data:
+    let mut chunk = header[chunk_type..chunk_type + 12].to_vec();
+    chunk[4..8].copy_from_slice(&frames.to_be_bytes());
+    let crc = crc32(&chunk);
+    chunk.extend_from_slice(&crc.to_be_bytes());
+
+    file.seek(SeekFrom::Start(chunk_type as u64))?;
+    file.write_all(&chunk)?;
+    file.flush()
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (!(crc & 1)).wrapping_add(1));
+        }
+    }
+    !crc
+}
+
+/// Write the frames as they arrive, until the recording stops.
+fn encode(
+    frames: &mpsc::Receiver<CapturedFrame>,
+    mut sink: Sink,
+    skipped_frames: &AtomicUsize,
+) -> crate::Result<RecordingSummary> {
+    let mut written = 0;
+    let mut size = None;
+    let mut first_time = None;
+
+    // We need the time of the next frame to know how long to show a frame:
+    let mut pending: Option<(image::RgbaImage, Instant)> = None;
+    let mut last_delay = Duration::from_millis(100);
+    for CapturedFrame { image, time } in frames {
+        let size = *size.get_or_insert(image.size);
+        first_time.get_or_insert(time);
+        if let Some((previous, previous_time)) = pending.take() {
+            last_delay = time.saturating_duration_since(previous_time);
+            sink.write(written, &previous, last_delay)?;
+            written += 1;
+        }
+        pending = Some((fit(&image, size), time));
+    }
+
+    let mut duration = Duration::ZERO;
+    if let Some((last, last_time)) = pending {
+        sink.write(written, &last, last_delay)?;
+        written += 1;
+        duration =
+            last_time.saturating_duration_since(first_time.unwrap_or(last_time)) + last_delay;
+    }
+    sink.finish(written)?;
+
+    Ok(RecordingSummary {
+        frames: written,
+        skipped_frames: skipped_frames.load(Ordering::Relaxed),
+        duration,
+    })
+}
+
+/// All frames have the size of the first one, so crop or pad the others, e.g. after a resize.
+fn fit(image: &egui::ColorImage, [width, height]: [usize; 2]) -> image::RgbaImage {
+    let mut fitted = image::RgbaImage::new(width as u32, height as u32);
+    for y in 0..height.min(image.height()) {
+        for x in 0..width.min(image.width()) {
+            let color = image.pixels[y * image.width() + x];
+            fitted.put_pixel(
+                x as u32,
+                y as u32,
+                image::Rgba(color.to_srgba_unmultiplied()),
+            );
+        }
+    }
+    fitted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_matches_png() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        // The CRC of every PNG's last chunk:
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+    }
+
+    #[test]
+    fn fit_crops_and_pads() {
+        let mut image = egui::ColorImage::new([3, 2], egui::Color32::RED);
+        image.pixels[2] = egui::Color32::BLUE;
+
+        let cropped = fit(&image, [2, 1]);
+        assert_eq!(cropped.dimensions(), (2, 1));
+        assert_eq!(cropped.get_pixel(1, 0), &image::Rgba([255, 0, 0, 255]));
+
+        let padded = fit(&image, [4, 3]);
+        assert_eq!(padded.dimensions(), (4, 3));
+        assert_eq!(padded.get_pixel(2, 0), &image::Rgba([0, 0, 255, 255]));
+        assert_eq!(padded.get_pixel(2, 1), &image::Rgba([255, 0, 0, 255]));
+        assert_eq!(padded.get_pixel(3, 0), &image::Rgba([0, 0, 0, 0]));
+        assert_eq!(padded.get_pixel(0, 2), &image::Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn apng_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("eframe_apng_round_trip_{}.png", std::process::id()));
+        let colors = [
+            egui::Color32::RED,
+            egui::Color32::GREEN,
+            egui::Color32::BLUE,
+        ];
+
+        let recorder =
+            Recorder::start(RecordingOptions::new(&path, RecordingFormat::Apng)).unwrap();
+        let start = Instant::now();
+        for (i, color) in colors.into_iter().enumerate() {
+            recorder
+                .frames
+                .send(CapturedFrame {
+                    image: egui::ColorImage::new([4, 3], color),
+                    time: start + Duration::from_millis(50 * i as u64),
+                })
+                .unwrap();
+        }
+        let summary = recorder.stop().wait().unwrap();
+        assert_eq!(summary.frames, 3);
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let animation = reader.info().animation_control.unwrap();
+        assert_eq!(animation.num_frames, 3);
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        for color in colors {
+            let frame = reader.next_frame(&mut buffer).unwrap();
+            assert_eq!((frame.width, frame.height), (4, 3));
+            assert_eq!(buffer[..4], color.to_srgba_unmultiplied());
+            let delay = reader.info().frame_control.unwrap();
+            assert_eq!((delay.delay_num, delay.delay_den), (50, 1000));
+        }
+        std::fs::remove_file(&path).ok();
+    }
+}
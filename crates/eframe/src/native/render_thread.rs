@@ -0,0 +1,302 @@
+#![allow(unsafe_code)]
+
+//! Tessellating, painting and presenting on a thread of its own,
+//! see [`crate::NativeOptions::pipelined_rendering`].
+
+use std::collections::HashMap;
+use std::sync::mpsc;
+
+use egui::NumExt as _;
+use glutin::prelude::*;
+
+use crate::epi::ScreenshotCallback;
+
+/// Tessellates the shapes of a frame on the render thread.
+pub(crate) type Tessellate = Box<dyn FnOnce() -> Vec<egui::ClippedPrimitive> + Send>;
+
+/// Prepare to tessellate `shapes` later, with the settings `egui_ctx` has now.
+///
+/// Like [`egui::Context::tessellate`], but the work can be done on another thread
+/// while `egui_ctx` runs the next frame.
+pub(crate) fn tessellate_later(
+    egui_ctx: &egui::Context,
+    shapes: Vec<egui::epaint::ClippedShape>,
+) -> Tessellate {
+    let pixels_per_point = egui_ctx.pixels_per_point();
+    let tessellation_options = egui_ctx.options(|options| options.tessellation_options);
+    let (font_tex_size, prepared_discs) = egui_ctx.fonts(|fonts| {
+        let atlas = fonts.texture_atlas();
+        let atlas = atlas.lock();
+        (atlas.size(), atlas.prepared_discs())
+    });
+    Box::new(move || {
+        egui::epaint::tessellate_shapes(
+            pixels_per_point,
+            tessellation_options,
+            font_tex_size,
+            prepared_discs,
+            shapes,
+        )
+    })
+}
+
+/// Everything the render thread needs to paint one frame.
+pub(crate) struct FrameJob {
+    pub screen_size_in_pixels: [u32; 2],
+    pub clear_color: [f32; 4],
+    pub pixels_per_point: f32,
+    pub tessellate: Tessellate,
+    pub textures_delta: egui::TexturesDelta,
+    pub colorblind_simulation: Option<Option<egui_glow::ColorblindSimulation>>,
+    pub screenshot_requested: bool,
+    pub screenshot_requests: Vec<(Option<egui::Rect>, ScreenshotCallback)>,
+}
+
+/// What the render thread reports back about a painted frame.
+pub(crate) struct FrameReport {
+    texture_upload_progress: HashMap<egui::TextureId, f32>,
+    painter_stats: egui_glow::PainterStats,
+    gpu_timings: Option<egui_glow::GpuTimings>,
+    screenshot: Option<egui::ColorImage>,
+}
+
+impl FrameReport {
+    /// Tell the app, like painting on the event loop thread does.
+    pub(crate) fn apply_to(self, frame: &mut crate::Frame) {
+        frame.texture_upload_progress = self.texture_upload_progress;
+        frame.info.painter_stats = Some(self.painter_stats);
+        frame.info.gpu_timings = self.gpu_timings;
+        if let Some(screenshot) = self.screenshot {
+            frame.screenshot.set(Some(screenshot));
+        }
+    }
+}
+
+/// What the render thread owns while it runs.
+pub(crate) struct RenderState {
+    pub painter: egui_glow::Painter,
+    pub context: glutin::context::NotCurrentContext,
+    pub surface: RenderSurface,
+}
+
+/// The window surface, moved to the render thread together with its context.
+pub(crate) struct RenderSurface(glutin::surface::Surface<glutin::surface::WindowSurface>);
+
+// SAFETY: the surface is only used together with its context, which is not current while they move,
+// and by one thread at a time.
+unsafe impl Send for RenderSurface {}
+
+impl RenderSurface {
+    pub(crate) fn new(surface: glutin::surface::Surface<glutin::surface::WindowSurface>) -> Self {
+        Self(surface)
+    }
+
+    pub(crate) fn into_inner(self) -> glutin::surface::Surface<glutin::surface::WindowSurface> {
+        self.0
+    }
+}
+
+type RenderResult<T> = Result<T, glutin::error::Error>;
+
+/// A thread that owns the OpenGL context, painting the frames the event loop thread sends it in order.
+pub(crate) struct RenderThread {
+    jobs: Option<mpsc::SyncSender<FrameJob>>,
+    /// A failure to make the context current ends the thread, and is its last report.
+    reports: mpsc::Receiver<RenderResult<FrameReport>>,
+    thread: Option<std::thread::JoinHandle<RenderResult<RenderState>>>,
+
+    /// Frames sent whose reports we haven't received yet.
+    in_flight: usize,
+}
+
+impl RenderThread {
+    /// At most `queue_depth` frames wait for the render thread, besides the one it is painting.
+    ///
+    /// The render thread requests a repaint of `egui_ctx` while textures are uploading or readbacks are pending.
+    pub(crate) fn spawn(state: RenderState, queue_depth: usize, egui_ctx: egui::Context) -> Self {
+        let (job_sender, job_receiver) = mpsc::sync_channel(queue_depth);
+        let (report_sender, report_receiver) = mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("eframe_render".to_owned())
+            .spawn(move || render_loop(state, &job_receiver, &report_sender, &egui_ctx))
+            .expect("failed to spawn render thread");
+        Self {
+            jobs: Some(job_sender),
+            reports: report_receiver,
+            thread: Some(thread),
+            in_flight: 0,
+        }
+    }
+
+    /// Queue a frame, blocking while the queue is full.
+    pub(crate) fn submit(&mut self, job: FrameJob) {
+        crate::profile_function!();
+        let Some(jobs) = &self.jobs else { return };
+        if jobs.send(job).is_ok() {
+            self.in_flight += 1;
+        } else {
+            log::error!("the render thread stopped unexpectedly");
+        }
+    }
+
+    /// The reports of the frames painted since the last call, oldest first.
+    pub(crate) fn reports(&mut self) -> RenderResult<Vec<FrameReport>> {
+        let mut reports = Vec::new();
+        while let Ok(report) = self.reports.try_recv() {
+            self.in_flight = self.in_flight.saturating_sub(1);
+            reports.push(report?);
+        }
+        Ok(reports)
+    }
+
+    /// Wait until all queued frames are painted, and return their reports, oldest first.
+    pub(crate) fn finish(&mut self) -> RenderResult<Vec<FrameReport>> {
+        crate::profile_function!();
+        let mut reports = Vec::new();
+        while self.in_flight > 0 {
+            let Ok(report) = self.reports.recv() else {
+                self.in_flight = 0;
+                break;
+            };
+            self.in_flight -= 1;
+            reports.push(report?);
+        }
+        Ok(reports)
+    }
+
+    /// Stop the thread once it has painted the queued frames, and take back what it owns.
+    ///
+    /// Fails if the thread lost the context, in which case it is gone.
+    pub(crate) fn join(mut self) -> crate::Result<RenderState> {
+        drop(self.jobs.take());
+        let Some(thread) = self.thread.take() else {
+            return Err(crate::Error::RenderThreadLost);
+        };
+        match thread.join() {
+            Ok(result) => Ok(result?),
+            Err(_) => Err(crate::Error::RenderThreadLost),
+        }
+    }
+}
+
+fn render_loop(
+    state: RenderState,
+    jobs: &mpsc::Receiver<FrameJob>,
+    reports: &mpsc::Sender<RenderResult<FrameReport>>,
+    egui_ctx: &egui::Context,
+) -> RenderResult<RenderState> {
+    let RenderState {
+        mut painter,
+        context,
+        surface,
+    } = state;
+    let surface = surface.into_inner();
+    let context = match context.make_current(&surface) {
+        Ok(context) => context,
+        Err(err) => {
+            log::error!("failed to make the OpenGL context current on the render thread: {err}");
+            reports.send(Err(err.clone())).ok();
+            return Err(err);
+        }
+    };
+
+    let mut surface_size = None;
+    for job in jobs {
+        let report = paint(&mut painter, &context, &surface, &mut surface_size, job);
+        if painter.has_pending_texture_uploads() || painter.has_pending_readbacks() {
+            egui_ctx.request_repaint();
+        }
+        if reports.send(Ok(report)).is_err() {
+            break;
+        }
+    }
+
+    let context = context.make_not_current()?;
+    Ok(RenderState {
+        painter,
+        context,
+        surface: RenderSurface::new(surface),
+    })
+}
+
+fn paint(
+    painter: &mut egui_glow::Painter,
+    context: &glutin::context::PossiblyCurrentContext,
+    surface: &glutin::surface::Surface<glutin::surface::WindowSurface>,
+    surface_size: &mut Option<[u32; 2]>,
+    job: FrameJob,
+) -> FrameReport {
+    crate::profile_scope!("render_frame");
+
+    let FrameJob {
+        screen_size_in_pixels,
+        clear_color,
+        pixels_per_point,
+        tessellate,
+        textures_delta,
+        colorblind_simulation,
+        screenshot_requested,
+        screenshot_requests,
+    } = job;
+
+    if *surface_size != Some(screen_size_in_pixels) {
+        let [width, height] = screen_size_in_pixels;
+        let width = std::num::NonZeroU32::new(width.at_least(1)).unwrap();
+        let height = std::num::NonZeroU32::new(height.at_least(1)).unwrap();
+        surface.resize(context, width, height);
+        *surface_size = Some(screen_size_in_pixels);
+    }
+
+    painter.clear(screen_size_in_pixels, clear_color);
+
+    let clipped_primitives = {
+        crate::profile_scope!("tessellate");
+        tessellate()
+    };
+
+    if let Some(simulation) = colorblind_simulation {
+        painter.set_colorblind_simulation(simulation);
+    }
+    if screenshot_requested || !screenshot_requests.is_empty() {
+        // Screenshots read the whole back buffer:
+        painter.invalidate_damage();
+    }
+
+    // Handing over the images lets queued uploads keep them without a copy:
+    for (id, image_delta) in textures_delta.set {
+        painter.set_texture_owned(id, image_delta);
+    }
+    if painter.damage_tracking() {
+        painter.set_target_buffer_age(surface.buffer_age());
+    }
+    painter.paint_primitives(screen_size_in_pixels, pixels_per_point, &clipped_primitives);
+    for id in textures_delta.free {
+        painter.free_texture(id);
+    }
+
+    let screenshot = screenshot_requested.then(|| painter.read_screen_rgba(screen_size_in_pixels));
+    for (region, callback) in screenshot_requests {
+        painter.read_screen_rgba_async(screen_size_in_pixels, pixels_per_point, region, callback);
+    }
+
+    // With damage tracking, an unchanged frame need not be presented:
+    if !painter.damage_tracking() || !painter.damage().is_empty() {
+        crate::profile_scope!("swap_buffers");
+        let [_, height] = screen_size_in_pixels;
+        let result = if painter.damage_tracking() {
+            super::present::swap_buffers_with_damage(surface, context, painter.damage(), height)
+        } else {
+            surface.swap_buffers(context)
+        };
+        if let Err(err) = result {
+            log::error!("failed to swap buffers on the render thread: {err}");
+        }
+    }
+
+    FrameReport {
+        texture_upload_progress: painter.pending_texture_uploads().into_iter().collect(),
+        painter_stats: painter.stats(),
+        gpu_timings: painter.gpu_timings().cloned(),
+        screenshot,
+    }
+}
@@ -72,6 +72,9 @@ trait WinitApp {
 
     fn save_and_destroy(&mut self);
 
+    /// When to start a frame that was requested for `requested`.
+    fn repaint_start_time(&self, requested: Instant) -> Instant;
+
     fn run_ui_and_paint(&mut self) -> EventResult;
 
     fn on_event(
@@ -184,13 +187,15 @@ fn run_and_return(
             EventResult::Wait => {}
             EventResult::RepaintNow => {
                 log::trace!("Repaint caused by winit::Event: {:?}", event);
-                if cfg!(windows) {
+                let now = Instant::now();
+                if cfg!(windows) && winit_app.repaint_start_time(now) <= now {
                     // Fix flickering on Windows, see https://github.com/emilk/egui/pull/2280
                     next_repaint_time = extremely_far_future();
                     winit_app.run_ui_and_paint();
                 } else {
+                    // With `low_latency`, the frame waits for `repaint_start_time` below.
                     // Fix for https://github.com/emilk/egui/issues/2425
-                    next_repaint_time = Instant::now();
+                    next_repaint_time = now;
                 }
             }
             EventResult::RepaintNext => {
@@ -208,6 +213,8 @@ fn run_and_return(
             }
         }
 
+        next_repaint_time = winit_app.repaint_start_time(next_repaint_time);
+
         *control_flow = if next_repaint_time <= Instant::now() {
             if let Some(window) = winit_app.window() {
                 log::trace!("request_redraw");
@@ -284,13 +291,15 @@ fn run_and_exit(event_loop: EventLoop<UserEvent>, mut winit_app: impl WinitApp +
         match event_result {
             EventResult::Wait => {}
             EventResult::RepaintNow => {
-                if cfg!(windows) {
+                let now = Instant::now();
+                if cfg!(windows) && winit_app.repaint_start_time(now) <= now {
                     // Fix flickering on Windows, see https://github.com/emilk/egui/pull/2280
                     next_repaint_time = extremely_far_future();
                     winit_app.run_ui_and_paint();
                 } else {
+                    // With `low_latency`, the frame waits for `repaint_start_time` below.
                     // Fix for https://github.com/emilk/egui/issues/2425
-                    next_repaint_time = Instant::now();
+                    next_repaint_time = now;
                 }
             }
             EventResult::RepaintNext => {
@@ -307,6 +316,8 @@ fn run_and_exit(event_loop: EventLoop<UserEvent>, mut winit_app: impl WinitApp +
             }
         }
 
+        next_repaint_time = winit_app.repaint_start_time(next_repaint_time);
+
         *control_flow = if next_repaint_time <= Instant::now() {
             if let Some(window) = winit_app.window() {
                 window.request_redraw();
@@ -333,6 +344,11 @@ mod glow_integration {
     };
     use raw_window_handle::HasRawWindowHandle;
 
+    use crate::native::render_thread::{
+        tessellate_later, FrameJob, FrameReport, RenderState, RenderSurface, RenderThread,
+    };
+    use crate::native::upload_context::{UploadContext, UploadSurface};
+
     use super::*;
 
     // Note: that the current Glutin API design tightly couples the GL context with
@@ -353,7 +369,12 @@ mod glow_integration {
     /// initialized once the application has an associated `SurfaceView`.
     struct GlowWinitRunning {
         gl: Arc<glow::Context>,
-        painter: egui_glow::Painter,
+        /// `None` while the render thread has it.
+        painter: Option<egui_glow::Painter>,
+        /// See [`NativeOptions::pipelined_rendering`].
+        render_thread: Option<RenderThread>,
+        /// See [`NativeOptions::low_latency`].
+        frame_pacer: crate::native::frame_pacing::FramePacer,
         integration: epi_integration::EpiIntegration,
         app: Box<dyn epi::App>,
         // Conceptually this will be split out eventually so that the rest of the state
@@ -377,10 +398,14 @@ mod glow_integration {
     struct GlutinWindowContext {
         builder: winit::window::WindowBuilder,
         swap_interval: glutin::surface::SwapInterval,
+        /// Ask for an sRGB surface, for [`egui_glow::OutputMode::Linear`].
+        srgb: bool,
         gl_config: glutin::config::Config,
         current_gl_context: Option<glutin::context::PossiblyCurrentContext>,
         gl_surface: Option<glutin::surface::Surface<glutin::surface::WindowSurface>>,
         not_current_gl_context: Option<glutin::context::NotCurrentContext>,
+        /// Shares objects with the main context, see [`NativeOptions::upload_context`].
+        upload_gl_context: Option<(glutin::context::NotCurrentContext, UploadSurface)>,
         window: Option<winit::window::Window>,
     }
 
@@ -411,6 +436,8 @@ mod glow_integration {
                 3. opengl context configuration
                 4. opengl context creation
             */
+            let srgb = native_options.output_mode == egui_glow::OutputMode::Linear;
+
             // start building config for gl display
             let config_template_builder = glutin::config::ConfigTemplateBuilder::new()
                 .prefer_hardware_accelerated(hardware_acceleration)
@@ -441,8 +468,13 @@ mod glow_integration {
                 .build(
                     event_loop,
                     config_template_builder.clone(),
-                    |mut config_iterator| {
-                        let config = config_iterator.next().expect(
+                    |config_iterator| {
+                        let mut configs: Vec<_> = config_iterator.collect();
+                        if srgb {
+                            // Prefer sRGB-capable configs, keeping the original order otherwise:
+                            configs.sort_by_key(|config| !config.srgb_capable());
+                        }
+                        let config = configs.into_iter().next().expect(
                             "failed to find a matching configuration for creating glutin config",
                         );
                         log::debug!(
@@ -467,23 +499,65 @@ mod glow_integration {
             );
 
             // create gl context. if core context cannot be created, try gl es context as fallback.
-            let context_attributes =
-                glutin::context::ContextAttributesBuilder::new().build(raw_window_handle);
+            let context_attributes = glutin::context::ContextAttributesBuilder::new()
+                .with_debug(native_options.gl_debug)
+                .build(raw_window_handle);
             let fallback_context_attributes = glutin::context::ContextAttributesBuilder::new()
+                .with_debug(native_options.gl_debug)
                 .with_context_api(glutin::context::ContextApi::Gles(None))
                 .build(raw_window_handle);
-            let gl_context = match gl_config
+            let (gl_context, context_api) = match gl_config
                 .display()
                 .create_context(&gl_config, &context_attributes)
             {
-                Ok(it) => it,
+                Ok(it) => (it, None),
                 Err(err) => {
                     log::warn!("failed to create context using default context attributes {context_attributes:?} due to error: {err}");
                     log::debug!("retrying with fallback context attributes: {fallback_context_attributes:?}");
-                    gl_config
+                    let context = gl_config
                         .display()
-                        .create_context(&gl_config, &fallback_context_attributes)?
+                        .create_context(&gl_config, &fallback_context_attributes)?;
+                    (context, Some(glutin::context::ContextApi::Gles(None)))
+                }
+            };
+
+            // the upload context is made current with a small pbuffer, and must use the same api as the main one to share objects with it.
+            let upload_gl_context = if native_options.upload_context {
+                let mut upload_context_attributes =
+                    glutin::context::ContextAttributesBuilder::new()
+                        .with_debug(native_options.gl_debug)
+                        .with_sharing(&gl_context);
+                if let Some(context_api) = context_api {
+                    upload_context_attributes =
+                        upload_context_attributes.with_context_api(context_api);
+                }
+                let upload_context_attributes = upload_context_attributes.build(None);
+                let size = std::num::NonZeroU32::new(1).unwrap();
+                let upload_surface_attributes = glutin::surface::SurfaceAttributesBuilder::<
+                    glutin::surface::PbufferSurface,
+                >::new()
+                .build(size, size);
+                match gl_config
+                    .display()
+                    .create_context(&gl_config, &upload_context_attributes)
+                {
+                    Ok(it) => match gl_config
+                        .display()
+                        .create_pbuffer_surface(&gl_config, &upload_surface_attributes)
+                    {
+                        Ok(surface) => Some((it, UploadSurface::new(surface))),
+                        Err(err) => {
+                            log::warn!("failed to create a pbuffer surface for the upload context due to error: {err}");
+                            None
+                        }
+                    },
+                    Err(err) => {
+                        log::warn!("failed to create upload context with attributes {upload_context_attributes:?} due to error: {err}");
+                        None
+                    }
                 }
+            } else {
+                None
             };
             let not_current_gl_context = Some(gl_context);
 
@@ -494,11 +568,13 @@ mod glow_integration {
             Ok(GlutinWindowContext {
                 builder: winit_window_builder,
                 swap_interval,
+                srgb,
                 gl_config,
                 current_gl_context: None,
                 window,
                 gl_surface: None,
                 not_current_gl_context,
+                upload_gl_context,
             })
         }
 
@@ -529,6 +605,7 @@ mod glow_integration {
             let height = std::num::NonZeroU32::new(height.at_least(1)).unwrap();
             let surface_attributes =
                 glutin::surface::SurfaceAttributesBuilder::<glutin::surface::WindowSurface>::new()
+                    .with_srgb(self.srgb.then_some(true))
                     .build(window.raw_window_handle(), width, height);
             log::debug!(
                 "creating surface with attributes: {:?}",
@@ -604,9 +681,125 @@ mod glow_integration {
                 )
         }
 
+        /// See [`egui_glow::Painter::set_target_buffer_age`].
+        fn buffer_age(&self) -> u32 {
+            self.gl_surface
+                .as_ref()
+                .expect("failed to get surface to query its buffer age")
+                .buffer_age()
+        }
+
+        /// Like [`Self::swap_buffers`], with the regions that changed, see [`egui_glow::Painter::damage`].
+        fn swap_buffers_with_damage(
+            &self,
+            damage: &[egui::Rect],
+            height: u32,
+        ) -> glutin::error::Result<()> {
+            crate::native::present::swap_buffers_with_damage(
+                self.gl_surface
+                    .as_ref()
+                    .expect("failed to get surface to swap buffers"),
+                self.current_gl_context
+                    .as_ref()
+                    .expect("failed to get current context to swap buffers"),
+                damage,
+                height,
+            )
+        }
+
         fn get_proc_address(&self, addr: &std::ffi::CStr) -> *const std::ffi::c_void {
             self.gl_config.display().get_proc_address(addr)
         }
+
+        /// Give up the context and surface, to hand them to a render thread.
+        fn lend_context(
+            &mut self,
+        ) -> Result<(
+            glutin::context::NotCurrentContext,
+            glutin::surface::Surface<glutin::surface::WindowSurface>,
+        )> {
+            let context = self
+                .current_gl_context
+                .take()
+                .expect("failed to get current context to lend");
+            let surface = self
+                .gl_surface
+                .take()
+                .expect("failed to get surface to lend");
+            Ok((context.make_not_current()?, surface))
+        }
+
+        /// Make the context current on this thread again, after [`Self::lend_context`].
+        fn take_back_context(
+            &mut self,
+            context: glutin::context::NotCurrentContext,
+            surface: glutin::surface::Surface<glutin::surface::WindowSurface>,
+        ) -> Result<()> {
+            self.current_gl_context = Some(context.make_current(&surface)?);
+            self.gl_surface = Some(surface);
+            Ok(())
+        }
+    }
+
+    impl GlowWinitRunning {
+        /// Move the painter and the OpenGL context to a render thread,
+        /// see [`NativeOptions::pipelined_rendering`].
+        fn start_render_thread(&mut self, queue_depth: usize) -> Result<()> {
+            if self.render_thread.is_some() {
+                return Ok(());
+            }
+            if cfg!(target_os = "macos") {
+                // CGL must update the context on the main thread whenever the window resizes.
+                log::warn!("pipelined_rendering is ignored on macOS");
+                return Ok(());
+            }
+            let (context, surface) = self.gl_window.lend_context()?;
+            let painter = self.painter.take().expect("painter already taken");
+            self.render_thread = Some(RenderThread::spawn(
+                RenderState {
+                    painter,
+                    context,
+                    surface: RenderSurface::new(surface),
+                },
+                queue_depth,
+                self.integration.egui_ctx.clone(),
+            ));
+            Ok(())
+        }
+
+        /// Paint on the event loop thread again, once the render thread has painted all queued frames.
+        fn stop_render_thread(&mut self) -> Result<()> {
+            if let Some(mut render_thread) = self.render_thread.take() {
+                // A failure here is reported by `join`:
+                let reports = render_thread.finish().unwrap_or_default();
+                if let Some(window) = &self.gl_window.window {
+                    post_rendering_painted(
+                        &mut self.integration,
+                        self.app.as_mut(),
+                        window,
+                        reports,
+                    );
+                }
+                let state = render_thread.join()?;
+                self.gl_window
+                    .take_back_context(state.context, state.surface.into_inner())?;
+                self.painter = Some(state.painter);
+            }
+            Ok(())
+        }
+    }
+
+    /// Run [`epi::App::post_rendering`] for each frame the render thread has painted, in order.
+    fn post_rendering_painted(
+        integration: &mut epi_integration::EpiIntegration,
+        app: &mut dyn epi::App,
+        window: &winit::window::Window,
+        reports: Vec<FrameReport>,
+    ) {
+        for report in reports {
+            report.apply_to(&mut integration.frame);
+            integration.post_rendering(app, window);
+        }
     }
 
     struct GlowWinitApp {
@@ -681,7 +874,7 @@ mod glow_integration {
                     .unwrap_or(&self.app_name),
             );
 
-            let (gl_window, gl) = Self::create_glutin_windowed_context(
+            let (mut gl_window, gl) = Self::create_glutin_windowed_context(
                 event_loop,
                 storage.as_deref(),
                 &self.app_name,
@@ -689,9 +882,75 @@ mod glow_integration {
             )?;
             let gl = Arc::new(gl);
 
-            let painter =
-                egui_glow::Painter::new(gl.clone(), "", self.native_options.shader_version)
-                    .unwrap_or_else(|error| panic!("some OpenGL error occurred {}\n", error));
+            let program_cache_directory = match &self.native_options.program_cache {
+                None => None,
+                Some(epi::ProgramCacheLocation::Directory(directory)) => Some(directory.clone()),
+                Some(epi::ProgramCacheLocation::Storage) => {
+                    #[cfg(feature = "persistence")]
+                    let data_dir = crate::native::file_storage::data_dir(
+                        self.native_options
+                            .app_id
+                            .as_ref()
+                            .unwrap_or(&self.app_name),
+                    );
+                    #[cfg(not(feature = "persistence"))]
+                    let data_dir: Option<std::path::PathBuf> = None;
+                    if data_dir.is_none() {
+                        log::warn!("NativeOptions::program_cache is set to Storage, but there is no storage. Enable the 'persistence' feature.");
+                    }
+                    data_dir.map(|data_dir| data_dir.join("program_cache"))
+                }
+            };
+            let program_cache_storage =
+                program_cache_directory.map(egui_glow::DirectoryStorage::new);
+            let mut program_cache = program_cache_storage.and_then(|program_cache_storage| {
+                egui_glow::ProgramCache::new(
+                    &gl,
+                    |s| gl_window.get_proc_address(s),
+                    program_cache_storage,
+                )
+            });
+            let painter_options = egui_glow::PainterOptions {
+                output_mode: self.native_options.output_mode,
+                debug: self.native_options.gl_debug,
+            };
+            let mut painter = if let Some(program_cache) = &mut program_cache {
+                egui_glow::Painter::new_with_program_cache(
+                    gl.clone(),
+                    "",
+                    self.native_options.shader_version,
+                    painter_options,
+                    program_cache,
+                )
+            } else {
+                egui_glow::Painter::new_with_options(
+                    gl.clone(),
+                    "",
+                    self.native_options.shader_version,
+                    painter_options,
+                )
+            }
+            .unwrap_or_else(|error| panic!("some OpenGL error occurred {}\n", error));
+            drop(program_cache);
+            painter.set_upload_budget(self.native_options.texture_upload_budget);
+            painter.set_gpu_timing(self.native_options.gpu_timing);
+            painter.set_damage_tracking(self.native_options.damage_tracking);
+
+            let upload_context = gl_window
+                .upload_gl_context
+                .take()
+                .map(|(context, surface)| {
+                    // loaded while the main context is current, which shares its functions with the upload context.
+                    let upload_gl = unsafe {
+                        glow::Context::from_loader_function(|s| {
+                            let s = std::ffi::CString::new(s).expect(
+                                "failed to construct C string from string for gl proc address",
+                            );
+                            gl_window.get_proc_address(&s)
+                        })
+                    };
+                    UploadContext::new(context, surface, upload_gl, painter.shared_textures())
+                });
 
             let system_theme = system_theme(gl_window.window(), &self.native_options);
             let mut integration = epi_integration::EpiIntegration::new(
@@ -712,6 +971,7 @@ mod glow_integration {
             // }
             let theme = system_theme.unwrap_or(self.native_options.default_theme);
             integration.egui_ctx.set_visuals(theme.egui_visuals());
+            integration.frame.upload_context = upload_context.clone();
 
             // gl_window.window().set_ime_allowed(true);
             if self.native_options.mouse_passthrough {
@@ -740,6 +1000,7 @@ mod glow_integration {
                 integration_info: integration.frame.info(),
                 storage: integration.frame.storage(),
                 gl: Some(gl.clone()),
+                upload_context,
                 #[cfg(feature = "wgpu")]
                 wgpu_render_state: None,
             });
@@ -748,13 +1009,19 @@ mod glow_integration {
                 integration.warm_up(app.as_mut(), gl_window.window());
             }
 
-            self.running = Some(GlowWinitRunning {
+            let mut running = GlowWinitRunning {
                 gl_window,
                 gl,
-                painter,
+                painter: Some(painter),
+                render_thread: None,
+                frame_pacer: Default::default(),
                 integration,
                 app,
-            });
+            };
+            if let Some(queue_depth) = self.native_options.pipelined_rendering {
+                running.start_render_thread(queue_depth)?;
+            }
+            self.running = Some(running);
 
             Ok(())
         }
@@ -781,11 +1048,27 @@ mod glow_integration {
 
         fn save_and_destroy(&mut self) {
             if let Some(mut running) = self.running.take() {
+                if let Err(err) = running.stop_render_thread() {
+                    log::error!("failed to stop the render thread: {err}");
+                }
                 running
                     .integration
                     .save(running.app.as_mut(), running.gl_window.window());
                 running.app.on_exit(Some(&running.gl));
-                running.painter.destroy();
+                if let Some(painter) = &mut running.painter {
+                    painter.destroy();
+                }
+            }
+        }
+
+        fn repaint_start_time(&self, requested: Instant) -> Instant {
+            match &self.running {
+                Some(running)
+                    if self.native_options.low_latency && running.render_thread.is_none() =>
+                {
+                    running.frame_pacer.start_time(requested)
+                }
+                _ => requested,
             }
         }
 
@@ -797,21 +1080,35 @@ mod glow_integration {
 
                 let GlowWinitRunning {
                     gl_window,
-                    gl,
+                    gl: _,
                     app,
                     integration,
                     painter,
+                    render_thread,
+                    frame_pacer,
                 } = running;
 
+                let frame_start = Instant::now();
                 let window = gl_window.window();
 
                 let screen_size_in_pixels: [u32; 2] = window.inner_size().into();
 
-                egui_glow::painter::clear(
-                    gl,
-                    screen_size_in_pixels,
-                    app.clear_color(&integration.egui_ctx.style().visuals),
-                );
+                let clear_color = app.clear_color(&integration.egui_ctx.style().visuals);
+                if let Some(render_thread) = render_thread {
+                    match render_thread.reports() {
+                        Ok(reports) => {
+                            post_rendering_painted(integration, app.as_mut(), window, reports);
+                        }
+                        Err(err) => {
+                            log::error!("Exiting because the render thread failed: {err}");
+                            return EventResult::Exit;
+                        }
+                    }
+                } else if let Some(painter) = painter {
+                    painter.clear(screen_size_in_pixels, clear_color);
+                    integration.frame.info.predicted_present_time =
+                        frame_pacer.predicted_present_time(frame_start);
+                }
 
                 let egui::FullOutput {
                     platform_output,
@@ -822,38 +1119,116 @@ mod glow_integration {
 
                 integration.handle_platform_output(window, platform_output);
 
-                let clipped_primitives = {
-                    crate::profile_scope!("tessellate");
-                    integration.egui_ctx.tessellate(shapes)
-                };
+                #[cfg(feature = "recording")]
+                integration.frame.capture_recording_frame();
+
+                if let Some(render_thread) = render_thread {
+                    let frame = &mut integration.frame;
+                    let screenshot_requested =
+                        std::mem::take(&mut frame.output.screenshot_requested);
+                    render_thread.submit(FrameJob {
+                        screen_size_in_pixels,
+                        clear_color,
+                        pixels_per_point: integration.egui_ctx.pixels_per_point(),
+                        tessellate: tessellate_later(&integration.egui_ctx, shapes),
+                        textures_delta,
+                        colorblind_simulation: frame.output.colorblind_simulation.take(),
+                        screenshot_requested,
+                        screenshot_requests: std::mem::take(&mut frame.screenshot_requests),
+                    });
 
-                painter.paint_and_update_textures(
-                    screen_size_in_pixels,
-                    integration.egui_ctx.pixels_per_point(),
-                    &clipped_primitives,
-                    &textures_delta,
-                );
+                    // The screenshot is expected in `post_rendering`,
+                    // and the window should only be shown once it is painted.
+                    // Otherwise `post_rendering` runs once the report of the frame comes back.
+                    if screenshot_requested || frame.output.visible.is_some() {
+                        match render_thread.finish() {
+                            Ok(reports) => {
+                                post_rendering_painted(integration, app.as_mut(), window, reports);
+                            }
+                            Err(err) => {
+                                log::error!("Exiting because the render thread failed: {err}");
+                                return EventResult::Exit;
+                            }
+                        }
+                    }
 
-                let screenshot_requested = &mut integration.frame.output.screenshot_requested;
+                    integration.post_present(window);
+                } else if let Some(painter) = painter {
+                    let clipped_primitives = {
+                        crate::profile_scope!("tessellate");
+                        integration.egui_ctx.tessellate(shapes)
+                    };
 
-                if *screenshot_requested {
-                    *screenshot_requested = false;
-                    let screenshot = painter.read_screen_rgba(screen_size_in_pixels);
-                    integration.frame.screenshot.set(Some(screenshot));
-                }
+                    if let Some(simulation) = integration.frame.output.colorblind_simulation.take()
+                    {
+                        painter.set_colorblind_simulation(simulation);
+                    }
 
-                integration.post_rendering(app.as_mut(), window);
+                    let frame = &integration.frame;
+                    if frame.output.screenshot_requested || !frame.screenshot_requests.is_empty() {
+                        // Screenshots read the whole back buffer:
+                        painter.invalidate_damage();
+                    }
 
-                {
-                    crate::profile_scope!("swap_buffers");
-                    gl_window.swap_buffers().unwrap();
-                }
+                    // Handing over the images lets queued uploads keep them without a copy:
+                    for (id, image_delta) in textures_delta.set {
+                        painter.set_texture_owned(id, image_delta);
+                    }
+                    if painter.damage_tracking() {
+                        painter.set_target_buffer_age(gl_window.buffer_age());
+                    }
+                    painter.paint_primitives(
+                        screen_size_in_pixels,
+                        integration.egui_ctx.pixels_per_point(),
+                        &clipped_primitives,
+                    );
+                    for id in textures_delta.free {
+                        painter.free_texture(id);
+                    }
+                    integration.frame.texture_upload_progress =
+                        painter.pending_texture_uploads().into_iter().collect();
+                    integration.frame.info.painter_stats = Some(painter.stats());
+                    integration.frame.info.gpu_timings = painter.gpu_timings().cloned();
 
-                integration.post_present(window);
+                    let screenshot_requested = &mut integration.frame.output.screenshot_requested;
+
+                    if *screenshot_requested {
+                        *screenshot_requested = false;
+                        let screenshot = painter.read_screen_rgba(screen_size_in_pixels);
+                        integration.frame.screenshot.set(Some(screenshot));
+                    }
+                    for (region, callback) in
+                        std::mem::take(&mut integration.frame.screenshot_requests)
+                    {
+                        painter.read_screen_rgba_async(
+                            screen_size_in_pixels,
+                            integration.egui_ctx.pixels_per_point(),
+                            region,
+                            callback,
+                        );
+                    }
+
+                    integration.post_rendering(app.as_mut(), window);
+
+                    let render_time = frame_start.elapsed();
+                    if !painter.damage_tracking() {
+                        crate::profile_scope!("swap_buffers");
+                        gl_window.swap_buffers().unwrap();
+                        frame_pacer.record_frame(render_time, Instant::now());
+                    } else if !painter.damage().is_empty() {
+                        crate::profile_scope!("swap_buffers");
+                        gl_window
+                            .swap_buffers_with_damage(painter.damage(), screen_size_in_pixels[1])
+                            .unwrap();
+                        frame_pacer.record_frame(render_time, Instant::now());
+                    }
+
+                    integration.post_present(window);
+                }
 
                 #[cfg(feature = "__screenshot")]
                 // give it time to settle:
-                if integration.egui_ctx.frame_nr() == 2 {
+                if let (2, Some(painter)) = (integration.egui_ctx.frame_nr(), painter.as_ref()) {
                     if let Ok(path) = std::env::var("EFRAME_SCREENSHOT_TO") {
                         assert!(
                             path.ends_with(".png"),
@@ -877,7 +1252,11 @@ mod glow_integration {
 
                 let control_flow = if integration.should_close() {
                     EventResult::Exit
-                } else if repaint_after.is_zero() {
+                } else if repaint_after.is_zero()
+                    || painter.as_ref().map_or(false, |painter| {
+                        painter.has_pending_texture_uploads() || painter.has_pending_readbacks()
+                    })
+                {
                     EventResult::RepaintNext
                 } else if let Some(repaint_after_instant) =
                     std::time::Instant::now().checked_add(repaint_after)
@@ -925,16 +1304,18 @@ mod glow_integration {
                         self.init_run_state(event_loop)?;
                     } else {
                         // not the first resume event. create whatever you need.
-                        self.running
-                            .as_mut()
-                            .unwrap()
-                            .gl_window
-                            .on_resume(event_loop)?;
+                        let running = self.running.as_mut().unwrap();
+                        running.gl_window.on_resume(event_loop)?;
+                        if let Some(queue_depth) = self.native_options.pipelined_rendering {
+                            running.start_render_thread(queue_depth)?;
+                        }
                     }
                     EventResult::RepaintNow
                 }
                 winit::event::Event::Suspended => {
-                    self.running.as_mut().unwrap().gl_window.on_suspend()?;
+                    let running = self.running.as_mut().unwrap();
+                    running.stop_render_thread()?;
+                    running.gl_window.on_suspend()?;
 
                     EventResult::Wait
                 }
@@ -966,7 +1347,11 @@ mod glow_integration {
                                 // Resize with 0 width and height is used by winit to signal a minimize event on Windows.
                                 // See: https://github.com/rust-windowing/winit/issues/208
                                 // This solves an issue where the app would panic when minimizing on Windows.
-                                if physical_size.width > 0 && physical_size.height > 0 {
+                                // With a render thread, each frame resizes the surface there.
+                                if physical_size.width > 0
+                                    && physical_size.height > 0
+                                    && running.render_thread.is_none()
+                                {
                                     running.gl_window.resize(*physical_size);
                                 }
                             }
@@ -975,7 +1360,9 @@ mod glow_integration {
                                 ..
                             } => {
                                 repaint_asap = true;
-                                running.gl_window.resize(**new_inner_size);
+                                if running.render_thread.is_none() {
+                                    running.gl_window.resize(**new_inner_size);
+                                }
                             }
                             winit::event::WindowEvent::CloseRequested
                                 if running.integration.should_close() =>
@@ -1201,6 +1588,8 @@ mod wgpu_integration {
                 storage: integration.frame.storage(),
                 #[cfg(feature = "glow")]
                 gl: None,
+                #[cfg(feature = "glow")]
+                upload_context: None,
                 wgpu_render_state,
             });
 
@@ -1254,6 +1643,10 @@ mod wgpu_integration {
             }
         }
 
+        fn repaint_start_time(&self, requested: Instant) -> Instant {
+            requested
+        }
+
         fn run_ui_and_paint(&mut self) -> EventResult {
             if let (Some(running), Some(window)) = (&mut self.running, &self.window) {
                 #[cfg(feature = "puffin")]
@@ -1291,6 +1684,14 @@ mod wgpu_integration {
                 );
                 *screenshot_requested = false;
                 integration.frame.screenshot.set(screenshot);
+                #[cfg(feature = "glow")]
+                if !integration.frame.screenshot_requests.is_empty() {
+                    log::warn!(
+                        "Dropping {} request(s) of Frame::request_screenshot_async: not supported by the wgpu renderer",
+                        integration.frame.screenshot_requests.len()
+                    );
+                    integration.frame.screenshot_requests.clear();
+                }
 
                 integration.post_rendering(app.as_mut(), window);
                 integration.post_present(window);
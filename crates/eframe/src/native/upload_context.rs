@@ -0,0 +1,211 @@
+#![allow(unsafe_code)]
+
+//! A second OpenGL context for uploading textures on background threads, see [`UploadContext`].
+
+use std::sync::Arc;
+
+use egui::mutex::Mutex;
+use glow::HasContext as _;
+use glutin::prelude::*;
+
+/// How long to block in one `glClientWaitSync`, in nanoseconds.
+const FENCE_TIMEOUT_NS: i32 = 100_000_000;
+
+/// The 1×1 pbuffer the upload context is made current with,
+/// since glutin 0.30 can only make contexts current without a surface on EGL.
+pub(crate) struct UploadSurface(glutin::surface::Surface<glutin::surface::PbufferSurface>);
+
+// SAFETY: the surface is only used together with its context, by one thread at a time
+// (behind the lock in `Inner`), which EGL, GLX, WGL and CGL all allow.
+unsafe impl Send for UploadSurface {}
+
+impl UploadSurface {
+    pub(crate) fn new(surface: glutin::surface::Surface<glutin::surface::PbufferSurface>) -> Self {
+        Self(surface)
+    }
+}
+
+struct Inner {
+    /// `None` while a thread is using it, or after it was lost in an error.
+    context: Mutex<Option<(glutin::context::NotCurrentContext, UploadSurface)>>,
+
+    gl: glow::Context,
+
+    textures: egui_glow::SharedTextures,
+}
+
+/// An OpenGL context that shares objects with the one eframe paints with,
+/// for creating textures on a background thread without blocking the UI.
+///
+/// Enable it with [`crate::NativeOptions::upload_context`], and get it from
+/// [`crate::CreationContext::upload_context`] or [`crate::Frame::upload_context`].
+/// It is cheap to clone and can be sent to other threads:
+///
+/// ``` no_run
+/// use eframe_tao::egui;
+/// use eframe_tao::glow::{self, HasContext as _};
+///
+/// /// Create a texture from an image on the calling thread, for egui to show.
+/// fn upload_image(
+///     upload_context: &eframe_tao::UploadContext,
+///     rgba: &[u8],
+///     [width, height]: [i32; 2],
+/// ) -> eframe_tao::Result<egui::TextureId> {
+///     let texture = upload_context.upload(|gl| unsafe {
+///         let texture = gl.create_texture().unwrap();
+///         gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+///         gl.tex_image_2d(
+///             glow::TEXTURE_2D,
+///             0,
+///             glow::RGBA8 as i32,
+///             width,
+///             height,
+///             0,
+///             glow::RGBA,
+///             glow::UNSIGNED_BYTE,
+///             Some(rgba),
+///         );
+///         gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::LINEAR as i32);
+///         texture
+///     })?;
+///     Ok(upload_context.register_native_texture(texture))
+/// }
+///
+/// fn upload_in_background(
+///     upload_context: eframe_tao::UploadContext,
+///     ctx: egui::Context,
+///     rgba: Vec<u8>,
+///     size: [i32; 2],
+/// ) {
+///     std::thread::spawn(move || match upload_image(&upload_context, &rgba, size) {
+///         Ok(_texture_id) => {
+///             // Send the texture id to the app, then:
+///             ctx.request_repaint();
+///         }
+///         Err(err) => eprintln!("Failed to upload the image: {err}"),
+///     });
+/// }
+/// ```
+///
+/// Only available with the glow renderer.
+#[derive(Clone)]
+pub struct UploadContext(Arc<Inner>);
+
+impl UploadContext {
+    pub(crate) fn new(
+        context: glutin::context::NotCurrentContext,
+        surface: UploadSurface,
+        gl: glow::Context,
+        textures: egui_glow::SharedTextures,
+    ) -> Self {
+        Self(Arc::new(Inner {
+            context: Mutex::new(Some((context, surface))),
+            gl,
+            textures,
+        }))
+    }
+
+    /// Make the context current on this thread and run `upload` with it.
+    ///
+    /// Before returning, this waits (with a fence where supported) until the GPU has finished
+    /// the commands of `upload`, so the textures it created can be used by the painter right away,
+    /// e.g. with [`Self::register_native_texture`].
+    ///
+    /// Only one thread can use the context at a time; others block until it is free.
+    /// Don't call this on the UI thread, where eframe's own context is current.
+    pub fn upload<R>(&self, upload: impl FnOnce(&glow::Context) -> R) -> crate::Result<R> {
+        crate::profile_function!();
+        let mut slot = self.0.context.lock();
+        let (context, surface) = slot.take().ok_or(crate::Error::UploadContextLost)?;
+        let mut current = CurrentUploadContext {
+            slot: &mut slot,
+            context: Some((context.treat_as_possibly_current(), surface)),
+        };
+        current.make_current()?;
+
+        let gl = &self.0.gl;
+        let result = upload(gl);
+        unsafe { wait_for_gpu(gl) };
+
+        current.release()?;
+        Ok(result)
+    }
+
+    /// Let egui paint a texture created with [`Self::upload`].
+    ///
+    /// See [`egui_glow::SharedTextures::register_native_texture`].
+    pub fn register_native_texture(&self, native: glow::Texture) -> egui::TextureId {
+        self.0.textures.register_native_texture(native)
+    }
+
+    /// Free a texture registered with [`Self::register_native_texture`],
+    /// deleting it on this context. Don't paint it afterwards.
+    pub fn free_texture(&self, tex_id: egui::TextureId) -> crate::Result<()> {
+        self.upload(|gl| self.0.textures.free_texture(gl, tex_id))
+    }
+}
+
+/// The upload context while a thread uses it.
+///
+/// Puts it back into the slot when done, also when making it current fails or the upload panics,
+/// so it is only lost if it can't be released from the thread.
+struct CurrentUploadContext<'a> {
+    slot: &'a mut Option<(glutin::context::NotCurrentContext, UploadSurface)>,
+    context: Option<(glutin::context::PossiblyCurrentContext, UploadSurface)>,
+}
+
+impl CurrentUploadContext<'_> {
+    fn make_current(&self) -> crate::Result<()> {
+        if let Some((context, surface)) = &self.context {
+            context.make_current(&surface.0)?;
+        }
+        Ok(())
+    }
+
+    fn release(&mut self) -> crate::Result<()> {
+        if let Some((context, surface)) = self.context.take() {
+            *self.slot = Some((context.make_not_current()?, surface));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for CurrentUploadContext<'_> {
+    fn drop(&mut self) {
+        if let Err(err) = self.release() {
+            log::error!("Lost the upload context: {err}");
+        }
+    }
+}
+
+/// Block until the GPU has executed everything submitted on this context.
+unsafe fn wait_for_gpu(gl: &glow::Context) {
+    let version = gl.version();
+    // Fences need OpenGL 3.2 or OpenGL ES 3.0:
+    let has_fences = if version.is_embedded {
+        version.major >= 3
+    } else {
+        (version.major, version.minor) >= (3, 2)
+    };
+    let fence = if has_fences {
+        gl.fence_sync(glow::SYNC_GPU_COMMANDS_COMPLETE, 0).ok()
+    } else {
+        None
+    };
+    let Some(fence) = fence else {
+        gl.finish();
+        return;
+    };
+    loop {
+        match gl.client_wait_sync(fence, glow::SYNC_FLUSH_COMMANDS_BIT, FENCE_TIMEOUT_NS) {
+            glow::TIMEOUT_EXPIRED => continue,
+            glow::WAIT_FAILED => {
+                log::warn!("glClientWaitSync failed; falling back to glFinish");
+                gl.finish();
+                break;
+            }
+            _ => break, // ALREADY_SIGNALED or CONDITION_SATISFIED
+        }
+    }
+    gl.delete_sync(fence);
+}
@@ -0,0 +1,497 @@
+//! Running an [`App`] without a window or OpenGL, for unit tests.
+//!
+//! [`TestHarness`] runs frames of your app with scripted input, and records what it asked of the window:
+//!
+//! ```
+//! use eframe_tao::{egui, testing::TestHarness};
+//!
+//! #[derive(Default)]
+//! struct Counter {
+//!     count: u32,
+//! }
+//!
+//! impl eframe_tao::App for Counter {
+//!     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe_tao::Frame) {
+//!         egui::CentralPanel::default().show(ctx, |ui| {
+//!             ui.label(format!("Count: {}", self.count));
+//!             if ui.button("Increment").clicked() {
+//!                 self.count += 1;
+//!             }
+//!             if ui.button("Quit").clicked() {
+//!                 frame.close();
+//!             }
+//!         });
+//!     }
+//! }
+//!
+//! let mut harness = TestHarness::new(Counter::default());
+//! harness.run();
+//! harness.click_label("Increment");
+//! assert_eq!(harness.app.count, 1);
+//! harness.run(); // The label was painted before the click was handled.
+//! assert!(harness.find_label("Count: 1").is_some());
+//!
+//! harness.click_label("Quit");
+//! assert!(harness.app_output().close);
+//! ```
+
+use egui::{Event, Key, Modifiers, Pos2, Rect, Vec2};
+
+use crate::epi::{App, CreationContext, Frame, IntegrationInfo, WindowInfo};
+
+pub use crate::epi::backend::AppOutput;
+
+/// Runs an [`App`] frame by frame, with scripted input and a mock [`Frame`].
+///
+/// The helpers that simulate input (like [`Self::click_label`]) run the frames it takes;
+/// [`Self::push_event`] only queues an event for the next [`Self::run`].
+pub struct TestHarness<A: App> {
+    pub app: A,
+
+    pub ctx: egui::Context,
+
+    frame: Frame,
+
+    /// The input for the next frame.
+    input: egui::RawInput,
+
+    /// Advanced by `dt` each frame.
+    time: f64,
+
+    dt: f32,
+
+    modifiers: Modifiers,
+
+    pointer_pos: Option<Pos2>,
+
+    /// The output of the last frame.
+    output: egui::FullOutput,
+
+    /// What the app asked of the window in the last frame.
+    app_output: AppOutput,
+
+    /// … and in all frames so far.
+    app_outputs: Vec<AppOutput>,
+}
+
+impl<A: App> TestHarness<A> {
+    /// Test `app` on an 800×600 points screen.
+    pub fn new(app: A) -> Self {
+        Self::from_parts(app, egui::Context::default())
+    }
+
+    /// Create the app like [`crate::run_native`] does, with a [`CreationContext`] without storage or OpenGL.
+    pub fn with_creator(app_creator: impl FnOnce(&CreationContext<'_>) -> A) -> Self {
+        let ctx = egui::Context::default();
+        let creation_context = CreationContext {
+            egui_ctx: ctx.clone(),
+            integration_info: integration_info(default_screen_size()),
+            storage: None,
+            #[cfg(feature = "glow")]
+            gl: None,
+            #[cfg(feature = "glow")]
+            upload_context: None,
+            #[cfg(feature = "wgpu")]
+            wgpu_render_state: None,
+        };
+        let app = app_creator(&creation_context);
+        Self::from_parts(app, ctx)
+    }
+
+    fn from_parts(app: A, ctx: egui::Context) -> Self {
+        let screen_size = default_screen_size();
+        let frame = Frame::new(
+            integration_info(screen_size),
+            None,
+            #[cfg(feature = "glow")]
+            None,
+            #[cfg(feature = "wgpu")]
+            None,
+        );
+        let mut harness = Self {
+            app,
+            ctx,
+            frame,
+            input: Default::default(),
+            time: 0.0,
+            dt: 1.0 / 60.0,
+            modifiers: Modifiers::NONE,
+            pointer_pos: None,
+            output: Default::default(),
+            app_output: Default::default(),
+            app_outputs: Vec::new(),
+        };
+        harness.set_screen_size(screen_size);
+        harness.set_pixels_per_point(1.0);
+        harness
+    }
+
+    /// Resize the mock screen, in points.
+    pub fn set_screen_size(&mut self, size: Vec2) {
+        self.input.screen_rect = Some(Rect::from_min_size(Pos2::ZERO, size));
+        self.frame.info.window_info.size = size;
+    }
+
+    pub fn set_pixels_per_point(&mut self, pixels_per_point: f32) {
+        self.input.pixels_per_point = Some(pixels_per_point);
+        self.frame.info.native_pixels_per_point = Some(pixels_per_point);
+    }
+
+    /// How many seconds pass each frame. Default: `1.0 / 60.0`.
+    pub fn set_dt(&mut self, dt: f32) {
+        self.dt = dt;
+    }
+
+    /// The mock [`Frame`] the app gets, e.g. to check [`Frame::info`].
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+
+    pub fn frame_mut(&mut self) -> &mut Frame {
+        &mut self.frame
+    }
+
+    /// Queue an event for the next frame.
+    pub fn push_event(&mut self, event: Event) {
+        self.input.events.push(event);
+    }
+
+    /// Run one frame of the app, with the queued events.
+    pub fn run(&mut self) -> &egui::FullOutput {
+        let mut input = std::mem::take(&mut self.input);
+        // Keep the screen for the next frame:
+        self.input.screen_rect = input.screen_rect;
+        input.time = Some(self.time);
+        input.predicted_dt = self.dt;
+        input.modifiers = self.modifiers;
+        self.time += self.dt as f64;
+
+        let Self {
+            app, ctx, frame, ..
+        } = self;
+        self.output = ctx.run(input, |ctx| app.update(ctx, frame));
+
+        let [width, height] = self.screen_size_px();
+        self.app.post_rendering([width, height], &self.frame);
+        self.app_output = std::mem::take(&mut self.frame.output);
+        self.app_outputs.push(self.app_output.clone());
+        &self.output
+    }
+
+    /// Run `frames` frames, e.g. to let animations finish.
+    pub fn run_frames(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.run();
+        }
+    }
+
+    fn screen_size_px(&self) -> [u32; 2] {
+        let size = self.frame.info.window_info.size * self.ctx.pixels_per_point();
+        [size.x.round() as u32, size.y.round() as u32]
+    }
+
+    /// Move the mouse to `pos` (in points) and run a frame.
+    pub fn hover(&mut self, pos: Pos2) {
+        self.pointer_pos = Some(pos);
+        self.push_event(Event::PointerMoved(pos));
+        self.run();
+    }
+
+    /// Click the primary mouse button at `pos` (in points), running a frame to move there,
+    /// one to press and one to release.
+    pub fn click_at(&mut self, pos: Pos2) {
+        self.hover(pos);
+        for pressed in [true, false] {
+            self.push_event(Event::PointerButton {
+                pos,
+                button: egui::PointerButton::Primary,
+                pressed,
+                modifiers: self.modifiers,
+            });
+            self.run();
+        }
+    }
+
+    /// Click the center of the text `label`, e.g. on a button, like [`Self::click_at`].
+    ///
+    /// # Panics
+    /// If no visible text in the last frame is exactly `label`.
+    pub fn click_label(&mut self, label: &str) {
+        let rect = self.find_label(label).unwrap_or_else(|| {
+            panic!(
+                "No label {label:?} in the last frame. Visible labels: {:?}",
+                self.labels()
+            )
+        });
+        self.click_at(rect.center());
+    }
+
+    /// Where the last frame showed the text `label`, in points.
+    ///
+    /// If it was shown more than once, this is where it was painted last, i.e. on top.
+    pub fn find_label(&self, label: &str) -> Option<Rect> {
+        let mut found = None;
+        self.for_each_text(|text, rect| {
+            if text == label {
+                found = Some(rect);
+            }
+        });
+        found
+    }
+
+    /// All visible text in the last frame, in the order it was painted.
+    pub fn labels(&self) -> Vec<String> {
+        let mut labels = Vec::new();
+        self.for_each_text(|text, _| labels.push(text.to_owned()));
+        labels
+    }
+
+    fn for_each_text(&self, mut f: impl FnMut(&str, Rect)) {
+        fn visit(shape: &egui::Shape, clip_rect: Rect, f: &mut impl FnMut(&str, Rect)) {
+            match shape {
+                egui::Shape::Text(text) => {
+                    let rect = text.galley.rect.translate(text.pos.to_vec2());
+                    if rect.intersect(clip_rect).is_positive() {
+                        f(text.galley.text(), rect.intersect(clip_rect));
+                    }
+                }
+                egui::Shape::Vec(shapes) => {
+                    for shape in shapes {
+                        visit(shape, clip_rect, f);
+                    }
+                }
+                _ => {}
+            }
+        }
+        for egui::epaint::ClippedShape(clip_rect, shape) in &self.output.shapes {
+            visit(shape, *clip_rect, &mut f);
+        }
+    }
+
+    /// Type `text` into the focused widget, and run a frame.
+    pub fn type_text(&mut self, text: &str) {
+        self.push_event(Event::Text(text.to_owned()));
+        self.run();
+    }
+
+    /// Press and release `key` while holding `modifiers`, and run a frame.
+    ///
+    /// Like a real integration, egui doesn't turn e.g. ctrl-C into [`Event::Copy`];
+    /// push those yourself with [`Self::push_event`].
+    pub fn press_key(&mut self, modifiers: Modifiers, key: Key) {
+        let previous_modifiers = std::mem::replace(&mut self.modifiers, modifiers);
+        for pressed in [true, false] {
+            self.push_event(Event::Key {
+                key,
+                pressed,
+                repeat: false,
+                modifiers,
+            });
+        }
+        self.run();
+        self.modifiers = previous_modifiers;
+    }
+
+    /// Hold these modifiers in the following frames, e.g. for shift-clicking.
+    pub fn set_modifiers(&mut self, modifiers: Modifiers) {
+        self.modifiers = modifiers;
+    }
+
+    /// Where the mouse is, if it was moved with e.g. [`Self::hover`].
+    pub fn pointer_pos(&self) -> Option<Pos2> {
+        self.pointer_pos
+    }
+
+    /// The shapes, platform output (cursor, copied text, opened URLs, …) and repaint request of the last frame.
+    pub fn output(&self) -> &egui::FullOutput {
+        &self.output
+    }
+
+    /// What the app asked of the window in the last frame, e.g. [`AppOutput::close`] after [`Frame::close`].
+    pub fn app_output(&self) -> &AppOutput {
+        &self.app_output
+    }
+
+    /// What the app asked of the window in each frame so far.
+    pub fn app_outputs(&self) -> &[AppOutput] {
+        &self.app_outputs
+    }
+}
+
+fn default_screen_size() -> Vec2 {
+    egui::vec2(800.0, 600.0)
+}
+
+fn integration_info(screen_size: Vec2) -> IntegrationInfo {
+    IntegrationInfo {
+        system_theme: None,
+        cpu_usage: None,
+        native_pixels_per_point: Some(1.0),
+        window_info: WindowInfo {
+            position: Some(Pos2::ZERO),
+            fullscreen: false,
+            minimized: false,
+            maximized: false,
+            focused: true,
+            size: screen_size,
+            monitor_size: None,
+        },
+        #[cfg(feature = "glow")]
+        painter_stats: None,
+        #[cfg(feature = "glow")]
+        gpu_timings: None,
+        #[cfg(feature = "glow")]
+        predicted_present_time: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct TestApp {
+        count: u32,
+        text: String,
+        focus_text: bool,
+        /// The modifiers egui saw for each press of Z.
+        z_presses: Vec<Modifiers>,
+        /// The modifiers held in the last frame.
+        modifiers: Modifiers,
+    }
+
+    impl App for TestApp {
+        fn update(&mut self, ctx: &egui::Context, frame: &mut Frame) {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                ui.label(format!("Count: {}", self.count));
+                if ui.button("Increment").clicked() {
+                    self.count += 1;
+                }
+                let text_edit = ui.text_edit_singleline(&mut self.text);
+                if std::mem::take(&mut self.focus_text) {
+                    text_edit.request_focus();
+                }
+                if ui.button("Resize").clicked() {
+                    frame.set_window_size(egui::vec2(320.0, 240.0));
+                }
+                if ui.button("Rename").clicked() {
+                    frame.set_window_title("Renamed");
+                }
+                if ui.button("Quit").clicked() {
+                    frame.close();
+                }
+            });
+            ctx.input(|input| {
+                for event in &input.events {
+                    if let Event::Key {
+                        key: Key::Z,
+                        pressed: true,
+                        ..
+                    } = event
+                    {
+                        self.z_presses.push(input.modifiers);
+                    }
+                }
+                self.modifiers = input.modifiers;
+            });
+        }
+    }
+
+    #[test]
+    fn click_label_clicks_the_button_with_that_text() {
+        let mut harness = TestHarness::new(TestApp::default());
+        harness.run();
+        harness.click_label("Increment");
+        harness.click_label("Increment");
+        assert_eq!(harness.app.count, 2);
+        harness.run();
+        assert!(harness.find_label("Count: 2").is_some());
+        assert!(harness.find_label("Count: 1").is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "No label \"Decrement\"")]
+    fn click_label_panics_on_missing_labels() {
+        let mut harness = TestHarness::new(TestApp::default());
+        harness.run();
+        harness.click_label("Decrement");
+    }
+
+    #[test]
+    fn type_text_goes_to_the_focused_widget() {
+        let mut harness = TestHarness::new(TestApp {
+            focus_text: true,
+            ..Default::default()
+        });
+        harness.run();
+        harness.type_text("hello");
+        harness.type_text(" world");
+        assert_eq!(harness.app.text, "hello world");
+    }
+
+    #[test]
+    fn press_key_holds_the_modifiers_for_one_frame() {
+        let mut harness = TestHarness::new(TestApp::default());
+        harness.run();
+        harness.press_key(Modifiers::NONE, Key::Z);
+        harness.press_key(Modifiers::COMMAND, Key::Z);
+        let chord = Modifiers::CTRL | Modifiers::SHIFT;
+        harness.press_key(chord, Key::Z);
+        assert_eq!(
+            harness.app.z_presses,
+            [Modifiers::NONE, Modifiers::COMMAND, chord]
+        );
+        assert_eq!(harness.app.modifiers, chord);
+
+        harness.run();
+        assert_eq!(harness.app.modifiers, Modifiers::NONE);
+
+        // Held modifiers come back after a chord:
+        harness.set_modifiers(Modifiers::SHIFT);
+        harness.press_key(Modifiers::ALT, Key::Z);
+        harness.run();
+        assert_eq!(harness.app.z_presses.last(), Some(&Modifiers::ALT));
+        assert_eq!(harness.app.modifiers, Modifiers::SHIFT);
+    }
+
+    #[test]
+    fn app_outputs_are_recorded_per_frame() {
+        let mut harness = TestHarness::new(TestApp::default());
+        harness.run();
+        assert_eq!(harness.app_output().window_size, None);
+
+        harness.click_label("Resize");
+        assert_eq!(
+            harness.app_output().window_size,
+            Some(egui::vec2(320.0, 240.0))
+        );
+        // So later frames lay out for the new size:
+        assert_eq!(
+            harness.frame().info().window_info.size,
+            egui::vec2(320.0, 240.0)
+        );
+        harness.click_label("Rename");
+        assert_eq!(
+            harness.app_output().window_title.as_deref(),
+            Some("Renamed")
+        );
+        assert_eq!(harness.app_output().window_size, None);
+        assert!(!harness.app_output().close);
+
+        harness.click_label("Quit");
+        assert!(harness.app_output().close);
+
+        let outputs = harness.app_outputs();
+        // One run, then three frames per click:
+        assert_eq!(outputs.len(), 1 + 3 * 3);
+        assert_eq!(
+            outputs.iter().filter(|o| o.window_size.is_some()).count(),
+            1
+        );
+        assert_eq!(
+            outputs.iter().filter(|o| o.window_title.is_some()).count(),
+            1
+        );
+        assert_eq!(outputs.iter().filter(|o| o.close).count(), 1);
+    }
+}
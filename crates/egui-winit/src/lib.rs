@@ -573,31 +573,32 @@ impl State {
     }
 
     fn on_keyboard_input(&mut self, input: &winit::event::KeyEvent) {
-        let keycode = input.physical_key;
         let pressed = input.state == winit::event::ElementState::Pressed;
 
-        if pressed {
-            // VirtualKeyCode::Paste etc in winit are broken/untrustworthy,
-            // so we detect these things manually:
-            if is_cut_command(self.egui_input.modifiers, keycode) {
-                self.egui_input.events.push(egui::Event::Cut);
-            } else if is_copy_command(self.egui_input.modifiers, keycode) {
-                self.egui_input.events.push(egui::Event::Copy);
-            } else if is_paste_command(self.egui_input.modifiers, keycode) {
-                if let Some(contents) = self.clipboard.get() {
-                    let contents = contents.replace("\r\n", "\n");
-                    if !contents.is_empty() {
-                        self.egui_input.events.push(egui::Event::Paste(contents));
+        let key = translate_key(&input.logical_key, input.physical_key);
+
+        if let Some(key) = key {
+            if pressed {
+                // VirtualKeyCode::Paste etc in winit are broken/untrustworthy,
+                // so we detect these things manually:
+                if is_cut_command(self.egui_input.modifiers, key) {
+                    self.egui_input.events.push(egui::Event::Cut);
+                } else if is_copy_command(self.egui_input.modifiers, key) {
+                    self.egui_input.events.push(egui::Event::Copy);
+                } else if is_paste_command(self.egui_input.modifiers, key) {
+                    if let Some(contents) = self.clipboard.get() {
+                        let contents = contents.replace("\r\n", "\n");
+                        if !contents.is_empty() {
+                            self.egui_input.events.push(egui::Event::Paste(contents));
+                        }
                     }
                 }
             }
-        }
 
-        if let Some(key) = translate_virtual_key_code(keycode) {
             self.egui_input.events.push(egui::Event::Key {
                 key,
                 pressed,
-                repeat: false, // egui will fill this in for us!
+                repeat: input.repeat,
                 modifiers: self.egui_input.modifiers,
             });
         }
@@ -699,25 +700,19 @@ fn is_printable_char(chr: char) -> bool {
     !is_in_private_use_area && !chr.is_ascii_control()
 }
 
-fn is_cut_command(modifiers: egui::Modifiers, keycode: winit::keyboard::KeyCode) -> bool {
-    (modifiers.command && keycode == winit::keyboard::KeyCode::KeyX)
-        || (cfg!(target_os = "windows")
-            && modifiers.shift
-            && keycode == winit::keyboard::KeyCode::Delete)
+fn is_cut_command(modifiers: egui::Modifiers, key: egui::Key) -> bool {
+    (modifiers.command && key == egui::Key::X)
+        || (cfg!(target_os = "windows") && modifiers.shift && key == egui::Key::Delete)
 }
 
-fn is_copy_command(modifiers: egui::Modifiers, keycode: winit::keyboard::KeyCode) -> bool {
-    (modifiers.command && keycode == winit::keyboard::KeyCode::KeyC)
-        || (cfg!(target_os = "windows")
-            && modifiers.ctrl
-            && keycode == winit::keyboard::KeyCode::Insert)
+fn is_copy_command(modifiers: egui::Modifiers, key: egui::Key) -> bool {
+    (modifiers.command && key == egui::Key::C)
+        || (cfg!(target_os = "windows") && modifiers.ctrl && key == egui::Key::Insert)
 }
 
-fn is_paste_command(modifiers: egui::Modifiers, keycode: winit::keyboard::KeyCode) -> bool {
-    (modifiers.command && keycode == winit::keyboard::KeyCode::KeyV)
-        || (cfg!(target_os = "windows")
-            && modifiers.shift
-            && keycode == winit::keyboard::KeyCode::Insert)
+fn is_paste_command(modifiers: egui::Modifiers, key: egui::Key) -> bool {
+    (modifiers.command && key == egui::Key::V)
+        || (cfg!(target_os = "windows") && modifiers.shift && key == egui::Key::Insert)
 }
 
 fn translate_mouse_button(button: winit::event::MouseButton) -> Option<egui::PointerButton> {
@@ -732,7 +727,151 @@ fn translate_mouse_button(button: winit::event::MouseButton) -> Option<egui::Poi
     }
 }
 
-fn translate_virtual_key_code(key: winit::keyboard::KeyCode) -> Option<egui::Key> {
+/// The [`egui::Key`] for a key event.
+///
+/// The key as labeled in the current keyboard layout, so that e.g. ctrl-Z is undo on AZERTY too.
+/// Other keys fall back to their position: those that type no character or a non-latin one
+/// (e.g. on a cyrillic layout), and digit and punctuation keys, whose character depends on shift
+/// (`!` is shift-1) or the layout (AZERTY's digit row types `&é"'(`).
+/// Latin punctuation typed by a letter key gives `None`: on Dvorak, the key where QWERTY has Q types `'`,
+/// and must not be reported as Q.
+fn translate_key(
+    logical_key: &winit::keyboard::Key<'_>,
+    physical_key: winit::keyboard::KeyCode,
+) -> Option<egui::Key> {
+    if let Some(key) = translate_logical_key(logical_key) {
+        return Some(key);
+    }
+    let key = translate_physical_key(physical_key)?;
+    match logical_key {
+        winit::keyboard::Key::Character(text)
+            if text.is_ascii() && (egui::Key::A..=egui::Key::Z).contains(&key) =>
+        {
+            None
+        }
+        _ => Some(key),
+    }
+}
+
+/// The [`egui::Key`] for a key as the current keyboard layout labels it, i.e. [`winit::event::KeyEvent::logical_key`].
+///
+/// Returns `None` for keys egui has no [`egui::Key`] for, like media keys,
+/// and for characters other than latin letters, digits, `-`, `+` and `=`.
+pub fn translate_logical_key(key: &winit::keyboard::Key<'_>) -> Option<egui::Key> {
+    use egui::Key;
+    use winit::keyboard::Key as LogicalKey;
+
+    Some(match key {
+        LogicalKey::Character(text) => {
+            let mut chars = text.chars();
+            let (Some(c), None) = (chars.next(), chars.next()) else {
+                return None;
+            };
+            return translate_char(c);
+        }
+
+        LogicalKey::ArrowDown => Key::ArrowDown,
+        LogicalKey::ArrowLeft => Key::ArrowLeft,
+        LogicalKey::ArrowRight => Key::ArrowRight,
+        LogicalKey::ArrowUp => Key::ArrowUp,
+
+        LogicalKey::Escape => Key::Escape,
+        LogicalKey::Tab => Key::Tab,
+        LogicalKey::Backspace => Key::Backspace,
+        LogicalKey::Enter => Key::Enter,
+        LogicalKey::Space => Key::Space,
+
+        LogicalKey::Insert => Key::Insert,
+        LogicalKey::Delete => Key::Delete,
+        LogicalKey::Home => Key::Home,
+        LogicalKey::End => Key::End,
+        LogicalKey::PageUp => Key::PageUp,
+        LogicalKey::PageDown => Key::PageDown,
+
+        LogicalKey::F1 => Key::F1,
+        LogicalKey::F2 => Key::F2,
+        LogicalKey::F3 => Key::F3,
+        LogicalKey::F4 => Key::F4,
+        LogicalKey::F5 => Key::F5,
+        LogicalKey::F6 => Key::F6,
+        LogicalKey::F7 => Key::F7,
+        LogicalKey::F8 => Key::F8,
+        LogicalKey::F9 => Key::F9,
+        LogicalKey::F10 => Key::F10,
+        LogicalKey::F11 => Key::F11,
+        LogicalKey::F12 => Key::F12,
+        LogicalKey::F13 => Key::F13,
+        LogicalKey::F14 => Key::F14,
+        LogicalKey::F15 => Key::F15,
+        LogicalKey::F16 => Key::F16,
+        LogicalKey::F17 => Key::F17,
+        LogicalKey::F18 => Key::F18,
+        LogicalKey::F19 => Key::F19,
+        LogicalKey::F20 => Key::F20,
+
+        _ => {
+            return None;
+        }
+    })
+}
+
+fn translate_char(c: char) -> Option<egui::Key> {
+    use egui::Key;
+
+    Some(match c.to_ascii_lowercase() {
+        ' ' => Key::Space,
+        '-' => Key::Minus,
+        '+' | '=' => Key::PlusEquals,
+
+        '0' => Key::Num0,
+        '1' => Key::Num1,
+        '2' => Key::Num2,
+        '3' => Key::Num3,
+        '4' => Key::Num4,
+        '5' => Key::Num5,
+        '6' => Key::Num6,
+        '7' => Key::Num7,
+        '8' => Key::Num8,
+        '9' => Key::Num9,
+
+        'a' => Key::A,
+        'b' => Key::B,
+        'c' => Key::C,
+        'd' => Key::D,
+        'e' => Key::E,
+        'f' => Key::F,
+        'g' => Key::G,
+        'h' => Key::H,
+        'i' => Key::I,
+        'j' => Key::J,
+        'k' => Key::K,
+        'l' => Key::L,
+        'm' => Key::M,
+        'n' => Key::N,
+        'o' => Key::O,
+        'p' => Key::P,
+        'q' => Key::Q,
+        'r' => Key::R,
+        's' => Key::S,
+        't' => Key::T,
+        'u' => Key::U,
+        'v' => Key::V,
+        'w' => Key::W,
+        'x' => Key::X,
+        'y' => Key::Y,
+        'z' => Key::Z,
+
+        _ => {
+            return None;
+        }
+    })
+}
+
+/// The [`egui::Key`] for the key at this position on a US keyboard, whatever the current layout,
+/// i.e. for [`winit::event::KeyEvent::physical_key`].
+///
+/// `egui-winit` uses this for keys that type no character or a non-latin one, see [`translate_logical_key`].
+pub fn translate_physical_key(key: winit::keyboard::KeyCode) -> Option<egui::Key> {
     use egui::Key;
     use winit::keyboard::KeyCode;
 
@@ -744,8 +883,8 @@ fn translate_virtual_key_code(key: winit::keyboard::KeyCode) -> Option<egui::Key
 
         KeyCode::Escape => Key::Escape,
         KeyCode::Tab => Key::Tab,
-        KeyCode::Backspace => Key::Backspace,
-        KeyCode::Enter => Key::Enter,
+        KeyCode::Backspace | KeyCode::NumpadBackspace => Key::Backspace,
+        KeyCode::Enter | KeyCode::NumpadEnter => Key::Enter,
         KeyCode::Space => Key::Space,
 
         KeyCode::Insert => Key::Insert,
@@ -755,7 +894,8 @@ fn translate_virtual_key_code(key: winit::keyboard::KeyCode) -> Option<egui::Key
         KeyCode::PageUp => Key::PageUp,
         KeyCode::PageDown => Key::PageDown,
 
-        KeyCode::Minus => Key::Minus,
+        KeyCode::Minus | KeyCode::NumpadSubtract => Key::Minus,
+        KeyCode::NumpadAdd | KeyCode::NumpadEqual => Key::PlusEquals,
         // Using Mac the key with the Plus sign on it is reported as the Equals key
         // (with both English and Swedish keyboard).
         KeyCode::Equal => Key::PlusEquals,
@@ -894,3 +1034,166 @@ macro_rules! profile_scope {
 
 #[allow(unused_imports)]
 pub(crate) use profile_scope;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::Key;
+    use winit::keyboard::{Key as LogicalKey, KeyCode, NativeKeyCode};
+
+    #[test]
+    fn translate_char_knows_latin_letters_and_digits() {
+        assert_eq!(translate_char('a'), Some(Key::A));
+        assert_eq!(translate_char('A'), Some(Key::A));
+        assert_eq!(translate_char('7'), Some(Key::Num7));
+        assert_eq!(translate_char(' '), Some(Key::Space));
+        assert_eq!(translate_char('-'), Some(Key::Minus));
+        assert_eq!(translate_char('+'), Some(Key::PlusEquals));
+        assert_eq!(translate_char('='), Some(Key::PlusEquals));
+
+        for c in [',', '\'', '&', ';', 'é', 'ß', 'й', 'Я'] {
+            assert_eq!(translate_char(c), None, "{c:?}");
+        }
+    }
+
+    #[test]
+    fn translate_logical_key_takes_single_characters() {
+        assert_eq!(
+            translate_logical_key(&LogicalKey::Character("q")),
+            Some(Key::Q)
+        );
+        assert_eq!(
+            translate_logical_key(&LogicalKey::Character("Q")),
+            Some(Key::Q)
+        );
+        assert_eq!(translate_logical_key(&LogicalKey::Character("qu")), None);
+        assert_eq!(translate_logical_key(&LogicalKey::Character("")), None);
+        assert_eq!(translate_logical_key(&LogicalKey::Character(",")), None);
+        assert_eq!(translate_logical_key(&LogicalKey::Character("й")), None);
+        assert_eq!(
+            translate_logical_key(&LogicalKey::Escape),
+            Some(Key::Escape)
+        );
+        assert_eq!(translate_logical_key(&LogicalKey::Dead(Some('^'))), None);
+    }
+
+    #[test]
+    fn azerty() {
+        // The letters are where the layout puts them:
+        assert_eq!(
+            translate_key(&LogicalKey::Character("a"), KeyCode::KeyQ),
+            Some(Key::A)
+        );
+        assert_eq!(
+            translate_key(&LogicalKey::Character("z"), KeyCode::KeyW),
+            Some(Key::Z)
+        );
+        assert_eq!(
+            translate_key(&LogicalKey::Character("m"), KeyCode::Semicolon),
+            Some(Key::M)
+        );
+        // Where QWERTY has M, AZERTY has `,`, which is no second M:
+        assert_eq!(
+            translate_key(&LogicalKey::Character(","), KeyCode::KeyM),
+            None
+        );
+        // The digit row types punctuation and accented letters:
+        assert_eq!(
+            translate_key(&LogicalKey::Character("&"), KeyCode::Digit1),
+            Some(Key::Num1)
+        );
+        assert_eq!(
+            translate_key(&LogicalKey::Character("("), KeyCode::Digit5),
+            Some(Key::Num5)
+        );
+        assert_eq!(
+            translate_key(&LogicalKey::Character("é"), KeyCode::Digit2),
+            Some(Key::Num2)
+        );
+    }
+
+    #[test]
+    fn shifted_digits_and_punctuation_keep_their_key() {
+        // `logical_key` includes shift:
+        assert_eq!(
+            translate_key(&LogicalKey::Character("!"), KeyCode::Digit1),
+            Some(Key::Num1)
+        );
+        assert_eq!(
+            translate_key(&LogicalKey::Character(")"), KeyCode::Digit0),
+            Some(Key::Num0)
+        );
+        assert_eq!(
+            translate_key(&LogicalKey::Character("_"), KeyCode::Minus),
+            Some(Key::Minus)
+        );
+        assert_eq!(
+            translate_key(&LogicalKey::Character("+"), KeyCode::Equal),
+            Some(Key::PlusEquals)
+        );
+        // Shifted letters are still letters:
+        assert_eq!(
+            translate_key(&LogicalKey::Character("Z"), KeyCode::KeyZ),
+            Some(Key::Z)
+        );
+    }
+
+    #[test]
+    fn dvorak() {
+        assert_eq!(
+            translate_key(&LogicalKey::Character("'"), KeyCode::KeyQ),
+            None
+        );
+        assert_eq!(
+            translate_key(&LogicalKey::Character(","), KeyCode::KeyW),
+            None
+        );
+        assert_eq!(
+            translate_key(&LogicalKey::Character("q"), KeyCode::KeyX),
+            Some(Key::Q)
+        );
+        assert_eq!(
+            translate_key(&LogicalKey::Character("z"), KeyCode::Slash),
+            Some(Key::Z)
+        );
+    }
+
+    #[test]
+    fn cyrillic_falls_back_to_the_position() {
+        assert_eq!(
+            translate_key(&LogicalKey::Character("й"), KeyCode::KeyQ),
+            Some(Key::Q)
+        );
+        assert_eq!(
+            translate_key(&LogicalKey::Character("Я"), KeyCode::KeyZ),
+            Some(Key::Z)
+        );
+        assert_eq!(
+            translate_key(&LogicalKey::Character("с"), KeyCode::KeyC),
+            Some(Key::C)
+        );
+    }
+
+    #[test]
+    fn keys_without_characters_fall_back_to_the_position() {
+        assert_eq!(
+            translate_key(&LogicalKey::Escape, KeyCode::Escape),
+            Some(Key::Escape)
+        );
+        assert_eq!(
+            translate_key(
+                &LogicalKey::Unidentified(NativeKeyCode::Unidentified),
+                KeyCode::KeyA
+            ),
+            Some(Key::A)
+        );
+        assert_eq!(
+            translate_key(&LogicalKey::Dead(Some('^')), KeyCode::Digit6),
+            Some(Key::Num6)
+        );
+        assert_eq!(
+            translate_key(&LogicalKey::AudioVolumeUp, KeyCode::AudioVolumeUp),
+            None
+        );
+    }
+}
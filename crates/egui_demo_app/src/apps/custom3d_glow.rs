@@ -1,22 +1,22 @@
-use std::sync::Arc;
-
 use eframe::egui_glow;
-use egui::mutex::Mutex;
 use egui_glow::glow;
 
 pub struct Custom3d {
-    /// Behind an `Arc<Mutex<…>>` so we can pass it to [`egui::PaintCallback`] and paint later.
-    rotating_triangle: Arc<Mutex<RotatingTriangle>>,
     angle: f32,
 }
 
 impl Custom3d {
     pub fn new<'a>(cc: &'a eframe::CreationContext<'a>) -> Option<Self> {
         let gl = cc.gl.as_ref()?;
-        Some(Self {
-            rotating_triangle: Arc::new(Mutex::new(RotatingTriangle::new(gl)?)),
-            angle: 0.0,
-        })
+        let shader_version = egui_glow::ShaderVersion::get(gl);
+        if !shader_version.is_new_shader_interface() {
+            log::warn!(
+                "Custom 3D painting hasn't been ported to {:?}",
+                shader_version
+            );
+            return None;
+        }
+        Some(Self { angle: 0.0 })
     }
 }
 
@@ -42,12 +42,6 @@ impl eframe::App for Custom3d {
                 });
         });
     }
-
-    fn on_exit(&mut self, gl: Option<&glow::Context>) {
-        if let Some(gl) = gl {
-            self.rotating_triangle.lock().destroy(gl);
-        }
-    }
 }
 
 impl Custom3d {
@@ -57,19 +51,38 @@ impl Custom3d {
 
         self.angle += response.drag_delta().x * 0.01;
 
-        // Clone locals so we can move them into the paint callback:
-        let angle = self.angle;
-        let rotating_triangle = self.rotating_triangle.clone();
+        ui.painter().add(egui_glow::Callback::new_paint_callback(
+            rect,
+            RotatingTriangleCallback { angle: self.angle },
+        ));
+    }
+}
 
-        let cb = egui_glow::CallbackFn::new(move |_info, painter| {
-            rotating_triangle.lock().paint(painter.gl(), angle);
-        });
+/// The per-frame part of the painting.
+/// The [`RotatingTriangle`] itself lives in the painter's [`egui_glow::CallbackResources`].
+struct RotatingTriangleCallback {
+    angle: f32,
+}
 
-        let callback = egui::PaintCallback {
-            rect,
-            callback: Arc::new(cb),
-        };
-        ui.painter().add(callback);
+impl egui_glow::CallbackTrait for RotatingTriangleCallback {
+    fn prepare(
+        &self,
+        _info: &egui::PaintCallbackInfo,
+        painter: &egui_glow::Painter,
+        resources: &mut egui_glow::CallbackResources,
+    ) {
+        resources.get_or_insert_with(|| RotatingTriangle::new(painter.gl()));
+    }
+
+    fn paint(
+        &self,
+        _info: egui::PaintCallbackInfo,
+        painter: &egui_glow::Painter,
+        resources: &egui_glow::CallbackResources,
+    ) {
+        if let Some(rotating_triangle) = resources.get::<RotatingTriangle>() {
+            rotating_triangle.paint(painter.gl(), self.angle);
+        }
     }
 }
 
@@ -80,7 +93,7 @@ struct RotatingTriangle {
 
 #[allow(unsafe_code)] // we need unsafe code to use glow
 impl RotatingTriangle {
-    fn new(gl: &glow::Context) -> Option<Self> {
+    fn new(gl: &glow::Context) -> Self {
         use glow::HasContext as _;
 
         let shader_version = egui_glow::ShaderVersion::get(gl);
@@ -88,14 +101,6 @@ impl RotatingTriangle {
         unsafe {
             let program = gl.create_program().expect("Cannot create program");
 
-            if !shader_version.is_new_shader_interface() {
-                log::warn!(
-                    "Custom 3D painting hasn't been ported to {:?}",
-                    shader_version
-                );
-                return None;
-            }
-
             let (vertex_shader_source, fragment_shader_source) = (
                 r#"
                     const vec2 verts[3] = vec2[3](
@@ -173,18 +178,10 @@ impl RotatingTriangle {
                 .create_vertex_array()
                 .expect("Cannot create vertex array");
 
-            Some(Self {
+            Self {
                 program,
                 vertex_array,
-            })
-        }
-    }
-
-    fn destroy(&self, gl: &glow::Context) {
-        use glow::HasContext as _;
-        unsafe {
-            gl.delete_program(self.program);
-            gl.delete_vertex_array(self.vertex_array);
+            }
         }
     }
 
@@ -201,3 +198,14 @@ impl RotatingTriangle {
         }
     }
 }
+
+#[allow(unsafe_code)] // we need unsafe code to use glow
+impl egui_glow::CallbackResource for RotatingTriangle {
+    fn destroy(&self, gl: &glow::Context) {
+        use glow::HasContext as _;
+        unsafe {
+            gl.delete_program(self.program);
+            gl.delete_vertex_array(self.vertex_array);
+        }
+    }
+}
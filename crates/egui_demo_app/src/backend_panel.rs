@@ -58,6 +58,10 @@ pub struct BackendPanel {
     #[cfg_attr(feature = "serde", serde(skip))]
     frame_history: crate::frame_history::FrameHistory,
 
+    #[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    colorblind_simulation: Option<eframe::egui_glow::ColorblindSimulation>,
+
     egui_windows: EguiWindows,
 }
 
@@ -196,6 +200,43 @@ impl BackendPanel {
                 .on_hover_ui(|ui| {
                     window_info_ui(ui, &frame.info().window_info);
                 });
+
+            #[cfg(feature = "glow")]
+            if let Some(painter_stats) = frame.info().painter_stats {
+                ui.button("Renderer stats (hover me)").on_hover_ui(|ui| {
+                    painter_stats_ui(ui, &painter_stats);
+                });
+            }
+        }
+
+        #[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+        self.colorblind_simulation_ui(ui, frame);
+    }
+
+    #[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+    fn colorblind_simulation_ui(&mut self, ui: &mut egui::Ui, frame: &mut eframe::Frame) {
+        use eframe::egui_glow::ColorblindSimulation;
+
+        let before = self.colorblind_simulation;
+        egui::ComboBox::from_label("Colorblind simulation")
+            .selected_text(match self.colorblind_simulation {
+                Some(simulation) => simulation.to_string(),
+                None => "Off".to_owned(),
+            })
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.colorblind_simulation, None, "Off");
+                for simulation in ColorblindSimulation::ALL {
+                    ui.selectable_value(
+                        &mut self.colorblind_simulation,
+                        Some(simulation),
+                        simulation.to_string(),
+                    );
+                }
+            })
+            .response
+            .on_hover_text("Preview how the app looks to people with color vision deficiencies. Only works with the glow renderer.");
+        if self.colorblind_simulation != before {
+            frame.set_colorblind_simulation(self.colorblind_simulation);
         }
     }
 
@@ -472,3 +513,49 @@ fn call_after_delay(delay: std::time::Duration, f: impl FnOnce() + Send + 'stati
         .unwrap();
     closure.forget(); // We must forget it, or else the callback is canceled on drop
 }
+
+#[cfg(all(feature = "glow", not(target_arch = "wasm32")))]
+fn painter_stats_ui(ui: &mut egui::Ui, stats: &eframe::egui_glow::PainterStats) {
+    let eframe::egui_glow::PainterStats {
+        draw_calls,
+        vertices,
+        indices,
+        texture_upload_bytes,
+        textures,
+        texture_bytes,
+        callbacks,
+        gl_errors,
+    } = *stats;
+
+    egui::Grid::new("painter_stats_grid")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Draw calls:");
+            ui.monospace(draw_calls.to_string());
+            ui.end_row();
+
+            ui.label("Vertices:");
+            ui.monospace(vertices.to_string());
+            ui.end_row();
+
+            ui.label("Triangles:");
+            ui.monospace((indices / 3).to_string());
+            ui.end_row();
+
+            ui.label("Texture uploads:");
+            ui.monospace(format!("{:.1} kB", texture_upload_bytes as f64 / 1e3));
+            ui.end_row();
+
+            ui.label("Textures:");
+            ui.monospace(format!("{textures} ({:.1} MB)", texture_bytes as f64 / 1e6));
+            ui.end_row();
+
+            ui.label("Callbacks:");
+            ui.monospace(callbacks.to_string());
+            ui.end_row();
+
+            ui.label("GL errors:");
+            ui.monospace(gl_errors.to_string());
+            ui.end_row();
+        });
+}
@@ -0,0 +1,162 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::Arc,
+};
+
+use egui::PaintCallbackInfo;
+
+use crate::{Painter, SendSyncIfNative};
+
+/// A long-lived resource used by paint callbacks, stored in [`CallbackResources`].
+///
+/// Typically this holds shader programs, buffers and textures.
+pub trait CallbackResource: Any + SendSyncIfNative {
+    /// Release the OpenGL objects owned by the resource.
+    ///
+    /// Called by [`Painter::destroy`] for every resource still in the [`CallbackResources`].
+    fn destroy(&self, gl: &glow::Context);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+type AnyResource = dyn Any + Send + Sync;
+
+#[cfg(target_arch = "wasm32")]
+type AnyResource = dyn Any;
+
+struct Entry {
+    value: Box<AnyResource>,
+    destroy: fn(&dyn Any, &glow::Context),
+}
+
+fn destroy_entry<T: CallbackResource>(value: &dyn Any, gl: &glow::Context) {
+    if let Some(value) = value.downcast_ref::<T>() {
+        value.destroy(gl);
+    }
+}
+
+/// A map from type to a value of that type, for resources shared between paint callbacks
+/// and kept across frames.
+///
+/// Owned by the [`Painter`], see [`Painter::callback_resources_mut`].
+/// Everything still in here is destroyed in [`Painter::destroy`].
+///
+/// This mirrors `egui_wgpu::CallbackResources`.
+#[derive(Default)]
+pub struct CallbackResources {
+    map: HashMap<TypeId, Entry>,
+}
+
+impl CallbackResources {
+    /// Insert a resource, returning the previous one of the same type, if any.
+    ///
+    /// The returned resource is no longer owned by the map, so you need to destroy it yourself.
+    pub fn insert<T: CallbackResource>(&mut self, value: T) -> Option<T> {
+        let entry = Entry {
+            value: Box::new(value),
+            destroy: destroy_entry::<T>,
+        };
+        self.map
+            .insert(TypeId::of::<T>(), entry)
+            .and_then(|old| old.value.downcast().ok())
+            .map(|old| *old)
+    }
+
+    pub fn get<T: CallbackResource>(&self) -> Option<&T> {
+        self.map
+            .get(&TypeId::of::<T>())
+            .and_then(|entry| entry.value.downcast_ref())
+    }
+
+    pub fn get_mut<T: CallbackResource>(&mut self) -> Option<&mut T> {
+        self.map
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|entry| entry.value.downcast_mut())
+    }
+
+    /// Get the resource of type `T`, inserting it with `create` if there is none.
+    pub fn get_or_insert_with<T: CallbackResource>(
+        &mut self,
+        create: impl FnOnce() -> T,
+    ) -> &mut T {
+        let entry = self.map.entry(TypeId::of::<T>()).or_insert_with(|| Entry {
+            value: Box::new(create()),
+            destroy: destroy_entry::<T>,
+        });
+        entry.value.downcast_mut().unwrap()
+    }
+
+    pub fn contains<T: CallbackResource>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Remove a resource. It is no longer owned by the map, so you need to destroy it yourself.
+    pub fn remove<T: CallbackResource>(&mut self) -> Option<T> {
+        self.map
+            .remove(&TypeId::of::<T>())
+            .and_then(|entry| entry.value.downcast().ok())
+            .map(|value| *value)
+    }
+
+    /// Destroy and remove all resources.
+    pub(crate) fn destroy(&mut self, gl: &glow::Context) {
+        for (_, entry) in self.map.drain() {
+            (entry.destroy)(entry.value.as_ref(), gl);
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// A paint callback with a prepare phase and access to [`CallbackResources`].
+///
+/// Turn it into an [`egui::PaintCallback`] with [`Callback::new_paint_callback`].
+///
+/// This mirrors `egui_wgpu::CallbackTrait`.
+pub trait CallbackTrait: Send + Sync {
+    /// Called for each callback before egui is painted.
+    ///
+    /// This is the place to create or update resources, and to render into your own offscreen framebuffers.
+    /// You may change any OpenGL state; the [`Painter`] restores what it needs afterwards.
+    #[allow(unused_variables)]
+    fn prepare(
+        &self,
+        info: &PaintCallbackInfo,
+        painter: &Painter,
+        resources: &mut CallbackResources,
+    ) {
+    }
+
+    /// Paint into the egui framebuffer.
+    ///
+    /// The viewport is set to [`PaintCallbackInfo::viewport`], and the scissor rect to the clip rect.
+    /// If you bind another framebuffer, restore it with [`Painter::intermediate_fbo`] before returning.
+    fn paint(&self, info: PaintCallbackInfo, painter: &Painter, resources: &CallbackResources);
+}
+
+/// Wraps a [`CallbackTrait`] so it can be put in an [`egui::PaintCallback`].
+pub struct Callback(Box<dyn CallbackTrait>);
+
+impl Callback {
+    /// Create a paint callback that is handled by `egui_glow`.
+    pub fn new_paint_callback(
+        rect: egui::Rect,
+        callback: impl CallbackTrait + 'static,
+    ) -> egui::PaintCallback {
+        egui::PaintCallback {
+            rect,
+            callback: Arc::new(Self(Box::new(callback))),
+        }
+    }
+}
+
+/// Find the [`CallbackTrait`] of an [`egui::PaintCallback`] made with [`Callback`] or [`crate::CallbackFn`].
+pub(crate) fn callback_trait(callback: &egui::PaintCallback) -> Option<&dyn CallbackTrait> {
+    if let Some(callback) = callback.callback.downcast_ref::<Callback>() {
+        Some(callback.0.as_ref())
+    } else if let Some(callback) = callback.callback.downcast_ref::<crate::CallbackFn>() {
+        Some(callback)
+    } else {
+        None
+    }
+}
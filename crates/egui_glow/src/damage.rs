@@ -0,0 +1,432 @@
+//! Finding what changed between two frames, for [`crate::Painter::set_damage_tracking`].
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash as _, Hasher as _};
+
+use egui::epaint::{Primitive, Vertex};
+use egui::{ClippedPrimitive, Pos2, Rect};
+
+/// With more damaged regions than this, damage their bounding box instead.
+const MAX_DAMAGE_RECTS: usize = 4;
+
+/// Everything besides the primitives that affects the whole frame.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) struct FrameSetup {
+    pub screen_size_px: [u32; 2],
+    pub pixels_per_point: f32,
+    pub clear_color: [f32; 4],
+}
+
+#[derive(Default)]
+pub(crate) struct DamageTracker {
+    previous_setup: Option<FrameSetup>,
+
+    /// The primitives of the previous frame, and their [`signature`]s.
+    previous: Vec<ClippedPrimitive>,
+    previous_signatures: Vec<Option<u64>>,
+
+    /// Textures whose contents changed since the previous frame.
+    pub changed_textures: HashSet<egui::TextureId>,
+}
+
+impl DamageTracker {
+    /// Damage everything in the next frame.
+    pub(crate) fn invalidate(&mut self) {
+        self.previous_setup = None;
+        self.previous.clear();
+        self.previous_signatures.clear();
+    }
+
+    /// The regions (in physical pixels from the top left) that differ from the previous frame.
+    ///
+    /// Repainting all `primitives` clipped to these regions, on top of the previous frame,
+    /// gives the same image as painting them all from scratch.
+    pub(crate) fn damage(
+        &mut self,
+        setup: FrameSetup,
+        primitives: &[ClippedPrimitive],
+    ) -> Vec<Rect> {
+        crate::profile_function!();
+        let [width, height] = setup.screen_size_px;
+        let screen = Rect::from_min_max(Pos2::ZERO, Pos2::new(width as f32, height as f32));
+
+        let signatures: Vec<Option<u64>> = primitives
+            .iter()
+            .map(|primitive| signature(primitive, &self.changed_textures))
+            .collect();
+        let previous = std::mem::replace(&mut self.previous, primitives.to_vec());
+        let previous_signatures = std::mem::replace(&mut self.previous_signatures, signatures);
+        let changed_textures = std::mem::take(&mut self.changed_textures);
+        if self.previous_setup.replace(setup) != Some(setup) {
+            return vec![screen];
+        }
+
+        // Everything painted the same way before the first and after the last difference
+        // ends up the same, so only the primitives in between matter:
+        let (prefix, suffix) =
+            common_ends(&previous_signatures, &self.previous_signatures, |a, b| {
+                a.is_some() && a == b
+            });
+        let previous = &previous[prefix..previous.len() - suffix];
+        let current = &primitives[prefix..primitives.len() - suffix];
+
+        // The same goes for the triangles of those primitives, since egui merges many shapes into one mesh:
+        let previous_items = items(previous, &changed_textures);
+        let current_items = items(current, &changed_textures);
+        let (prefix, suffix) = common_ends(&previous_items, &current_items, Item::paints_like);
+
+        let mut changed = Vec::new();
+        for items in [
+            &previous_items[prefix..previous_items.len() - suffix],
+            &current_items[prefix..current_items.len() - suffix],
+        ] {
+            // One rectangle per primitive:
+            let mut bounds = Rect::NOTHING;
+            let mut primitive = None;
+            for item in items {
+                if primitive != Some(item.primitive) {
+                    changed.push(bounds);
+                    bounds = Rect::NOTHING;
+                    primitive = Some(item.primitive);
+                }
+                bounds = bounds.union(item.bounds(setup.pixels_per_point));
+            }
+            changed.push(bounds);
+        }
+        merge(
+            changed
+                .into_iter()
+                .map(|bounds| bounds.intersect(screen))
+                .filter(|bounds| bounds.is_positive()),
+        )
+    }
+}
+
+/// How many elements at the start and the end of `a` and `b` are the same.
+fn common_ends<T>(a: &[T], b: &[T], same: impl Fn(&T, &T) -> bool) -> (usize, usize) {
+    let prefix = a.iter().zip(b).take_while(|(a, b)| same(a, b)).count();
+    let suffix = a
+        .iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take(a.len().min(b.len()) - prefix)
+        .take_while(|(a, b)| same(a, b))
+        .count();
+    (prefix, suffix)
+}
+
+/// Equal for primitives that paint the same, unless their textures changed.
+///
+/// `None` for primitives that must be repainted anyway, like paint callbacks.
+fn signature(
+    primitive: &ClippedPrimitive,
+    changed_textures: &HashSet<egui::TextureId>,
+) -> Option<u64> {
+    let Primitive::Mesh(mesh) = &primitive.primitive else {
+        return None; // A callback can paint anything.
+    };
+    if changed_textures.contains(&mesh.texture_id) {
+        return None;
+    }
+    let mut hasher = DefaultHasher::new();
+    let clip_rect = primitive.clip_rect;
+    [
+        clip_rect.min.x,
+        clip_rect.min.y,
+        clip_rect.max.x,
+        clip_rect.max.y,
+    ]
+    .map(f32::to_bits)
+    .hash(&mut hasher);
+    mesh.texture_id.hash(&mut hasher);
+    hasher.write(bytemuck::cast_slice(&mesh.indices));
+    hasher.write(bytemuck::cast_slice(&mesh.vertices));
+    Some(hasher.finish())
+}
+
+/// A triangle or paint callback.
+struct Item<'a> {
+    /// Index of the primitive it belongs to.
+    primitive: usize,
+    clip_rect: Rect,
+
+    /// `None` for callbacks and triangles with changed textures.
+    triangle: Option<(egui::TextureId, [&'a Vertex; 3])>,
+
+    /// For callbacks.
+    rect: Rect,
+}
+
+impl Item<'_> {
+    fn paints_like(&self, other: &Self) -> bool {
+        self.triangle.is_some()
+            && self.triangle == other.triangle
+            && self.clip_rect == other.clip_rect
+    }
+
+    /// Where it may paint, in physical pixels, rounded outwards.
+    fn bounds(&self, pixels_per_point: f32) -> Rect {
+        let painted = match &self.triangle {
+            Some((_, vertices)) => Rect::from_points(&vertices.map(|vertex| vertex.pos)),
+            None => self.rect,
+        };
+        let rect = painted.intersect(self.clip_rect);
+        if !rect.is_positive() {
+            return Rect::NOTHING;
+        }
+        Rect::from_min_max(
+            (rect.min.to_vec2() * pixels_per_point).to_pos2().floor(),
+            (rect.max.to_vec2() * pixels_per_point).to_pos2().ceil(),
+        )
+    }
+}
+
+fn items<'a>(
+    primitives: &'a [ClippedPrimitive],
+    changed_textures: &HashSet<egui::TextureId>,
+) -> Vec<Item<'a>> {
+    let mut items = Vec::new();
+    for (i, primitive) in primitives.iter().enumerate() {
+        match &primitive.primitive {
+            Primitive::Mesh(mesh) => {
+                let changed = changed_textures.contains(&mesh.texture_id);
+                let mut rect = Rect::NOTHING;
+                for triangle in mesh.indices.chunks_exact(3) {
+                    let vertices = [0, 1, 2].map(|j| &mesh.vertices[triangle[j] as usize]);
+                    if changed {
+                        // Compare as a whole, we just need the bounds:
+                        for vertex in vertices {
+                            rect.extend_with(vertex.pos);
+                        }
+                    } else {
+                        items.push(Item {
+                            primitive: i,
+                            clip_rect: primitive.clip_rect,
+                            triangle: Some((mesh.texture_id, vertices)),
+                            rect: Rect::NOTHING,
+                        });
+                    }
+                }
+                if changed {
+                    items.push(Item {
+                        primitive: i,
+                        clip_rect: primitive.clip_rect,
+                        triangle: None,
+                        rect,
+                    });
+                }
+            }
+            Primitive::Callback(callback) => items.push(Item {
+                primitive: i,
+                clip_rect: primitive.clip_rect,
+                triangle: None,
+                rect: callback.rect,
+            }),
+        }
+    }
+    items
+}
+
+/// Merge overlapping rectangles, and everything if there are too many.
+fn merge(rects: impl Iterator<Item = Rect>) -> Vec<Rect> {
+    let mut merged: Vec<Rect> = Vec::new();
+    for mut rect in rects {
+        // Absorb everything `rect` overlaps, which may make it overlap more:
+        while let Some(i) = merged.iter().position(|other| other.intersects(rect)) {
+            rect = rect.union(merged.swap_remove(i));
+        }
+        merged.push(rect);
+    }
+    if merged.len() > MAX_DAMAGE_RECTS {
+        let bounding = merged.iter().fold(Rect::NOTHING, |a, &b| a.union(b));
+        vec![bounding]
+    } else {
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::epaint::Mesh;
+    use egui::{pos2, vec2, Color32};
+
+    const SETUP: FrameSetup = FrameSetup {
+        screen_size_px: [100, 100],
+        pixels_per_point: 1.0,
+        clear_color: [0.0; 4],
+    };
+
+    fn rect(min: [f32; 2], size: [f32; 2]) -> Rect {
+        Rect::from_min_size(pos2(min[0], min[1]), vec2(size[0], size[1]))
+    }
+
+    fn mesh(texture_id: egui::TextureId, rects: &[Rect]) -> ClippedPrimitive {
+        let mut mesh = Mesh::with_texture(texture_id);
+        for &rect in rects {
+            mesh.add_rect_with_uv(
+                rect,
+                Rect::from_min_max(Pos2::ZERO, pos2(1.0, 1.0)),
+                Color32::WHITE,
+            );
+        }
+        ClippedPrimitive {
+            clip_rect: Rect::EVERYTHING,
+            primitive: Primitive::Mesh(mesh),
+        }
+    }
+
+    fn callback(rect: Rect) -> ClippedPrimitive {
+        ClippedPrimitive {
+            clip_rect: Rect::EVERYTHING,
+            primitive: Primitive::Callback(egui::PaintCallback {
+                rect,
+                callback: std::sync::Arc::new(()),
+            }),
+        }
+    }
+
+    fn text() -> Vec<Rect> {
+        (0..10)
+            .map(|i| rect([10.0 + 6.0 * i as f32, 10.0], [5.0, 8.0]))
+            .collect()
+    }
+
+    #[test]
+    fn the_first_frame_is_damaged_everywhere() {
+        let mut tracker = DamageTracker::default();
+        let frame = [mesh(egui::TextureId::default(), &text())];
+        assert_eq!(
+            tracker.damage(SETUP, &frame),
+            [rect([0.0, 0.0], [100.0, 100.0])]
+        );
+    }
+
+    #[test]
+    fn identical_frames_have_no_damage() {
+        let mut tracker = DamageTracker::default();
+        let frame = [mesh(egui::TextureId::default(), &text())];
+        tracker.damage(SETUP, &frame);
+        assert_eq!(tracker.damage(SETUP, &frame), []);
+        assert_eq!(tracker.damage(SETUP, &frame), []);
+    }
+
+    #[test]
+    fn a_blinking_cursor_damages_just_the_cursor() {
+        let mut tracker = DamageTracker::default();
+        let cursor = rect([70.0, 10.0], [1.0, 8.0]);
+        let without_cursor = [mesh(egui::TextureId::default(), &text())];
+        let with_cursor = [mesh(
+            egui::TextureId::default(),
+            &[text(), vec![cursor]].concat(),
+        )];
+        tracker.damage(SETUP, &with_cursor);
+        assert_eq!(tracker.damage(SETUP, &without_cursor), [cursor]);
+        assert_eq!(tracker.damage(SETUP, &with_cursor), [cursor]);
+    }
+
+    #[test]
+    fn a_changed_texture_repaints_its_meshes() {
+        let mut tracker = DamageTracker::default();
+        let image = rect([20.0, 30.0], [40.0, 20.0]);
+        let frame = [
+            mesh(egui::TextureId::default(), &text()),
+            mesh(egui::TextureId::User(1), &[image]),
+        ];
+        tracker.damage(SETUP, &frame);
+        tracker.changed_textures.insert(egui::TextureId::User(1));
+        assert_eq!(tracker.damage(SETUP, &frame), [image]);
+        assert_eq!(tracker.damage(SETUP, &frame), []);
+    }
+
+    #[test]
+    fn callbacks_are_always_repainted() {
+        let mut tracker = DamageTracker::default();
+        let viewport = rect([50.0, 50.0], [30.0, 30.0]);
+        let frame = [
+            mesh(egui::TextureId::default(), &text()),
+            callback(viewport),
+        ];
+        tracker.damage(SETUP, &frame);
+        assert_eq!(tracker.damage(SETUP, &frame), [viewport]);
+    }
+
+    #[test]
+    fn a_setup_change_damages_everything() {
+        let screen = [rect([0.0, 0.0], [100.0, 100.0])];
+        let frame = [mesh(egui::TextureId::default(), &text())];
+        for setup in [
+            FrameSetup {
+                screen_size_px: [100, 50],
+                ..SETUP
+            },
+            FrameSetup {
+                pixels_per_point: 2.0,
+                ..SETUP
+            },
+            FrameSetup {
+                clear_color: [1.0; 4],
+                ..SETUP
+            },
+        ] {
+            let mut tracker = DamageTracker::default();
+            tracker.damage(SETUP, &frame);
+            let [width, height] = setup.screen_size_px;
+            assert_eq!(
+                tracker.damage(setup, &frame),
+                [rect([0.0, 0.0], [width as f32, height as f32])]
+            );
+            assert_eq!(tracker.damage(SETUP, &frame), screen);
+        }
+    }
+
+    #[test]
+    fn invalidating_damages_everything() {
+        let mut tracker = DamageTracker::default();
+        let frame = [mesh(egui::TextureId::default(), &text())];
+        tracker.damage(SETUP, &frame);
+        tracker.invalidate();
+        assert_eq!(
+            tracker.damage(SETUP, &frame),
+            [rect([0.0, 0.0], [100.0, 100.0])]
+        );
+    }
+
+    #[test]
+    fn common_ends_do_not_overlap() {
+        let same = |a: &i32, b: &i32| a == b;
+        assert_eq!(common_ends(&[1, 2, 3], &[1, 2, 3], same), (3, 0));
+        assert_eq!(common_ends(&[1, 2, 3], &[1, 5, 3], same), (1, 1));
+        assert_eq!(common_ends(&[1, 2, 1], &[1], same), (1, 0));
+        assert_eq!(common_ends(&[1, 2], &[2], same), (0, 1));
+        assert_eq!(common_ends(&[], &[1], same), (0, 0));
+    }
+
+    #[test]
+    fn merge_joins_overlapping_rects() {
+        let a = rect([0.0, 0.0], [10.0, 10.0]);
+        let b = rect([5.0, 5.0], [10.0, 10.0]);
+        let c = rect([50.0, 50.0], [10.0, 10.0]);
+        let bridge = rect([8.0, 8.0], [45.0, 45.0]);
+        assert_eq!(merge([a, c].into_iter()), [a, c]);
+        assert_eq!(merge([a, b, c].into_iter()), [a.union(b), c]);
+        assert_eq!(
+            merge([a, c, bridge].into_iter()),
+            [a.union(c).union(bridge)]
+        );
+    }
+
+    #[test]
+    fn merge_falls_back_to_the_bounding_box() {
+        let rects: Vec<Rect> = (0..=MAX_DAMAGE_RECTS)
+            .map(|i| rect([20.0 * i as f32, 0.0], [10.0, 10.0]))
+            .collect();
+        let bounding = rects.iter().fold(Rect::NOTHING, |a, &b| a.union(b));
+        assert_eq!(
+            merge(rects[..MAX_DAMAGE_RECTS].iter().copied()).len(),
+            MAX_DAMAGE_RECTS
+        );
+        assert_eq!(merge(rects.into_iter()), [bounding]);
+    }
+}
@@ -0,0 +1,104 @@
+#![allow(unsafe_code)]
+
+//! `KHR_debug` support: driver messages, object labels and debug groups.
+
+use glow::HasContext as _;
+
+/// Turn on debug output and send the driver messages to [`log`].
+///
+/// Returns `false` if the context doesn't support `KHR_debug` (or OpenGL 4.3 / OpenGL ES 3.2).
+pub(crate) unsafe fn enable_debug_output(gl: &glow::Context) -> bool {
+    if !gl.supports_debug() {
+        return false;
+    }
+    gl.enable(glow::DEBUG_OUTPUT);
+    // Report messages on the thread and in the call that caused them:
+    gl.enable(glow::DEBUG_OUTPUT_SYNCHRONOUS);
+    // `glow` 0.12 hands the driver a pointer to the callback that doesn't outlive this call.
+    // That is only sound for a zero-sized callback, such as a function item.
+    gl.debug_message_callback(log_debug_message);
+    true
+}
+
+fn log_debug_message(source: u32, message_type: u32, id: u32, severity: u32, message: &str) {
+    let source = match source {
+        glow::DEBUG_SOURCE_API => "API",
+        glow::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        glow::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        glow::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        glow::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    };
+    let message_type_str = match message_type {
+        glow::DEBUG_TYPE_ERROR => "error",
+        glow::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated behavior",
+        glow::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        glow::DEBUG_TYPE_PORTABILITY => "portability",
+        glow::DEBUG_TYPE_PERFORMANCE => "performance",
+        glow::DEBUG_TYPE_MARKER => "marker",
+        glow::DEBUG_TYPE_PUSH_GROUP => "push group",
+        glow::DEBUG_TYPE_POP_GROUP => "pop group",
+        _ => "other",
+    };
+    let level = match (message_type, severity) {
+        (glow::DEBUG_TYPE_PUSH_GROUP | glow::DEBUG_TYPE_POP_GROUP, _) => log::Level::Trace,
+        (glow::DEBUG_TYPE_ERROR, _) | (_, glow::DEBUG_SEVERITY_HIGH) => log::Level::Error,
+        (_, glow::DEBUG_SEVERITY_MEDIUM) => log::Level::Warn,
+        (_, glow::DEBUG_SEVERITY_LOW) => log::Level::Info,
+        _ => log::Level::Debug,
+    };
+    log::log!(
+        level,
+        "OpenGL {source} {message_type_str} (id 0x{id:X}): {}",
+        message.trim_end()
+    );
+}
+
+/// An OpenGL object that can be given a label with [`object_label`].
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) trait Object: Copy {
+    const IDENTIFIER: u32;
+
+    fn name(self) -> u32;
+}
+
+macro_rules! impl_object {
+    ($($object: ty => $identifier: expr),* $(,)?) => {
+        $(
+            #[cfg(not(target_arch = "wasm32"))]
+            impl Object for $object {
+                const IDENTIFIER: u32 = $identifier;
+
+                fn name(self) -> u32 {
+                    self.0.get()
+                }
+            }
+        )*
+    };
+}
+
+impl_object! {
+    glow::NativeBuffer => glow::BUFFER,
+    glow::NativeFramebuffer => glow::FRAMEBUFFER,
+    glow::NativeProgram => glow::PROGRAM,
+    glow::NativeTexture => glow::TEXTURE,
+    glow::NativeVertexArray => glow::VERTEX_ARRAY,
+}
+
+/// Object labels are not available on the web.
+#[cfg(target_arch = "wasm32")]
+pub(crate) trait Object {}
+
+#[cfg(target_arch = "wasm32")]
+impl<T> Object for T {}
+
+/// Name an object for debuggers like `RenderDoc` and `apitrace`.
+///
+/// Buffers and vertex arrays must have been bound at least once.
+pub(crate) unsafe fn object_label<T: Object>(gl: &glow::Context, object: T, label: &str) {
+    #[cfg(not(target_arch = "wasm32"))]
+    gl.object_label(T::IDENTIFIER, object.name(), Some(label));
+
+    #[cfg(target_arch = "wasm32")]
+    let _ = (gl, object, label);
+}
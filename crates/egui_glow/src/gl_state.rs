@@ -0,0 +1,552 @@
+#![allow(unsafe_code)]
+
+//! Saving and restoring OpenGL state, for [`crate::Painter::set_preserve_gl_state`].
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::num::NonZeroU32;
+
+#[cfg(not(target_arch = "wasm32"))]
+use glow::HasContext as _;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::check_for_gl_error;
+
+/// What the context supports, which decides what state exists to be saved.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct GlStateSupport {
+    /// OpenGL 3.0+ or OpenGL ES 3.0+: separate read/draw framebuffers and pixel buffers.
+    pub gl3: bool,
+
+    /// Has a `GL_FRAMEBUFFER_SRGB` switch (desktop OpenGL).
+    pub framebuffer_srgb: bool,
+
+    /// Has real vertex array objects (rather than the emulated ones of [`crate::vao`]).
+    pub vertex_arrays: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl GlStateSupport {
+    pub(crate) fn new(gl: &glow::Context) -> Self {
+        let version = gl.version();
+        let gl3 = version.major >= 3;
+        let framebuffer_srgb = gl.supported_extensions().iter().any(|extension| {
+            // GL_ARB_framebuffer_sRGB, GL_EXT_framebuffer_sRGB, GL_EXT_sRGB_write_control, …
+            extension.contains("framebuffer_sRGB") || extension.contains("sRGB_write_control")
+        }) || (!version.is_embedded && gl3);
+        Self {
+            gl3,
+            framebuffer_srgb,
+            vertex_arrays: crate::vao::supports_vao(gl),
+        }
+    }
+}
+
+/// OpenGL object names can't be read back on the web, so there is no state to save there.
+#[cfg(target_arch = "wasm32")]
+pub(crate) enum GlState {}
+
+#[cfg(target_arch = "wasm32")]
+impl GlState {
+    pub(crate) unsafe fn restore(&self, _gl: &glow::Context) {
+        match *self {}
+    }
+}
+
+/// A snapshot of all OpenGL state the [`crate::Painter`] may change.
+///
+/// Object bindings can only be read back as integer names, so this is native-only.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct GlState {
+    support: GlStateSupport,
+
+    viewport: [i32; 4],
+    scissor_box: [i32; 4],
+    clear_color: [f32; 4],
+    color_mask: [i32; 4],
+
+    scissor_test: bool,
+    blend: bool,
+    cull_face: bool,
+    depth_test: bool,
+    stencil_test: bool,
+    framebuffer_srgb: bool,
+
+    blend_src_rgb: i32,
+    blend_dst_rgb: i32,
+    blend_src_alpha: i32,
+    blend_dst_alpha: i32,
+    blend_equation_rgb: i32,
+    blend_equation_alpha: i32,
+
+    draw_framebuffer: i32,
+    read_framebuffer: i32,
+    renderbuffer: i32,
+    program: i32,
+    vertex_array: i32,
+    array_buffer: i32,
+    element_array_buffer: i32,
+    pixel_pack_buffer: i32,
+    pixel_unpack_buffer: i32,
+
+    active_texture: i32,
+    /// `TEXTURE_2D` binding of texture unit 0, which we use for post-processing.
+    texture_unit_0: i32,
+    /// `TEXTURE_2D` binding of the active texture unit, which we use for painting.
+    texture_active_unit: i32,
+    /// `TEXTURE_2D` bindings of texture units 1 and 2, which we use for YUV chroma planes.
+    texture_units_1_2: [i32; 2],
+
+    pack_alignment: i32,
+    unpack_alignment: i32,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl GlState {
+    pub(crate) unsafe fn capture(gl: &glow::Context, support: GlStateSupport) -> Self {
+        crate::profile_function!();
+        let i32_4 = |parameter| {
+            let mut values = [0; 4];
+            gl.get_parameter_i32_slice(parameter, &mut values);
+            values
+        };
+        let i32_if = |supported: bool, parameter| {
+            if supported {
+                gl.get_parameter_i32(parameter)
+            } else {
+                0
+            }
+        };
+
+        let active_texture = gl.get_parameter_i32(glow::ACTIVE_TEXTURE);
+        let texture_active_unit = gl.get_parameter_i32(glow::TEXTURE_BINDING_2D);
+        let mut texture_units = [0; 3];
+        for (unit, binding) in texture_units.iter_mut().enumerate() {
+            gl.active_texture(glow::TEXTURE0 + unit as u32);
+            *binding = gl.get_parameter_i32(glow::TEXTURE_BINDING_2D);
+        }
+        gl.active_texture(active_texture as u32);
+        let [texture_unit_0, texture_units_1_2 @ ..] = texture_units;
+
+        let mut clear_color = [0.0; 4];
+        gl.get_parameter_f32_slice(glow::COLOR_CLEAR_VALUE, &mut clear_color);
+
+        let (draw_framebuffer, read_framebuffer) = if support.gl3 {
+            (
+                gl.get_parameter_i32(glow::DRAW_FRAMEBUFFER_BINDING),
+                gl.get_parameter_i32(glow::READ_FRAMEBUFFER_BINDING),
+            )
+        } else {
+            let framebuffer = gl.get_parameter_i32(glow::FRAMEBUFFER_BINDING);
+            (framebuffer, framebuffer)
+        };
+
+        let state = Self {
+            support,
+
+            viewport: i32_4(glow::VIEWPORT),
+            scissor_box: i32_4(glow::SCISSOR_BOX),
+            clear_color,
+            color_mask: i32_4(glow::COLOR_WRITEMASK),
+
+            scissor_test: gl.is_enabled(glow::SCISSOR_TEST),
+            blend: gl.is_enabled(glow::BLEND),
+            cull_face: gl.is_enabled(glow::CULL_FACE),
+            depth_test: gl.is_enabled(glow::DEPTH_TEST),
+            stencil_test: gl.is_enabled(glow::STENCIL_TEST),
+            framebuffer_srgb: support.framebuffer_srgb && gl.is_enabled(glow::FRAMEBUFFER_SRGB),
+
+            blend_src_rgb: gl.get_parameter_i32(glow::BLEND_SRC_RGB),
+            blend_dst_rgb: gl.get_parameter_i32(glow::BLEND_DST_RGB),
+            blend_src_alpha: gl.get_parameter_i32(glow::BLEND_SRC_ALPHA),
+            blend_dst_alpha: gl.get_parameter_i32(glow::BLEND_DST_ALPHA),
+            blend_equation_rgb: gl.get_parameter_i32(glow::BLEND_EQUATION_RGB),
+            blend_equation_alpha: gl.get_parameter_i32(glow::BLEND_EQUATION_ALPHA),
+
+            draw_framebuffer,
+            read_framebuffer,
+            renderbuffer: gl.get_parameter_i32(glow::RENDERBUFFER_BINDING),
+            program: gl.get_parameter_i32(glow::CURRENT_PROGRAM),
+            vertex_array: i32_if(support.vertex_arrays, glow::VERTEX_ARRAY_BINDING),
+            array_buffer: gl.get_parameter_i32(glow::ARRAY_BUFFER_BINDING),
+            element_array_buffer: gl.get_parameter_i32(glow::ELEMENT_ARRAY_BUFFER_BINDING),
+            pixel_pack_buffer: i32_if(support.gl3, glow::PIXEL_PACK_BUFFER_BINDING),
+            pixel_unpack_buffer: i32_if(support.gl3, glow::PIXEL_UNPACK_BUFFER_BINDING),
+
+            active_texture,
+            texture_unit_0,
+            texture_active_unit,
+            texture_units_1_2,
+
+            pack_alignment: gl.get_parameter_i32(glow::PACK_ALIGNMENT),
+            unpack_alignment: gl.get_parameter_i32(glow::UNPACK_ALIGNMENT),
+        };
+        check_for_gl_error!(gl, "GlState::capture");
+        state
+    }
+
+    pub(crate) unsafe fn restore(&self, gl: &glow::Context) {
+        crate::profile_function!();
+        let Self {
+            support,
+            viewport: [x, y, width, height],
+            scissor_box: [scissor_x, scissor_y, scissor_width, scissor_height],
+            clear_color: [r, g, b, a],
+            color_mask,
+            scissor_test,
+            blend,
+            cull_face,
+            depth_test,
+            stencil_test,
+            framebuffer_srgb,
+            blend_src_rgb,
+            blend_dst_rgb,
+            blend_src_alpha,
+            blend_dst_alpha,
+            blend_equation_rgb,
+            blend_equation_alpha,
+            draw_framebuffer,
+            read_framebuffer,
+            renderbuffer,
+            program,
+            vertex_array,
+            array_buffer,
+            element_array_buffer,
+            pixel_pack_buffer,
+            pixel_unpack_buffer,
+            active_texture,
+            texture_unit_0,
+            texture_active_unit,
+            texture_units_1_2,
+            pack_alignment,
+            unpack_alignment,
+        } = *self;
+
+        gl.viewport(x, y, width, height);
+        gl.scissor(scissor_x, scissor_y, scissor_width, scissor_height);
+        gl.clear_color(r, g, b, a);
+        let [mask_r, mask_g, mask_b, mask_a] = color_mask.map(|mask| mask != 0);
+        gl.color_mask(mask_r, mask_g, mask_b, mask_a);
+
+        set_enabled(gl, glow::SCISSOR_TEST, scissor_test);
+        set_enabled(gl, glow::BLEND, blend);
+        set_enabled(gl, glow::CULL_FACE, cull_face);
+        set_enabled(gl, glow::DEPTH_TEST, depth_test);
+        set_enabled(gl, glow::STENCIL_TEST, stencil_test);
+        if support.framebuffer_srgb {
+            set_enabled(gl, glow::FRAMEBUFFER_SRGB, framebuffer_srgb);
+        }
+
+        gl.blend_func_separate(
+            blend_src_rgb as u32,
+            blend_dst_rgb as u32,
+            blend_src_alpha as u32,
+            blend_dst_alpha as u32,
+        );
+        gl.blend_equation_separate(blend_equation_rgb as u32, blend_equation_alpha as u32);
+
+        if support.gl3 {
+            gl.bind_framebuffer(
+                glow::DRAW_FRAMEBUFFER,
+                name(draw_framebuffer).map(glow::NativeFramebuffer),
+            );
+            gl.bind_framebuffer(
+                glow::READ_FRAMEBUFFER,
+                name(read_framebuffer).map(glow::NativeFramebuffer),
+            );
+            gl.bind_buffer(
+                glow::PIXEL_PACK_BUFFER,
+                name(pixel_pack_buffer).map(glow::NativeBuffer),
+            );
+            gl.bind_buffer(
+                glow::PIXEL_UNPACK_BUFFER,
+                name(pixel_unpack_buffer).map(glow::NativeBuffer),
+            );
+        } else {
+            gl.bind_framebuffer(
+                glow::FRAMEBUFFER,
+                name(draw_framebuffer).map(glow::NativeFramebuffer),
+            );
+        }
+        gl.bind_renderbuffer(
+            glow::RENDERBUFFER,
+            name(renderbuffer).map(glow::NativeRenderbuffer),
+        );
+        gl.use_program(name(program).map(glow::NativeProgram));
+
+        if support.vertex_arrays {
+            gl.bind_vertex_array(name(vertex_array).map(glow::NativeVertexArray));
+        }
+        // The element array buffer is part of the vertex array state, so restore it after the vertex array:
+        gl.bind_buffer(
+            glow::ELEMENT_ARRAY_BUFFER,
+            name(element_array_buffer).map(glow::NativeBuffer),
+        );
+        gl.bind_buffer(
+            glow::ARRAY_BUFFER,
+            name(array_buffer).map(glow::NativeBuffer),
+        );
+
+        for (unit, binding) in std::iter::once(texture_unit_0)
+            .chain(texture_units_1_2)
+            .enumerate()
+        {
+            gl.active_texture(glow::TEXTURE0 + unit as u32);
+            gl.bind_texture(glow::TEXTURE_2D, name(binding).map(glow::NativeTexture));
+        }
+        gl.active_texture(active_texture as u32);
+        if active_texture as u32 != glow::TEXTURE0 {
+            gl.bind_texture(
+                glow::TEXTURE_2D,
+                name(texture_active_unit).map(glow::NativeTexture),
+            );
+        }
+
+        gl.pixel_store_i32(glow::PACK_ALIGNMENT, pack_alignment);
+        gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, unpack_alignment);
+
+        check_for_gl_error!(gl, "GlState::restore");
+    }
+}
+
+/// The framebuffer that is bound for drawing, to bind again after binding our own.
+///
+/// Bindings can't be read back on the web, so there this is the default framebuffer.
+pub(crate) unsafe fn draw_framebuffer(gl: &glow::Context) -> Option<glow::Framebuffer> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        // Same as `DRAW_FRAMEBUFFER_BINDING`, and also available on OpenGL (ES) 2:
+        name(gl.get_parameter_i32(glow::FRAMEBUFFER_BINDING)).map(glow::NativeFramebuffer)
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = gl;
+        None
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn name(name: i32) -> Option<NonZeroU32> {
+    NonZeroU32::new(name as u32)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn set_enabled(gl: &glow::Context, capability: u32, enabled: bool) {
+    if enabled {
+        gl.enable(capability);
+    } else {
+        gl.disable(capability);
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::headless;
+
+    /// The state [`GlState`] covers, read with `glGet` on its own.
+    unsafe fn snapshot(gl: &glow::Context) -> Vec<(&'static str, Vec<i32>)> {
+        let support = GlStateSupport::new(gl);
+        let i32_n = |parameter, n| {
+            let mut values = vec![0; n];
+            gl.get_parameter_i32_slice(parameter, &mut values);
+            values
+        };
+        let mut clear_color = [0.0; 4];
+        gl.get_parameter_f32_slice(glow::COLOR_CLEAR_VALUE, &mut clear_color);
+
+        let mut state = vec![
+            ("viewport", i32_n(glow::VIEWPORT, 4)),
+            ("scissor box", i32_n(glow::SCISSOR_BOX, 4)),
+            ("color mask", i32_n(glow::COLOR_WRITEMASK, 4)),
+            (
+                "clear color",
+                clear_color
+                    .map(f32::to_bits)
+                    .map(|bits| bits as i32)
+                    .to_vec(),
+            ),
+        ];
+        for (name, capability) in [
+            ("scissor test", glow::SCISSOR_TEST),
+            ("blend", glow::BLEND),
+            ("cull face", glow::CULL_FACE),
+            ("depth test", glow::DEPTH_TEST),
+            ("stencil test", glow::STENCIL_TEST),
+        ] {
+            state.push((name, vec![gl.is_enabled(capability) as i32]));
+        }
+        if support.framebuffer_srgb {
+            state.push(("sRGB", vec![gl.is_enabled(glow::FRAMEBUFFER_SRGB) as i32]));
+        }
+        let mut parameters = vec![
+            ("blend src rgb", glow::BLEND_SRC_RGB),
+            ("blend dst rgb", glow::BLEND_DST_RGB),
+            ("blend src alpha", glow::BLEND_SRC_ALPHA),
+            ("blend dst alpha", glow::BLEND_DST_ALPHA),
+            ("blend equation rgb", glow::BLEND_EQUATION_RGB),
+            ("blend equation alpha", glow::BLEND_EQUATION_ALPHA),
+            ("framebuffer", glow::FRAMEBUFFER_BINDING),
+            ("renderbuffer", glow::RENDERBUFFER_BINDING),
+            ("program", glow::CURRENT_PROGRAM),
+            ("array buffer", glow::ARRAY_BUFFER_BINDING),
+            ("element array buffer", glow::ELEMENT_ARRAY_BUFFER_BINDING),
+            ("active texture", glow::ACTIVE_TEXTURE),
+            ("pack alignment", glow::PACK_ALIGNMENT),
+            ("unpack alignment", glow::UNPACK_ALIGNMENT),
+        ];
+        if support.gl3 {
+            parameters.extend([
+                ("read framebuffer", glow::READ_FRAMEBUFFER_BINDING),
+                ("pixel pack buffer", glow::PIXEL_PACK_BUFFER_BINDING),
+                ("pixel unpack buffer", glow::PIXEL_UNPACK_BUFFER_BINDING),
+            ]);
+        }
+        if support.vertex_arrays {
+            parameters.push(("vertex array", glow::VERTEX_ARRAY_BINDING));
+        }
+        for (name, parameter) in parameters {
+            state.push((name, vec![gl.get_parameter_i32(parameter)]));
+        }
+
+        let active_texture = gl.get_parameter_i32(glow::ACTIVE_TEXTURE);
+        let mut textures = Vec::new();
+        for unit in 0..4 {
+            gl.active_texture(glow::TEXTURE0 + unit);
+            textures.push(gl.get_parameter_i32(glow::TEXTURE_BINDING_2D));
+        }
+        gl.active_texture(active_texture as u32);
+        state.push(("texture bindings", textures));
+        state
+    }
+
+    /// State that is unlike both the defaults and what the painter sets.
+    unsafe fn set_unusual_state(gl: &glow::Context, fbo: glow::Framebuffer) {
+        let support = GlStateSupport::new(gl);
+        gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
+        gl.viewport(1, 2, 3, 4);
+        gl.scissor(2, 3, 4, 5);
+        gl.color_mask(true, false, true, false);
+        gl.clear_color(0.1, 0.2, 0.3, 0.4);
+        for capability in [
+            glow::SCISSOR_TEST,
+            glow::CULL_FACE,
+            glow::DEPTH_TEST,
+            glow::STENCIL_TEST,
+        ] {
+            gl.enable(capability);
+        }
+        gl.disable(glow::BLEND);
+        if support.framebuffer_srgb {
+            gl.enable(glow::FRAMEBUFFER_SRGB);
+        }
+        gl.blend_func_separate(
+            glow::SRC_COLOR,
+            glow::DST_COLOR,
+            glow::ZERO,
+            glow::SRC_ALPHA,
+        );
+        gl.blend_equation_separate(glow::FUNC_SUBTRACT, glow::FUNC_REVERSE_SUBTRACT);
+
+        if support.vertex_arrays {
+            gl.bind_vertex_array(Some(gl.create_vertex_array().unwrap()));
+        }
+        gl.bind_buffer(glow::ARRAY_BUFFER, Some(gl.create_buffer().unwrap()));
+        gl.bind_buffer(
+            glow::ELEMENT_ARRAY_BUFFER,
+            Some(gl.create_buffer().unwrap()),
+        );
+        if support.gl3 {
+            gl.bind_buffer(glow::PIXEL_PACK_BUFFER, Some(gl.create_buffer().unwrap()));
+            gl.bind_buffer(glow::PIXEL_UNPACK_BUFFER, Some(gl.create_buffer().unwrap()));
+        }
+        let renderbuffer = gl.create_renderbuffer().unwrap();
+        gl.bind_renderbuffer(glow::RENDERBUFFER, Some(renderbuffer));
+        for unit in 0..4 {
+            gl.active_texture(glow::TEXTURE0 + unit);
+            gl.bind_texture(glow::TEXTURE_2D, Some(gl.create_texture().unwrap()));
+        }
+        gl.pixel_store_i32(glow::PACK_ALIGNMENT, 1);
+        gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 2);
+    }
+
+    /// Paint a frame with a callback that changes state too.
+    fn paint_frame(painter: &mut crate::Painter) {
+        let rect = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(16.0, 16.0));
+        let mut primitives = headless::colored_rect(rect, egui::Color32::RED);
+        primitives.push(egui::ClippedPrimitive {
+            clip_rect: rect,
+            primitive: egui::epaint::Primitive::Callback(egui::PaintCallback {
+                rect,
+                callback: Arc::new(crate::CallbackFn::new(|_, painter| unsafe {
+                    let gl = painter.gl();
+                    gl.viewport(0, 0, 1, 1);
+                    gl.disable(glow::SCISSOR_TEST);
+                    gl.blend_func(glow::ONE, glow::ONE);
+                    gl.active_texture(glow::TEXTURE2);
+                    gl.bind_texture(glow::TEXTURE_2D, None);
+                    gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 8);
+                })),
+            }),
+        });
+        painter.paint_and_update_textures([16, 16], 1.0, &primitives, &headless::font_texture());
+    }
+
+    #[test]
+    fn painting_preserves_gl_state() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let gl = &headless.gl;
+        let mut painter = crate::Painter::new(gl.clone(), "", None).unwrap();
+        painter.set_preserve_gl_state(true);
+        unsafe {
+            let (fbo, _) = headless::render_target(gl, [16, 16]);
+            set_unusual_state(gl, fbo);
+            let before = snapshot(gl);
+            paint_frame(&mut painter);
+            let after = snapshot(gl);
+            for ((name, before), (_, after)) in before.iter().zip(&after) {
+                assert_eq!(before, after, "{name} changed");
+            }
+        }
+    }
+
+    #[test]
+    fn painting_without_preserving_changes_gl_state() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let gl = &headless.gl;
+        let mut painter = crate::Painter::new(gl.clone(), "", None).unwrap();
+        unsafe {
+            let (fbo, _) = headless::render_target(gl, [16, 16]);
+            set_unusual_state(gl, fbo);
+            let before = snapshot(gl);
+            paint_frame(&mut painter);
+            assert_ne!(before, snapshot(gl));
+        }
+    }
+
+    #[test]
+    fn restore_undoes_changes() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let gl = &headless.gl;
+        unsafe {
+            let (fbo, _) = headless::render_target(gl, [16, 16]);
+            set_unusual_state(gl, fbo);
+            let before = snapshot(gl);
+            let state = GlState::capture(gl, GlStateSupport::new(gl));
+            let (other_fbo, _) = headless::render_target(gl, [16, 16]);
+            set_unusual_state(gl, other_fbo);
+            gl.viewport(5, 6, 7, 8);
+            gl.active_texture(glow::TEXTURE1);
+            state.restore(gl);
+            assert_eq!(before, snapshot(gl));
+        }
+    }
+}
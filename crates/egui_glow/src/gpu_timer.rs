@@ -0,0 +1,265 @@
+#![allow(unsafe_code)]
+
+//! Measuring GPU time with timer queries, for [`crate::Painter::set_gpu_timing`].
+
+use std::collections::VecDeque;
+
+use glow::HasContext as _;
+
+use crate::check_for_gl_error;
+
+/// `GL_GPU_DISJOINT_EXT`, which glow doesn't define.
+const GPU_DISJOINT: u32 = 0x8FBB;
+
+/// Drop the oldest frame if the GPU is this many frames behind.
+const MAX_FRAMES_IN_FLIGHT: usize = 8;
+
+/// How long the GPU spent on the parts of a frame, in seconds.
+///
+/// Each section is read back as a 32-bit count of nanoseconds,
+/// so sections that take longer than about 4.3 seconds wrap around.
+///
+/// See [`crate::Painter::gpu_timings`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GpuTimings {
+    /// Uploading egui textures, including the part of budgeted uploads done this frame.
+    pub texture_uploads: f32,
+
+    /// Drawing the egui meshes.
+    pub meshes: f32,
+
+    /// Each paint callback, in the order they were painted.
+    pub callbacks: Vec<f32>,
+}
+
+impl GpuTimings {
+    /// The time of all measured parts together.
+    pub fn total(&self) -> f32 {
+        self.texture_uploads + self.meshes + self.callbacks.iter().sum::<f32>()
+    }
+}
+
+/// What a timer query measures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Section {
+    TextureUploads,
+    Meshes,
+    Callback,
+}
+
+/// Timer queries around the parts of each frame, read back a few frames later.
+pub(crate) struct GpuTimer {
+    /// `TIME_ELAPSED` queries: OpenGL 3.3, `ARB_timer_query`, or `EXT_disjoint_timer_query` on OpenGL ES and WebGL.
+    supported: bool,
+
+    /// OpenGL ES and WebGL may report that the timer was disturbed, e.g. by a frequency change.
+    check_disjoint: bool,
+
+    enabled: bool,
+
+    /// Is a query between `begin_query` and `end_query`?
+    active: bool,
+
+    /// The queries of the frame being recorded.
+    recording: Vec<(Section, glow::Query)>,
+
+    /// Finished frames, oldest first.
+    in_flight: VecDeque<Vec<(Section, glow::Query)>>,
+
+    /// Queries whose results we have read, to use again.
+    free: Vec<glow::Query>,
+
+    latest: Option<GpuTimings>,
+}
+
+impl GpuTimer {
+    pub(crate) fn new(gl: &glow::Context, is_webgl_1: bool) -> Self {
+        let version = gl.version();
+        let extensions = gl.supported_extensions();
+        let has_disjoint_timer_query = extensions
+            .iter()
+            .any(|extension| extension.contains("disjoint_timer_query"));
+        let supported = !is_webgl_1
+            && if version.is_embedded || cfg!(target_arch = "wasm32") {
+                version.major >= 3 && has_disjoint_timer_query
+            } else {
+                (version.major, version.minor) >= (3, 3)
+                    || extensions.contains("GL_ARB_timer_query")
+            };
+        Self {
+            supported,
+            check_disjoint: version.is_embedded || cfg!(target_arch = "wasm32"),
+            enabled: false,
+            active: false,
+            recording: Vec::new(),
+            in_flight: VecDeque::new(),
+            free: Vec::new(),
+            latest: None,
+        }
+    }
+
+    pub(crate) fn is_supported(&self) -> bool {
+        self.supported
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns `false` if timer queries are not supported.
+    pub(crate) fn set_enabled(&mut self, enabled: bool) -> bool {
+        self.enabled = enabled && self.supported;
+        if !self.enabled {
+            self.latest = None;
+        }
+        self.enabled || !enabled
+    }
+
+    pub(crate) fn latest(&self) -> Option<&GpuTimings> {
+        self.latest.as_ref()
+    }
+
+    /// Start timing `section`, ending the one before.
+    pub(crate) unsafe fn begin(&mut self, gl: &glow::Context, section: Section) {
+        if !self.enabled {
+            return;
+        }
+        self.end(gl);
+        let query = match self.free.pop() {
+            Some(query) => query,
+            None => match gl.create_query() {
+                Ok(query) => query,
+                Err(err) => {
+                    log::warn!("Failed to create timer query: {err}");
+                    return;
+                }
+            },
+        };
+        gl.begin_query(glow::TIME_ELAPSED, query);
+        self.recording.push((section, query));
+        self.active = true;
+    }
+
+    /// Stop timing the current section, if any.
+    pub(crate) unsafe fn end(&mut self, gl: &glow::Context) {
+        if self.active {
+            gl.end_query(glow::TIME_ELAPSED);
+            self.active = false;
+        }
+    }
+
+    /// Close the frame being recorded, and read the results of earlier frames the GPU is done with.
+    pub(crate) unsafe fn end_frame(&mut self, gl: &glow::Context) {
+        self.end(gl);
+        if !self.recording.is_empty() {
+            self.in_flight
+                .push_back(std::mem::take(&mut self.recording));
+        }
+        if self.in_flight.is_empty() {
+            return;
+        }
+        crate::profile_function!();
+
+        while self.in_flight.len() > MAX_FRAMES_IN_FLIGHT {
+            let frame = self.in_flight.pop_front().unwrap_or_default();
+            self.free.extend(frame.into_iter().map(|(_, query)| query));
+        }
+
+        let mut finished = Vec::new();
+        while let Some(frame) = self.in_flight.front() {
+            let available = frame.iter().all(|&(_, query)| {
+                gl.get_query_parameter_u32(query, glow::QUERY_RESULT_AVAILABLE) != 0
+            });
+            if !available {
+                break;
+            }
+            finished.extend(self.in_flight.pop_front());
+        }
+        if finished.is_empty() {
+            return;
+        }
+
+        // The results are meaningless if the GPU was disturbed while they were measured:
+        let disjoint = self.check_disjoint && gl.get_parameter_i32(GPU_DISJOINT) != 0;
+        for frame in finished {
+            let mut timings = GpuTimings::default();
+            for &(section, query) in &frame {
+                let seconds = gl.get_query_parameter_u32(query, glow::QUERY_RESULT) as f32 * 1e-9;
+                match section {
+                    Section::TextureUploads => timings.texture_uploads += seconds,
+                    Section::Meshes => timings.meshes += seconds,
+                    Section::Callback => timings.callbacks.push(seconds),
+                }
+            }
+            self.finish_frame(timings, disjoint);
+            self.free.extend(frame.into_iter().map(|(_, query)| query));
+        }
+        check_for_gl_error!(gl, "timer queries");
+    }
+
+    /// Make `timings` the latest, or forget the latest if the GPU was disturbed while measuring them.
+    fn finish_frame(&mut self, timings: GpuTimings, disjoint: bool) {
+        if self.enabled {
+            self.latest = (!disjoint).then_some(timings);
+        }
+    }
+
+    pub(crate) unsafe fn destroy(&self, gl: &glow::Context) {
+        let in_flight = self.in_flight.iter().flatten().chain(&self.recording);
+        for &(_, query) in in_flight {
+            gl.delete_query(query);
+        }
+        for &query in &self.free {
+            gl.delete_query(query);
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::headless;
+
+    #[test]
+    fn unsupported_contexts_have_no_timings() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let gl = &headless.gl;
+        // WebGL1 has no timer queries:
+        let mut timer = GpuTimer::new(gl, true);
+        assert!(!timer.is_supported());
+        assert!(!timer.set_enabled(true));
+        assert!(!timer.is_enabled());
+        unsafe {
+            timer.begin(gl, Section::Meshes);
+            timer.end_frame(gl);
+        }
+        assert!(timer.recording.is_empty() && timer.in_flight.is_empty());
+        assert_eq!(timer.latest(), None);
+    }
+
+    #[test]
+    fn disjoint_frames_clear_the_timings() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let mut timer = GpuTimer::new(&headless.gl, false);
+        if !timer.set_enabled(true) {
+            eprintln!("Skipping: the context doesn't support timer queries");
+            return;
+        }
+        let timings = GpuTimings {
+            meshes: 0.001,
+            ..Default::default()
+        };
+        timer.finish_frame(timings.clone(), false);
+        assert_eq!(timer.latest(), Some(&timings));
+        timer.finish_frame(timings.clone(), true);
+        assert_eq!(timer.latest(), None);
+
+        timer.set_enabled(false);
+        timer.finish_frame(timings, false);
+        assert_eq!(timer.latest(), None);
+    }
+}
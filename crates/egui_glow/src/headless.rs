@@ -0,0 +1,149 @@
+#![allow(unsafe_code)]
+
+//! A surfaceless OpenGL context for tests, e.g. on llvmpipe in CI.
+
+use std::ffi::CString;
+use std::sync::Arc;
+
+use glutin::api::egl;
+use glutin::config::{ConfigSurfaceTypes, ConfigTemplateBuilder};
+use glutin::context::ContextAttributesBuilder;
+use glutin::display::GlDisplay as _;
+
+/// An OpenGL context that is current on this thread, without a window or surface.
+pub(crate) struct HeadlessGl {
+    pub gl: Arc<glow::Context>,
+    display: egl::display::Display,
+    _context: egl::context::PossiblyCurrentContext,
+}
+
+impl HeadlessGl {
+    pub fn get_proc_address(&self, symbol: &std::ffi::CStr) -> *const std::ffi::c_void {
+        self.display.get_proc_address(symbol)
+    }
+}
+
+/// A context on the first EGL device, or `None` (with a note on stderr) if there is none,
+/// so tests can skip themselves on machines without OpenGL.
+pub(crate) fn gl() -> Option<HeadlessGl> {
+    match create() {
+        Ok(headless) => Some(headless),
+        Err(err) => {
+            eprintln!("Skipping OpenGL test: no headless OpenGL context: {err}");
+            None
+        }
+    }
+}
+
+fn create() -> Result<HeadlessGl, String> {
+    let device = egl::device::Device::query_devices()
+        .map_err(|err| err.to_string())?
+        .next()
+        .ok_or("no EGL device")?;
+    let display =
+        unsafe { egl::display::Display::with_device(&device, None) }.map_err(|e| e.to_string())?;
+    let template = ConfigTemplateBuilder::new()
+        .with_surface_type(ConfigSurfaceTypes::empty())
+        .build();
+    let config = unsafe { display.find_configs(template) }
+        .map_err(|err| err.to_string())?
+        .next()
+        .ok_or("no EGL config")?;
+    let context =
+        unsafe { display.create_context(&config, &ContextAttributesBuilder::new().build(None)) }
+            .map_err(|err| err.to_string())?
+            .make_current_surfaceless()
+            .map_err(|err| err.to_string())?;
+    let gl = unsafe {
+        glow::Context::from_loader_function(|symbol| {
+            let symbol = CString::new(symbol).unwrap();
+            display.get_proc_address(&symbol).cast()
+        })
+    };
+    Ok(HeadlessGl {
+        gl: Arc::new(gl),
+        display,
+        _context: context,
+    })
+}
+
+/// A frame with a solid `color` rectangle at `rect` (in points).
+pub(crate) fn colored_rect(rect: egui::Rect, color: egui::Color32) -> Vec<egui::ClippedPrimitive> {
+    let mut mesh = egui::Mesh::default();
+    mesh.add_colored_rect(rect, color);
+    vec![egui::ClippedPrimitive {
+        clip_rect: egui::Rect::EVERYTHING,
+        primitive: egui::epaint::Primitive::Mesh(mesh),
+    }]
+}
+
+/// A white font texture, which is what [`colored_rect`] samples.
+pub(crate) fn font_texture() -> egui::TexturesDelta {
+    let image = egui::ColorImage::new([1, 1], egui::Color32::WHITE);
+    egui::TexturesDelta {
+        set: vec![(
+            egui::TextureId::default(),
+            egui::epaint::ImageDelta::full(image, egui::TextureOptions::NEAREST),
+        )],
+        free: vec![],
+    }
+}
+
+/// A framebuffer with an RGBA8 texture of `[width, height]` pixels, to paint into
+/// instead of the default framebuffer, which a surfaceless context doesn't have.
+pub(crate) unsafe fn render_target(
+    gl: &glow::Context,
+    [width, height]: [u32; 2],
+) -> (glow::Framebuffer, glow::Texture) {
+    use glow::HasContext as _;
+    let texture = gl.create_texture().unwrap();
+    gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+    gl.tex_image_2d(
+        glow::TEXTURE_2D,
+        0,
+        glow::RGBA8 as i32,
+        width as i32,
+        height as i32,
+        0,
+        glow::RGBA,
+        glow::UNSIGNED_BYTE,
+        None,
+    );
+    gl.bind_texture(glow::TEXTURE_2D, None);
+    let fbo = gl.create_framebuffer().unwrap();
+    gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
+    gl.framebuffer_texture_2d(
+        glow::FRAMEBUFFER,
+        glow::COLOR_ATTACHMENT0,
+        glow::TEXTURE_2D,
+        Some(texture),
+        0,
+    );
+    gl.clear_color(0.0, 0.0, 0.0, 0.0);
+    gl.clear(glow::COLOR_BUFFER_BIT);
+    (fbo, texture)
+}
+
+/// The color of a pixel of `fbo`, counting rows from the bottom like OpenGL does.
+pub(crate) unsafe fn read_pixel(
+    gl: &glow::Context,
+    fbo: glow::Framebuffer,
+    [x, y]: [i32; 2],
+) -> egui::Color32 {
+    use glow::HasContext as _;
+    let previous = crate::gl_state::draw_framebuffer(gl);
+    gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
+    let mut rgba = [0_u8; 4];
+    gl.read_pixels(
+        x,
+        y,
+        1,
+        1,
+        glow::RGBA,
+        glow::UNSIGNED_BYTE,
+        glow::PixelPackData::Slice(&mut rgba),
+    );
+    gl.bind_framebuffer(glow::FRAMEBUFFER, previous);
+    let [r, g, b, a] = rgba;
+    egui::Color32::from_rgba_premultiplied(r, g, b, a)
+}
@@ -11,14 +11,73 @@
 #![allow(clippy::float_cmp)]
 #![allow(clippy::manual_range_contains)]
 
+mod callback;
+mod damage;
+mod debug;
+mod gl_state;
+mod gpu_timer;
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod headless;
+pub use callback::{Callback, CallbackResource, CallbackResources, CallbackTrait};
 pub mod painter;
 pub use glow;
-pub use painter::{CallbackFn, Painter};
+pub use painter::{
+    CallbackFn, OutputMode, Painter, PainterOptions, PainterStats, SamplerOptions, TextureWrapMode,
+};
 mod misc_util;
+mod post_process;
+mod readback;
+pub use post_process::{ColorblindSimulation, PostProcess};
+#[cfg(not(target_arch = "wasm32"))]
+mod program_cache;
+#[cfg(not(target_arch = "wasm32"))]
+pub use program_cache::{DirectoryStorage, ProgramCache, ProgramCacheStorage};
 mod shader_version;
+mod shared_textures;
+mod texture_upload;
 mod vao;
+mod yuv;
 
+pub use gpu_timer::GpuTimings;
 pub use shader_version::ShaderVersion;
+pub use shared_textures::SharedTextures;
+pub use yuv::{YuvColorSpace, YuvPlanes, YuvRange};
+
+/// `Send + Sync` on native.
+///
+/// On the web, OpenGL objects are neither, so there this is implemented for all types.
+#[cfg(not(target_arch = "wasm32"))]
+pub trait SendSyncIfNative: Send + Sync {}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send + Sync> SendSyncIfNative for T {}
+
+/// `Send + Sync` on native.
+///
+/// On the web, OpenGL objects are neither, so there this is implemented for all types.
+#[cfg(target_arch = "wasm32")]
+pub trait SendSyncIfNative {}
+
+#[cfg(target_arch = "wasm32")]
+impl<T> SendSyncIfNative for T {}
+
+/// `Send` on native.
+///
+/// On the web, OpenGL objects aren't, so there this is implemented for all types.
+#[cfg(not(target_arch = "wasm32"))]
+pub trait SendIfNative: Send {}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send> SendIfNative for T {}
+
+/// `Send` on native.
+///
+/// On the web, OpenGL objects aren't, so there this is implemented for all types.
+#[cfg(target_arch = "wasm32")]
+pub trait SendIfNative {}
+
+#[cfg(target_arch = "wasm32")]
+impl<T> SendIfNative for T {}
 
 #[cfg(all(not(target_arch = "wasm32"), feature = "winit"))]
 pub mod winit;
@@ -69,12 +128,27 @@ macro_rules! check_for_gl_error_even_in_release {
     }};
 }
 
+thread_local! {
+    /// The number of errors [`check_for_gl_error_impl`] has seen on this thread.
+    static GL_ERROR_COUNT: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// The number of errors [`check_for_gl_error_impl`] has seen on this thread,
+/// which is where the OpenGL context is current.
+///
+/// A [`Painter`] only counts the errors during its own calls, so painters sharing a thread
+/// don't see each other's errors.
+pub(crate) fn gl_error_count() -> usize {
+    GL_ERROR_COUNT.with(|count| count.get())
+}
+
 #[doc(hidden)]
 pub fn check_for_gl_error_impl(gl: &glow::Context, file: &str, line: u32, context: &str) {
     use glow::HasContext as _;
     #[allow(unsafe_code)]
     let error_code = unsafe { gl.get_error() };
     if error_code != glow::NO_ERROR {
+        GL_ERROR_COUNT.with(|count| count.set(count.get() + 1));
         let error_str = match error_code {
             glow::INVALID_ENUM => "GL_INVALID_ENUM",
             glow::INVALID_VALUE => "GL_INVALID_VALUE",
@@ -16,25 +16,53 @@ pub(crate) unsafe fn compile_shader(
     if gl.get_shader_compile_status(shader) {
         Ok(shader)
     } else {
-        Err(gl.get_shader_info_log(shader))
+        let log = gl.get_shader_info_log(shader);
+        gl.delete_shader(shader);
+        Err(log)
     }
 }
 
-pub(crate) unsafe fn link_program<'a, T: IntoIterator<Item = &'a glow::Shader>>(
+/// Compile the shaders and link them into a program, calling `before_link` just before linking.
+pub(crate) unsafe fn compile_and_link(
     gl: &glow::Context,
-    shaders: T,
+    sources: &[(u32, &str)],
+    before_link: impl FnOnce(glow::Program),
 ) -> Result<glow::Program, String> {
-    let program = gl.create_program()?;
-
-    for shader in shaders {
-        gl.attach_shader(program, *shader);
+    let mut shaders = Vec::with_capacity(sources.len());
+    for &(shader_type, source) in sources {
+        match compile_shader(gl, shader_type, source) {
+            Ok(shader) => shaders.push(shader),
+            Err(err) => {
+                for shader in shaders {
+                    gl.delete_shader(shader);
+                }
+                return Err(err);
+            }
+        }
     }
-
+    let program = match gl.create_program() {
+        Ok(program) => program,
+        Err(err) => {
+            for shader in shaders {
+                gl.delete_shader(shader);
+            }
+            return Err(err);
+        }
+    };
+    for &shader in &shaders {
+        gl.attach_shader(program, shader);
+    }
+    before_link(program);
     gl.link_program(program);
-
+    for shader in shaders {
+        gl.detach_shader(program, shader);
+        gl.delete_shader(shader);
+    }
     if gl.get_program_link_status(program) {
         Ok(program)
     } else {
-        Err(gl.get_program_info_log(program))
+        let log = gl.get_program_info_log(program);
+        gl.delete_program(program);
+        Err(log)
     }
 }
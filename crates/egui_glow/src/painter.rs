@@ -1,19 +1,33 @@
 #![allow(clippy::collapsible_else_if)]
 #![allow(unsafe_code)]
 
-use std::{collections::HashMap, sync::Arc};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::sync::Arc;
 
 use egui::{
-    emath::Rect,
+    emath::{Pos2, Rect},
     epaint::{Mesh, PaintCallbackInfo, Primitive, Vertex},
 };
 use glow::HasContext as _;
 use memoffset::offset_of;
 
+use crate::callback::{callback_trait, CallbackResources, CallbackTrait};
 use crate::check_for_gl_error;
-use crate::misc_util::{compile_shader, link_program};
+use crate::damage::{DamageTracker, FrameSetup};
+use crate::debug::{enable_debug_output, object_label};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::gl_state::GlStateSupport;
+use crate::gl_state::{draw_framebuffer, GlState};
+use crate::gpu_timer::{GpuTimer, GpuTimings, Section};
+use crate::misc_util::compile_and_link;
+use crate::post_process::{ColorblindSimulation, IntermediateTarget, PostProcess, Quad};
+use crate::readback::{read_pixels_rgba, region_in_pixels, Readbacks};
 use crate::shader_version::ShaderVersion;
+use crate::shared_textures::SharedTextures;
+use crate::texture_upload::{PendingUpload, TextureUploads};
 use crate::vao;
+use crate::yuv::{YuvColorSpace, YuvPlanes, YuvRange, YuvTexture};
 
 /// Re-exported [`glow::Context`].
 pub use glow::Context;
@@ -21,6 +35,23 @@ pub use glow::Context;
 const VERT_SRC: &str = include_str!("shader/vertex.glsl");
 const FRAG_SRC: &str = include_str!("shader/fragment.glsl");
 
+/// Post-process pass that encodes linear colors to sRGB gamma.
+const ENCODE_SRC: &str = r#"
+vec4 post_process(vec2 uv) {
+    vec4 color = texture2D(u_sampler, uv);
+    return vec4(srgb_gamma_from_linear(clamp(color.rgb, 0.0, 1.0)), color.a);
+}
+"#;
+
+// Values of `u_texture_format` in the fragment shader:
+const TEXTURE_FORMAT_RGBA: i32 = 0;
+const TEXTURE_FORMAT_COVERAGE: i32 = 1;
+const TEXTURE_FORMAT_NV12: i32 = 2;
+const TEXTURE_FORMAT_I420: i32 = 3;
+
+/// Older targets are copied in full, see [`Painter::set_target_buffer_age`].
+const MAX_TARGET_BUFFER_AGE: u32 = 4;
+
 trait TextureFilterExt {
     fn glow_code(&self) -> u32;
 }
@@ -34,6 +65,138 @@ impl TextureFilterExt for egui::TextureFilter {
     }
 }
 
+/// The minification filter to use when sampling between mip levels.
+fn mipmap_min_filter(minification: egui::TextureFilter, mipmap: egui::TextureFilter) -> u32 {
+    use egui::TextureFilter::{Linear, Nearest};
+    match (minification, mipmap) {
+        (Nearest, Nearest) => glow::NEAREST_MIPMAP_NEAREST,
+        (Nearest, Linear) => glow::NEAREST_MIPMAP_LINEAR,
+        (Linear, Nearest) => glow::LINEAR_MIPMAP_NEAREST,
+        (Linear, Linear) => glow::LINEAR_MIPMAP_LINEAR,
+    }
+}
+
+/// How to sample a texture outside of the `[0, 1]` uv range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TextureWrapMode {
+    /// Stretch the edge pixels (default).
+    #[default]
+    ClampToEdge,
+
+    /// Tile the texture.
+    Repeat,
+
+    /// Tile the texture, mirroring every other repetition.
+    MirroredRepeat,
+}
+
+impl TextureWrapMode {
+    fn glow_code(&self) -> u32 {
+        match self {
+            Self::ClampToEdge => glow::CLAMP_TO_EDGE,
+            Self::Repeat => glow::REPEAT,
+            Self::MirroredRepeat => glow::MIRRORED_REPEAT,
+        }
+    }
+}
+
+/// How to sample a texture, on top of what [`egui::TextureOptions`] describes.
+///
+/// Set with [`Painter::set_sampler_options`] or [`Painter::register_native_texture_with_options`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SamplerOptions {
+    /// How to sample outside of the `[0, 1]` uv range.
+    pub wrap_mode: TextureWrapMode,
+
+    /// If set, mipmaps are generated each time the texture is uploaded,
+    /// and this filter is used between mip levels when minifying.
+    ///
+    /// Use this for large images that are shown small, to avoid shimmering.
+    pub mipmap_mode: Option<egui::TextureFilter>,
+
+    /// Maximum level of anisotropic filtering, e.g. `16`.
+    ///
+    /// Clamped to what the driver supports, and ignored without `EXT_texture_filter_anisotropic`.
+    /// 0 or 1 means off (default).
+    pub anisotropy: u8,
+}
+
+/// What [`Painter`] did in a frame, see [`Painter::stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PainterStats {
+    /// Draw calls for egui meshes. Callbacks and post-processing passes are not counted.
+    pub draw_calls: usize,
+
+    /// Vertices submitted for egui meshes.
+    pub vertices: usize,
+
+    /// Indices submitted for egui meshes, three per triangle.
+    pub indices: usize,
+
+    /// Bytes of texture data uploaded, including what [`Painter::set_upload_budget`] spread over this frame.
+    pub texture_upload_bytes: usize,
+
+    /// Textures alive at the end of the frame, including native and YUV textures.
+    pub textures: usize,
+
+    /// Estimated GPU memory of the textures egui uploaded, including mipmaps.
+    ///
+    /// Native and YUV textures are not included, as their size is not known.
+    pub texture_bytes: usize,
+
+    /// Paint callbacks that were called.
+    pub callbacks: usize,
+
+    /// OpenGL errors during [`Painter::paint_primitives`].
+    ///
+    /// `glGetError` is checked at the end of every frame, and in debug builds also after each step,
+    /// see [`crate::check_for_gl_error`].
+    pub gl_errors: usize,
+}
+
+/// How [`Painter`] blends colors and writes them to the framebuffer.
+///
+/// In all modes the output has premultiplied alpha, so transparent windows composite correctly.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum OutputMode {
+    /// Blend in gamma space and write sRGB gamma-encoded colors (default).
+    ///
+    /// This is what egui is designed for, and what makes text look the way it should.
+    #[default]
+    Gamma,
+
+    /// Blend in linear space, and let the GPU encode to sRGB on write (`GL_FRAMEBUFFER_SRGB`).
+    ///
+    /// This matches what egui-wgpu does with an sRGB surface.
+    /// The framebuffer must be sRGB-capable; eframe asks for one when this mode is selected.
+    /// Not supported on WebGL.
+    Linear,
+
+    /// Blend in linear space into an RGBA16F offscreen target, which is encoded to sRGB for the screen.
+    ///
+    /// [`Painter::paint_to_texture`] returns the linear RGBA16F texture as-is.
+    /// Requires OpenGL 3.0, or OpenGL ES 3.0 or WebGL2 with `EXT_color_buffer_float`.
+    Hdr,
+}
+
+/// Options for [`Painter::new_with_options`] that can't be changed after the painter is created.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PainterOptions {
+    /// Gamma or linear blending, see [`OutputMode`].
+    ///
+    /// If the mode is not supported by the context, the painter falls back to [`OutputMode::Gamma`] with a warning.
+    pub output_mode: OutputMode,
+
+    /// Turn on `KHR_debug` output (or `GL_ARB_debug_output` via OpenGL 4.3), even in release builds.
+    ///
+    /// Driver messages are then sent to [`log`] with a matching level,
+    /// egui's OpenGL objects are labeled, and each phase of painting is put in a debug group,
+    /// so you can tell egui apart from the rest in tools like `RenderDoc` and `apitrace`.
+    /// This replaces any debug message callback already set on the context.
+    /// Some drivers only report messages for contexts created with the debug flag.
+    pub debug: bool,
+}
+
 /// An OpenGL painter using [`glow`].
 ///
 /// This is responsible for painting egui and managing egui textures.
@@ -49,19 +212,114 @@ pub struct Painter {
     program: glow::Program,
     u_screen_size: glow::UniformLocation,
     u_sampler: glow::UniformLocation,
+    u_sampler_u: glow::UniformLocation,
+    u_sampler_v: glow::UniformLocation,
+    u_texture_format: glow::UniformLocation,
+    u_yuv_matrix: glow::UniformLocation,
+    u_yuv_offset: glow::UniformLocation,
+    shader_version: ShaderVersion,
     is_webgl_1: bool,
     vao: crate::vao::VertexArrayObject,
     srgb_textures: bool,
+
+    /// Internal and source format of font textures: `R8` where supported, else `LUMINANCE`.
+    coverage_format: (u32, u32),
+    output_mode: OutputMode,
     vbo: glow::Buffer,
     element_array_buffer: glow::Buffer,
 
-    textures: HashMap<egui::TextureId, glow::Texture>,
+    /// The textures, possibly shared with other painters, see [`Self::set_shared_textures`].
+    store: SharedTextures,
 
-    next_native_tex_id: u64,
+    /// Our id in [`Self::store`], for the textures we keep alive.
+    store_painter_id: usize,
+
+    /// Reused for the coverage of partial font texture updates.
+    coverage_buffer: Vec<u8>,
+
+    /// Bytes of texture data to upload per frame, see [`Self::set_upload_budget`].
+    upload_budget: Option<usize>,
+    texture_uploads: TextureUploads,
+
+    /// Screen readbacks waiting for the GPU, see [`Self::read_screen_rgba_async`].
+    readbacks: Readbacks,
+
+    /// The last finished frame, see [`Self::stats`].
+    stats: PainterStats,
+
+    /// The frame in progress, moved to `stats` at the end of [`Self::paint_primitives`].
+    frame_stats: PainterStats,
+
+    /// The value of [`crate::gl_error_count`] at the start of [`Self::paint_primitives`].
+    gl_errors_before_frame: usize,
+
+    /// See [`Self::set_gpu_timing`].
+    gpu_timer: GpuTimer,
+
+    /// 1.0 if `EXT_texture_filter_anisotropic` is not supported.
+    max_anisotropy: f32,
 
     /// Stores outdated OpenGL textures that are yet to be deleted
     textures_to_destroy: Vec<glow::Texture>,
 
+    /// The offscreen framebuffer we are currently painting into, if any.
+    intermediate_fbo: Option<glow::Framebuffer>,
+
+    /// Where the final image ends up. `None` means the default framebuffer.
+    target_fbo: Option<glow::Framebuffer>,
+
+    /// Is [`Self::target_fbo`] an RGBA16F texture from [`Self::paint_to_texture`]?
+    target_is_hdr: bool,
+
+    /// Where egui goes in [`Self::target_fbo`], see [`Self::set_target_offset`].
+    target_offset: [i32; 2],
+
+    /// MSAA level of the intermediate target. 0 or 1 means off.
+    multisampling: u8,
+    post_processes: Vec<PostProcess>,
+    colorblind_simulation: Option<(ColorblindSimulation, PostProcess)>,
+
+    /// Allocated when we need to paint via an offscreen target (MSAA or post-processing).
+    intermediate: Option<IntermediateTarget>,
+
+    /// Copies the intermediate target to the screen when there are no post-processes.
+    copy_pass: Option<PostProcess>,
+
+    /// Encodes linear colors to sRGB gamma for [`OutputMode::Hdr`].
+    encode_pass: Option<PostProcess>,
+    quad: Option<Quad>,
+
+    /// Has [`Painter::clear`] cleared the intermediate target since it was last shown?
+    intermediate_cleared: bool,
+
+    /// See [`Self::set_damage_tracking`].
+    damage_tracker: Option<DamageTracker>,
+
+    /// The color [`Painter::clear`] should have cleared to, when tracking damage.
+    damage_clear_color: [f32; 4],
+
+    /// See [`Self::damage`].
+    damage: Vec<Rect>,
+
+    /// See [`Self::set_target_buffer_age`].
+    target_buffer_age: u32,
+
+    /// The bounding box of the damage of the last few frames that changed the target, newest first.
+    recent_damage: VecDeque<Rect>,
+
+    callback_resources: CallbackResources,
+
+    /// Save and restore the OpenGL state around everything we do, see [`Self::set_preserve_gl_state`].
+    preserve_gl_state: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    gl_state_support: GlStateSupport,
+
+    /// Has an outer call already saved the OpenGL state, so that nested calls don't need to?
+    gl_state_saved: bool,
+
+    /// Is `KHR_debug` output on, so we should label objects and push debug groups?
+    debug: bool,
+
     /// Used to make sure we are destroyed correctly.
     destroyed: bool,
 }
@@ -72,6 +330,8 @@ pub struct Painter {
 /// The callback is passed, the [`egui::PaintCallbackInfo`] and the [`Painter`] which can be used to
 /// access the OpenGL context.
 ///
+/// For a prepare phase and long-lived resources, implement [`CallbackTrait`] instead.
+///
 /// # Example
 ///
 /// See the [`custom3d_glow`](https://github.com/emilk/egui/blob/master/crates/egui_demo_app/src/apps/custom3d_wgpu.rs) demo source for a detailed usage example.
@@ -86,6 +346,12 @@ impl CallbackFn {
     }
 }
 
+impl CallbackTrait for CallbackFn {
+    fn paint(&self, info: PaintCallbackInfo, painter: &Painter, _resources: &CallbackResources) {
+        (self.f)(info, painter);
+    }
+}
+
 impl Painter {
     /// Create painter.
     ///
@@ -94,6 +360,9 @@ impl Painter {
     /// Set `shader_prefix` if you want to turn on shader workaround e.g. `"#define APPLY_BRIGHTENING_GAMMA\n"`
     /// (see <https://github.com/emilk/egui/issues/794>).
     ///
+    /// `shader_version` overrides the detected version, see [`ShaderVersion::get`].
+    /// The [`ShaderVersion::ENV_VAR`] environment variable overrides both.
+    ///
     /// # Errors
     /// will return `Err` below cases
     /// * failed to compile shader
@@ -103,9 +372,58 @@ impl Painter {
         gl: Arc<glow::Context>,
         shader_prefix: &str,
         shader_version: Option<ShaderVersion>,
+    ) -> Result<Painter, String> {
+        Self::new_with_options(gl, shader_prefix, shader_version, PainterOptions::default())
+    }
+
+    /// Like [`Self::new`], with [`PainterOptions`].
+    ///
+    /// # Errors
+    /// See [`Self::new`].
+    pub fn new_with_options(
+        gl: Arc<glow::Context>,
+        shader_prefix: &str,
+        shader_version: Option<ShaderVersion>,
+        options: PainterOptions,
+    ) -> Result<Painter, String> {
+        Self::new_impl(
+            gl,
+            shader_prefix,
+            shader_version,
+            options,
+            |gl, sources| unsafe { compile_and_link(gl, sources, |_| {}) },
+        )
+    }
+
+    /// Like [`Self::new_with_options`], but loads egui's shader program from `program_cache` if it is there,
+    /// and otherwise adds it to the cache.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new_with_program_cache(
+        gl: Arc<glow::Context>,
+        shader_prefix: &str,
+        shader_version: Option<ShaderVersion>,
+        options: PainterOptions,
+        program_cache: &mut crate::ProgramCache<'_>,
+    ) -> Result<Painter, String> {
+        Self::new_impl(
+            gl,
+            shader_prefix,
+            shader_version,
+            options,
+            |gl, sources| unsafe { program_cache.link_program(gl, sources) },
+        )
+    }
+
+    fn new_impl(
+        gl: Arc<glow::Context>,
+        shader_prefix: &str,
+        shader_version: Option<ShaderVersion>,
+        options: PainterOptions,
+        link_program: impl FnOnce(&glow::Context, &[(u32, &str)]) -> Result<glow::Program, String>,
     ) -> Result<Painter, String> {
         crate::profile_function!();
         crate::check_for_gl_error_even_in_release!(&gl, "before Painter::new");
+        let PainterOptions { output_mode, debug } = options;
 
         // some useful debug info. all three of them are present in gl 1.1.
         unsafe {
@@ -125,51 +443,114 @@ impl Painter {
         }
 
         let max_texture_side = unsafe { gl.get_parameter_i32(glow::MAX_TEXTURE_SIZE) } as usize;
-        let shader_version = shader_version.unwrap_or_else(|| ShaderVersion::get(&gl));
+        let shader_version = ShaderVersion::from_env()
+            .or(shader_version)
+            .unwrap_or_else(|| ShaderVersion::detect(&gl));
         let is_webgl_1 = shader_version == ShaderVersion::Es100;
         let shader_version_declaration = shader_version.version_declaration();
         log::debug!("Shader header: {:?}.", shader_version_declaration);
 
         let supported_extensions = gl.supported_extensions();
         log::trace!("OpenGL extensions: {supported_extensions:?}");
-        let srgb_textures = shader_version == ShaderVersion::Es300 // WebGL2 always support sRGB
+        // WebGL2 and OpenGL ES 3 always support sRGB:
+        let srgb_textures = matches!(shader_version, ShaderVersion::Es300 | ShaderVersion::Es310)
             || supported_extensions.iter().any(|extension| {
                 // EXT_sRGB, GL_ARB_framebuffer_sRGB, GL_EXT_sRGB, GL_EXT_texture_sRGB_decode, …
                 extension.contains("sRGB")
             });
         log::debug!("SRGB texture Support: {:?}", srgb_textures);
 
+        // GL_ARB_texture_rg, GL_EXT_texture_rg, …
+        let coverage_format = if !is_webgl_1
+            && (gl.version().major >= 3
+                || supported_extensions
+                    .iter()
+                    .any(|extension| extension.ends_with("texture_rg")))
+        {
+            (glow::R8, glow::RED)
+        } else {
+            // WebGL1 and OpenGL 2.1 (removed from core profiles, which all have `R8`):
+            (glow::LUMINANCE, glow::LUMINANCE)
+        };
+
+        // EXT_texture_filter_anisotropic, GL_EXT_texture_filter_anisotropic, GL_ARB_texture_filter_anisotropic, …
+        let max_anisotropy = if supported_extensions
+            .iter()
+            .any(|extension| extension.contains("texture_filter_anisotropic"))
+        {
+            unsafe { gl.get_parameter_f32(glow::MAX_TEXTURE_MAX_ANISOTROPY_EXT) }.max(1.0)
+        } else {
+            1.0
+        };
+        log::debug!("Max anisotropy: {max_anisotropy}");
+
+        let output_mode_supported = match output_mode {
+            OutputMode::Gamma => true,
+            OutputMode::Linear => !cfg!(target_arch = "wasm32") && srgb_textures,
+            OutputMode::Hdr => {
+                shader_version.is_new_shader_interface()
+                    && (!shader_version.is_embedded()
+                        || supported_extensions
+                            .iter()
+                            .any(|extension| extension.contains("color_buffer_float")))
+            }
+        };
+        let output_mode = if output_mode_supported {
+            output_mode
+        } else {
+            log::warn!("{output_mode:?} output is not supported by this OpenGL context. Falling back to gamma output.");
+            OutputMode::Gamma
+        };
+        log::debug!("Output mode: {output_mode:?}");
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let gl_state_support = GlStateSupport::new(&gl);
+
+        let debug = debug
+            && {
+                let supported = unsafe { enable_debug_output(&gl) };
+                if !supported {
+                    log::warn!("OpenGL debug output was requested, but KHR_debug is not supported by this context");
+                }
+                supported
+            };
+
+        let vert_src = format!(
+            "{}\n#define NEW_SHADER_INTERFACE {}\n{}\n{}",
+            shader_version_declaration,
+            shader_version.is_new_shader_interface() as i32,
+            shader_prefix,
+            VERT_SRC
+        );
+        let frag_src = format!(
+            "{}\n#define NEW_SHADER_INTERFACE {}\n#define SRGB_TEXTURES {}\n#define LINEAR_OUTPUT {}\n#define UV_CHANNELS {}\n{}\n{}",
+            shader_version_declaration,
+            shader_version.is_new_shader_interface() as i32,
+            srgb_textures as i32,
+            (output_mode != OutputMode::Gamma) as i32,
+            // The second channel of `RG8` or `LUMINANCE_ALPHA`, for NV12:
+            if coverage_format.1 == glow::RED { "rg" } else { "ra" },
+            shader_prefix,
+            FRAG_SRC
+        );
+
         unsafe {
-            let vert = compile_shader(
-                &gl,
-                glow::VERTEX_SHADER,
-                &format!(
-                    "{}\n#define NEW_SHADER_INTERFACE {}\n{}\n{}",
-                    shader_version_declaration,
-                    shader_version.is_new_shader_interface() as i32,
-                    shader_prefix,
-                    VERT_SRC
-                ),
-            )?;
-            let frag = compile_shader(
+            let program = link_program(
                 &gl,
-                glow::FRAGMENT_SHADER,
-                &format!(
-                    "{}\n#define NEW_SHADER_INTERFACE {}\n#define SRGB_TEXTURES {}\n{}\n{}",
-                    shader_version_declaration,
-                    shader_version.is_new_shader_interface() as i32,
-                    srgb_textures as i32,
-                    shader_prefix,
-                    FRAG_SRC
-                ),
+                &[
+                    (glow::VERTEX_SHADER, &vert_src),
+                    (glow::FRAGMENT_SHADER, &frag_src),
+                ],
             )?;
-            let program = link_program(&gl, [vert, frag].iter())?;
-            gl.detach_shader(program, vert);
-            gl.detach_shader(program, frag);
-            gl.delete_shader(vert);
-            gl.delete_shader(frag);
             let u_screen_size = gl.get_uniform_location(program, "u_screen_size").unwrap();
             let u_sampler = gl.get_uniform_location(program, "u_sampler").unwrap();
+            let u_sampler_u = gl.get_uniform_location(program, "u_sampler_u").unwrap();
+            let u_sampler_v = gl.get_uniform_location(program, "u_sampler_v").unwrap();
+            let u_texture_format = gl
+                .get_uniform_location(program, "u_texture_format")
+                .unwrap();
+            let u_yuv_matrix = gl.get_uniform_location(program, "u_yuv_matrix").unwrap();
+            let u_yuv_offset = gl.get_uniform_location(program, "u_yuv_offset").unwrap();
 
             let vbo = gl.create_buffer()?;
 
@@ -208,6 +589,26 @@ impl Painter {
 
             let element_array_buffer = gl.create_buffer()?;
 
+            if debug {
+                object_label(&gl, program, "egui");
+                vao.set_label(&gl, "egui");
+                for (buffer, label) in [
+                    (vbo, "egui vertices"),
+                    (element_array_buffer, "egui indices"),
+                ] {
+                    // Objects only exist once they have been bound:
+                    gl.bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
+                    object_label(&gl, buffer, label);
+                }
+                gl.bind_buffer(glow::ARRAY_BUFFER, None);
+            }
+
+            let texture_uploads = TextureUploads::new(&gl, is_webgl_1, debug);
+            let readbacks = Readbacks::new(&gl, is_webgl_1);
+            let gpu_timer = GpuTimer::new(&gl, is_webgl_1);
+            let store = SharedTextures::default();
+            let store_painter_id = store.attach();
+
             crate::check_for_gl_error_even_in_release!(&gl, "after Painter::new");
 
             Ok(Painter {
@@ -216,14 +617,54 @@ impl Painter {
                 program,
                 u_screen_size,
                 u_sampler,
+                u_sampler_u,
+                u_sampler_v,
+                u_texture_format,
+                u_yuv_matrix,
+                u_yuv_offset,
+                shader_version,
                 is_webgl_1,
                 vao,
                 srgb_textures,
+                coverage_format,
+                output_mode,
                 vbo,
                 element_array_buffer,
-                textures: Default::default(),
-                next_native_tex_id: 1 << 32,
+                store,
+                store_painter_id,
+                coverage_buffer: Vec::new(),
+                upload_budget: None,
+                texture_uploads,
+                readbacks,
+                stats: PainterStats::default(),
+                frame_stats: PainterStats::default(),
+                gl_errors_before_frame: crate::gl_error_count(),
+                gpu_timer,
+                max_anisotropy,
                 textures_to_destroy: Vec::new(),
+                intermediate_fbo: None,
+                target_fbo: None,
+                target_is_hdr: false,
+                target_offset: [0, 0],
+                multisampling: 0,
+                post_processes: Vec::new(),
+                colorblind_simulation: None,
+                intermediate: None,
+                copy_pass: None,
+                encode_pass: None,
+                quad: None,
+                intermediate_cleared: false,
+                damage_tracker: None,
+                damage_clear_color: [0.0; 4],
+                damage: Vec::new(),
+                target_buffer_age: 0,
+                recent_damage: VecDeque::new(),
+                callback_resources: Default::default(),
+                preserve_gl_state: false,
+                #[cfg(not(target_arch = "wasm32"))]
+                gl_state_support,
+                gl_state_saved: false,
+                debug,
                 destroyed: false,
             })
         }
@@ -238,6 +679,96 @@ impl Painter {
         self.max_texture_side
     }
 
+    /// Resources shared by paint callbacks, see [`CallbackTrait`].
+    pub fn callback_resources(&self) -> &CallbackResources {
+        &self.callback_resources
+    }
+
+    /// Insert resources here, e.g. right after creating the [`Painter`],
+    /// or lazily in [`CallbackTrait::prepare`].
+    pub fn callback_resources_mut(&mut self) -> &mut CallbackResources {
+        &mut self.callback_resources
+    }
+
+    /// The [`OutputMode`] in use, after any fallback done by [`Self::new`].
+    pub fn output_mode(&self) -> OutputMode {
+        self.output_mode
+    }
+
+    /// Leave the OpenGL state as we found it.
+    ///
+    /// Use this when embedding egui in an engine that has its own assumptions about the OpenGL state.
+    /// When on, every method that touches OpenGL saves the state it may change and restores it before returning.
+    /// This includes the viewport, scissor, blending, enabled capabilities, framebuffer, program,
+    /// vertex array and buffer bindings, the active texture unit and its binding,
+    /// pixel store alignment and `GL_FRAMEBUFFER_SRGB`.
+    ///
+    /// The same is done around each [`egui::PaintCallback`],
+    /// so callbacks can change any of this without breaking egui's painting.
+    ///
+    /// Vertex attributes are only preserved if the context supports vertex array objects.
+    /// Reading back the state costs a number of `glGet` calls, so this is off by default.
+    ///
+    /// Not available on the web, where the bound objects can't be read back.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_preserve_gl_state(&mut self, preserve: bool) {
+        self.preserve_gl_state = preserve;
+    }
+
+    /// See [`Self::set_preserve_gl_state`].
+    pub fn preserve_gl_state(&self) -> bool {
+        self.preserve_gl_state
+    }
+
+    /// Snapshot the OpenGL state, if [`Self::set_preserve_gl_state`] is on.
+    fn capture_gl_state(&self) -> Option<GlState> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.preserve_gl_state {
+            return Some(unsafe { GlState::capture(&self.gl, self.gl_state_support) });
+        }
+        None
+    }
+
+    unsafe fn restore_gl_state(&self, state: Option<GlState>) {
+        if let Some(state) = state {
+            state.restore(&self.gl);
+        }
+    }
+
+    /// Like [`Self::capture_gl_state`], but only in the outermost public method.
+    /// Must be paired with [`Self::pop_gl_state`].
+    fn push_gl_state(&mut self) -> Option<GlState> {
+        if self.gl_state_saved {
+            return None;
+        }
+        let state = self.capture_gl_state();
+        self.gl_state_saved = state.is_some();
+        state
+    }
+
+    fn pop_gl_state(&mut self, state: Option<GlState>) {
+        if state.is_some() {
+            self.gl_state_saved = false;
+            unsafe { self.restore_gl_state(state) };
+        }
+    }
+
+    /// Start a `KHR_debug` group, if debug output is on. Must be paired with [`Self::pop_debug_group`].
+    fn push_debug_group(&self, message: &str) {
+        if self.debug {
+            unsafe {
+                self.gl
+                    .push_debug_group(glow::DEBUG_SOURCE_APPLICATION, 0, message);
+            }
+        }
+    }
+
+    fn pop_debug_group(&self) {
+        if self.debug {
+            unsafe { self.gl.pop_debug_group() };
+        }
+    }
+
     /// The framebuffer we use as an intermediate render target,
     /// or `None` if we are painting to the screen framebuffer directly.
     ///
@@ -247,91 +778,632 @@ impl Painter {
     /// So if in a [`egui::Shape::Callback`] you need to use an offscreen FBO, you should
     /// then restore to this afterwards with
     /// `gl.bind_framebuffer(glow::FRAMEBUFFER, painter.intermediate_fbo());`
-    #[allow(clippy::unused_self)]
+    ///
+    /// This is `Some` while painting with [`Self::paint_to_texture`] or [`Self::paint_to_image`],
+    /// and when using multisampling or post-processing
+    /// (see [`Self::set_multisampling`] and [`Self::add_post_process`]).
     pub fn intermediate_fbo(&self) -> Option<glow::Framebuffer> {
-        // We don't currently ever render to an offscreen buffer,
-        // but we may want to start to in order to do anti-aliasing on web, for instance.
-        None
+        self.intermediate_fbo
     }
 
-    unsafe fn prepare_painting(
-        &mut self,
-        [width_in_pixels, height_in_pixels]: [u32; 2],
-        pixels_per_point: f32,
-    ) -> (u32, u32) {
-        self.gl.enable(glow::SCISSOR_TEST);
-        // egui outputs mesh in both winding orders
-        self.gl.disable(glow::CULL_FACE);
-        self.gl.disable(glow::DEPTH_TEST);
-
-        self.gl.color_mask(true, true, true, true);
+    /// Paint egui into a region of the framebuffer, e.g. one player's part of a split screen.
+    ///
+    /// `offset_px` is the bottom left corner of the region, in pixels from the bottom left
+    /// of the framebuffer, as for `glViewport`. The size of the region is the `screen_size_px`
+    /// you pass to [`Self::clear`] and [`Self::paint_primitives`], and egui is clipped to it.
+    /// [`Self::clear`] only clears the region.
+    ///
+    /// Paint callbacks get their viewport set to their rect within the region.
+    /// Callbacks that set their own viewport or scissor must add [`Self::viewport_offset`].
+    ///
+    /// [`Self::paint_to_texture`] and [`Self::paint_to_image`] ignore this. Default: `[0, 0]`.
+    pub fn set_target_offset(&mut self, offset_px: [i32; 2]) {
+        self.target_offset = offset_px;
+    }
 
-        self.gl.enable(glow::BLEND);
-        self.gl
-            .blend_equation_separate(glow::FUNC_ADD, glow::FUNC_ADD);
-        self.gl.blend_func_separate(
-            // egui outputs colors with premultiplied alpha:
-            glow::ONE,
-            glow::ONE_MINUS_SRC_ALPHA,
-            // Less important, but this is technically the correct alpha blend function
-            // when you want to make use of the framebuffer alpha (for screenshots, compositing, etc).
-            glow::ONE_MINUS_DST_ALPHA,
-            glow::ONE,
-        );
+    /// The offset set with [`Self::set_target_offset`].
+    pub fn target_offset(&self) -> [i32; 2] {
+        self.target_offset
+    }
 
-        if !cfg!(target_arch = "wasm32") {
-            self.gl.disable(glow::FRAMEBUFFER_SRGB);
-            check_for_gl_error!(&self.gl, "FRAMEBUFFER_SRGB");
+    /// Where egui's screen starts in the framebuffer that is currently bound for painting,
+    /// in pixels from the bottom left.
+    ///
+    /// This is [`Self::target_offset`] when painting straight to the target,
+    /// and zero when painting into an [intermediate framebuffer](Self::intermediate_fbo)
+    /// for multisampling or post-processing.
+    pub fn viewport_offset(&self) -> [i32; 2] {
+        if self.intermediate_fbo == self.target_fbo {
+            self.target_offset
+        } else {
+            [0, 0]
         }
+    }
 
-        let width_in_points = width_in_pixels as f32 / pixels_per_point;
-        let height_in_points = height_in_pixels as f32 / pixels_per_point;
-
-        self.gl
-            .viewport(0, 0, width_in_pixels as i32, height_in_pixels as i32);
-        self.gl.use_program(Some(self.program));
+    /// Paint egui into a multisampled offscreen framebuffer, which is then resolved to the screen.
+    ///
+    /// `egui` already anti-aliases its own shapes via "feathering",
+    /// so this is mostly useful for 3D content painted with [`egui::PaintCallback`]s.
+    ///
+    /// `samples` is clamped to what the driver supports. 0 or 1 turns multisampling off (default).
+    /// Only supported on OpenGL 3.0+, OpenGL ES 3.0+ and WebGL2; ignored elsewhere.
+    ///
+    /// Note that you must use [`Self::clear`] instead of [`clear`] while this is on.
+    pub fn set_multisampling(&mut self, samples: u8) {
+        // MSAA renderbuffers and blitting came with GL 3.0 and ES 3.0,
+        // which is the same versions that introduced the new shader interface.
+        let samples = if samples <= 1 {
+            0
+        } else if !self.shader_version.is_new_shader_interface() {
+            log::warn!(
+                "Multisampling is not supported on {:?}",
+                self.shader_version
+            );
+            0
+        } else {
+            let max_samples = unsafe { self.gl.get_parameter_i32(glow::MAX_SAMPLES) };
+            samples.min(max_samples.clamp(0, u8::MAX as i32) as u8)
+        };
+        self.multisampling = samples;
+    }
 
-        self.gl
-            .uniform_2_f32(Some(&self.u_screen_size), width_in_points, height_in_points);
-        self.gl.uniform_1_i32(Some(&self.u_sampler), 0);
-        self.gl.active_texture(glow::TEXTURE0);
+    /// The MSAA level set with [`Self::set_multisampling`] (after clamping). 0 means off.
+    pub fn multisampling(&self) -> u8 {
+        self.multisampling
+    }
 
-        self.vao.bind(&self.gl);
-        self.gl
-            .bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(self.element_array_buffer));
+    /// Add a full-screen pass that is run on the egui output before it is shown.
+    ///
+    /// Passes are run in the order they were added, before the built-in [`ColorblindSimulation`].
+    /// The [`Painter`] takes ownership and destroys the pass in [`Self::destroy`].
+    ///
+    /// Note that you must use [`Self::clear`] instead of [`clear`] while any pass is active,
+    /// or the pass will only see egui, and not what is behind it.
+    pub fn add_post_process(&mut self, post_process: PostProcess) {
+        self.post_processes.push(post_process);
+    }
 
-        check_for_gl_error!(&self.gl, "prepare_painting");
+    /// The passes added with [`Self::add_post_process`].
+    pub fn post_processes(&self) -> &[PostProcess] {
+        &self.post_processes
+    }
 
-        (width_in_pixels, height_in_pixels)
+    /// Remove and destroy all passes added with [`Self::add_post_process`].
+    pub fn clear_post_processes(&mut self) {
+        for post_process in self.post_processes.drain(..) {
+            post_process.destroy(&self.gl);
+        }
     }
 
-    /// You are expected to have cleared the color buffer before calling this.
-    pub fn paint_and_update_textures(
-        &mut self,
-        screen_size_px: [u32; 2],
-        pixels_per_point: f32,
-        clipped_primitives: &[egui::ClippedPrimitive],
-        textures_delta: &egui::TexturesDelta,
-    ) {
-        crate::profile_function!();
-        for (id, image_delta) in &textures_delta.set {
-            self.set_texture(*id, image_delta);
+    /// Preview how the UI looks to people with color vision deficiencies.
+    ///
+    /// `None` turns the simulation off (default).
+    pub fn set_colorblind_simulation(&mut self, simulation: Option<ColorblindSimulation>) {
+        if self.colorblind_simulation() == simulation {
+            return;
+        }
+        if let Some((_, post_process)) = self.colorblind_simulation.take() {
+            post_process.destroy(&self.gl);
         }
+        if let Some(simulation) = simulation {
+            let source = format!(
+                "{}{}",
+                self.post_process_prefix(),
+                simulation.fragment_source()
+            );
+            match PostProcess::new(&self.gl, self.shader_version, &source) {
+                Ok(post_process) => self.colorblind_simulation = Some((simulation, post_process)),
+                Err(err) => log::error!("Failed to compile colorblind simulation: {err}"),
+            }
+        }
+    }
 
-        self.paint_primitives(screen_size_px, pixels_per_point, clipped_primitives);
+    /// The simulation set with [`Self::set_colorblind_simulation`].
+    pub fn colorblind_simulation(&self) -> Option<ColorblindSimulation> {
+        self.colorblind_simulation
+            .as_ref()
+            .map(|(simulation, _)| *simulation)
+    }
 
-        for &id in &textures_delta.free {
-            self.free_texture(id);
+    /// Defines `LINEAR_COLORS` for built-in passes when the intermediate target holds linear colors.
+    fn post_process_prefix(&self) -> &'static str {
+        if self.output_mode == OutputMode::Gamma {
+            ""
+        } else {
+            "#define LINEAR_COLORS\n"
         }
     }
 
-    /// Main entry-point for painting a frame.
-    ///
-    /// You should call `target.clear_color(..)` before
-    /// and `target.finish()` after this.
-    ///
-    /// The following OpenGL features will be set:
-    /// - Scissor test will be enabled
+    /// Do we need to encode linear colors to gamma when presenting?
+    fn needs_encode_pass(&self) -> bool {
+        self.output_mode == OutputMode::Hdr && !self.target_is_hdr
+    }
+
+    /// Are we painting via [`Self::intermediate`] rather than directly to the target?
+    fn uses_intermediate_target(&self) -> bool {
+        self.multisampling > 1
+            || !self.post_processes.is_empty()
+            || self.colorblind_simulation.is_some()
+            || self.needs_encode_pass()
+            || self.damage_tracker.is_some()
+    }
+
+    /// Make sure [`Self::intermediate`] matches the current size and settings.
+    unsafe fn prepare_intermediate_target(&mut self, size: [u32; 2]) {
+        if let Some(intermediate) = &self.intermediate {
+            if intermediate.size == size && intermediate.samples == self.multisampling {
+                return;
+            }
+        }
+
+        if let Some(intermediate) = self.intermediate.take() {
+            intermediate.destroy(&self.gl);
+        }
+        if let Some(damage_tracker) = &mut self.damage_tracker {
+            // The new target is empty:
+            damage_tracker.invalidate();
+        }
+        if self.quad.is_none() {
+            self.quad = Some(Quad::new(&self.gl).unwrap());
+        }
+        if self.copy_pass.is_none() {
+            let copy = "vec4 post_process(vec2 uv) { return texture2D(u_sampler, uv); }";
+            self.copy_pass = Some(PostProcess::new(&self.gl, self.shader_version, copy).unwrap());
+        }
+        if self.encode_pass.is_none() && self.output_mode == OutputMode::Hdr {
+            self.encode_pass =
+                Some(PostProcess::new(&self.gl, self.shader_version, ENCODE_SRC).unwrap());
+        }
+        self.intermediate = Some(IntermediateTarget::new(
+            &self.gl,
+            size,
+            self.multisampling,
+            self.target_format(true).0,
+            |size| self.create_target_texture(size, true),
+        ));
+        self.gl.bind_framebuffer(glow::FRAMEBUFFER, self.target_fbo);
+    }
+
+    /// Resolve and post-process the intermediate target, and draw it to [`Self::target_fbo`].
+    ///
+    /// If `blend` is set, the result is blended on top of what is already there.
+    /// If `region` is set (in physical pixels from the top left), only that part of the target is drawn to.
+    unsafe fn present_intermediate_target(&mut self, blend: bool, region: Option<Rect>) {
+        self.push_debug_group("egui post-processing");
+        crate::profile_function!();
+        let (Some(intermediate), Some(quad), Some(copy_pass)) =
+            (&self.intermediate, &self.quad, &self.copy_pass)
+        else {
+            return;
+        };
+        let gl = &self.gl;
+
+        intermediate.resolve(gl);
+
+        gl.disable(glow::SCISSOR_TEST);
+        gl.disable(glow::BLEND);
+        let [width, height] = intermediate.size;
+        gl.viewport(0, 0, width as i32, height as i32);
+        quad.bind(gl);
+
+        let mut passes: Vec<&PostProcess> = self
+            .post_processes
+            .iter()
+            .chain(self.colorblind_simulation.as_ref().map(|(_, pass)| pass))
+            .collect();
+        if self.needs_encode_pass() {
+            passes.extend(&self.encode_pass);
+        }
+        if passes.is_empty() {
+            passes.push(copy_pass);
+        }
+
+        // Ping-pong between the two color targets, ending up at the real target:
+        let mut source = 0;
+        for (i, pass) in passes.iter().enumerate() {
+            if i + 1 == passes.len() {
+                gl.bind_framebuffer(glow::FRAMEBUFFER, self.target_fbo);
+                let [x, y] = self.target_offset;
+                gl.viewport(x, y, width as i32, height as i32);
+                if blend {
+                    gl.enable(glow::BLEND);
+                    gl.blend_func(glow::ONE, glow::ONE_MINUS_SRC_ALPHA);
+                }
+                if let Some(region) = region {
+                    gl.enable(glow::SCISSOR_TEST);
+                    gl.scissor(
+                        x + region.min.x as i32,
+                        y + height as i32 - region.max.y as i32,
+                        region.width() as i32,
+                        region.height() as i32,
+                    );
+                }
+            } else {
+                gl.bind_framebuffer(glow::FRAMEBUFFER, Some(intermediate.color[1 - source].fbo));
+            }
+            pass.draw(gl, intermediate.color[source].texture, intermediate.size);
+            source = 1 - source;
+        }
+
+        quad.unbind(gl);
+        gl.bind_texture(glow::TEXTURE_2D, None);
+        gl.disable(glow::SCISSOR_TEST);
+        gl.enable(glow::BLEND);
+        check_for_gl_error!(gl, "present_intermediate_target");
+        self.pop_debug_group();
+    }
+
+    /// Clear the screen (or the intermediate target, when using multisampling or post-processing).
+    ///
+    /// Call this instead of [`clear`] before [`Self::paint_primitives`].
+    ///
+    /// `clear_color` is in gamma space, like for [`clear`].
+    /// It is converted to linear when using [`OutputMode::Linear`] or [`OutputMode::Hdr`].
+    pub fn clear(&mut self, screen_size_in_pixels: [u32; 2], clear_color: [f32; 4]) {
+        let gl_state = self.push_gl_state();
+        self.push_debug_group("egui clear");
+        let clear_color = if self.output_mode == OutputMode::Gamma {
+            clear_color
+        } else {
+            // Like egui, we consider the color to be premultiplied in linear space:
+            let [r, g, b, a] = clear_color;
+            let linear = egui::ecolor::linear_from_gamma;
+            [linear(r), linear(g), linear(b), a]
+        };
+        unsafe { self.set_framebuffer_srgb() };
+
+        if self.damage_tracker.is_some() {
+            // Only the damaged regions are cleared, when painting:
+            unsafe { self.prepare_intermediate_target(screen_size_in_pixels) };
+            self.damage_clear_color = clear_color;
+            self.intermediate_cleared = true;
+        } else if self.uses_intermediate_target() {
+            unsafe {
+                self.prepare_intermediate_target(screen_size_in_pixels);
+                if let Some(intermediate) = &self.intermediate {
+                    self.gl
+                        .bind_framebuffer(glow::FRAMEBUFFER, Some(intermediate.paint_fbo()));
+                    clear(&self.gl, screen_size_in_pixels, clear_color);
+                    self.gl.bind_framebuffer(glow::FRAMEBUFFER, self.target_fbo);
+                }
+            }
+            self.intermediate_cleared = true;
+        } else if self.target_offset == [0, 0] {
+            clear(&self.gl, screen_size_in_pixels, clear_color);
+        } else {
+            let [x, y] = self.target_offset;
+            let [width, height] = screen_size_in_pixels.map(|side| side as i32);
+            unsafe {
+                self.gl.enable(glow::SCISSOR_TEST);
+                self.gl.scissor(x, y, width, height);
+                self.gl.viewport(x, y, width, height);
+                self.gl.clear_color(
+                    clear_color[0],
+                    clear_color[1],
+                    clear_color[2],
+                    clear_color[3],
+                );
+                self.gl.clear(glow::COLOR_BUFFER_BIT);
+                self.gl.disable(glow::SCISSOR_TEST);
+            }
+        }
+        self.pop_debug_group();
+        self.pop_gl_state(gl_state);
+    }
+
+    /// Enable sRGB encoding on write for [`OutputMode::Linear`], and disable it otherwise.
+    unsafe fn set_framebuffer_srgb(&self) {
+        if !cfg!(target_arch = "wasm32") {
+            if self.output_mode == OutputMode::Linear {
+                self.gl.enable(glow::FRAMEBUFFER_SRGB);
+            } else {
+                self.gl.disable(glow::FRAMEBUFFER_SRGB);
+            }
+            check_for_gl_error!(&self.gl, "FRAMEBUFFER_SRGB");
+        }
+    }
+
+    unsafe fn prepare_painting(
+        &mut self,
+        [width_in_pixels, height_in_pixels]: [u32; 2],
+        pixels_per_point: f32,
+    ) -> (u32, u32) {
+        if let Some(fbo) = self.intermediate_fbo {
+            // A callback may have bound some other framebuffer:
+            self.gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
+        }
+
+        self.gl.enable(glow::SCISSOR_TEST);
+        // egui outputs mesh in both winding orders
+        self.gl.disable(glow::CULL_FACE);
+        self.gl.disable(glow::DEPTH_TEST);
+
+        self.gl.color_mask(true, true, true, true);
+
+        self.gl.enable(glow::BLEND);
+        self.gl
+            .blend_equation_separate(glow::FUNC_ADD, glow::FUNC_ADD);
+        self.gl.blend_func_separate(
+            // egui outputs colors with premultiplied alpha:
+            glow::ONE,
+            glow::ONE_MINUS_SRC_ALPHA,
+            // Less important, but this is technically the correct alpha blend function
+            // when you want to make use of the framebuffer alpha (for screenshots, compositing, etc).
+            glow::ONE_MINUS_DST_ALPHA,
+            glow::ONE,
+        );
+
+        self.set_framebuffer_srgb();
+
+        let width_in_points = width_in_pixels as f32 / pixels_per_point;
+        let height_in_points = height_in_pixels as f32 / pixels_per_point;
+
+        let [x, y] = self.viewport_offset();
+        self.gl
+            .viewport(x, y, width_in_pixels as i32, height_in_pixels as i32);
+        self.gl.use_program(Some(self.program));
+
+        self.gl
+            .uniform_2_f32(Some(&self.u_screen_size), width_in_points, height_in_points);
+        self.gl.uniform_1_i32(Some(&self.u_sampler), 0);
+        self.gl.uniform_1_i32(Some(&self.u_sampler_u), 1);
+        self.gl.uniform_1_i32(Some(&self.u_sampler_v), 2);
+        self.gl.active_texture(glow::TEXTURE0);
+
+        self.vao.bind(&self.gl);
+        self.gl
+            .bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(self.element_array_buffer));
+
+        check_for_gl_error!(&self.gl, "prepare_painting");
+
+        (width_in_pixels, height_in_pixels)
+    }
+
+    /// You are expected to have cleared the color buffer before calling this.
+    pub fn paint_and_update_textures(
+        &mut self,
+        screen_size_px: [u32; 2],
+        pixels_per_point: f32,
+        clipped_primitives: &[egui::ClippedPrimitive],
+        textures_delta: &egui::TexturesDelta,
+    ) {
+        crate::profile_function!();
+        let gl_state = self.push_gl_state();
+        for (id, image_delta) in &textures_delta.set {
+            self.set_texture(*id, image_delta);
+        }
+
+        self.paint_primitives(screen_size_px, pixels_per_point, clipped_primitives);
+
+        for &id in &textures_delta.free {
+            self.free_texture(id);
+        }
+        self.pop_gl_state(gl_state);
+    }
+
+    /// Paint egui into a new texture instead of the screen.
+    ///
+    /// The texture is `screen_size_px` large, is cleared to transparent before painting,
+    /// and contains premultiplied gamma-space RGBA, just like the screen would.
+    /// Like with the screen, row 0 is at the bottom of the texture.
+    ///
+    /// This can be used to put egui on surfaces in a 3D world,
+    /// or to composite several egui layers yourself.
+    ///
+    /// The returned texture is owned by the caller, who is responsible for deleting it
+    /// with [`glow::HasContext::delete_texture`].
+    /// You can also give it to egui with [`Self::register_native_texture`].
+    ///
+    /// When done, the framebuffer that was bound before is bound again
+    /// (on the web, where it can't be read back, the default framebuffer).
+    pub fn paint_to_texture(
+        &mut self,
+        screen_size_px: [u32; 2],
+        pixels_per_point: f32,
+        clipped_primitives: &[egui::ClippedPrimitive],
+        textures_delta: &egui::TexturesDelta,
+    ) -> glow::Texture {
+        crate::profile_function!();
+        self.assert_not_destroyed();
+
+        self.paint_offscreen(
+            true,
+            screen_size_px,
+            pixels_per_point,
+            clipped_primitives,
+            textures_delta,
+            |_| {},
+        )
+    }
+
+    /// Paint egui offscreen and read the result back to the CPU.
+    ///
+    /// Like [`Self::paint_to_texture`], but returns the pixels instead of a texture.
+    /// Unlike the texture, the returned image has its first row at the top.
+    ///
+    /// This stalls until the GPU is done painting, so don't call it every frame.
+    pub fn paint_to_image(
+        &mut self,
+        screen_size_px: [u32; 2],
+        pixels_per_point: f32,
+        clipped_primitives: &[egui::ClippedPrimitive],
+        textures_delta: &egui::TexturesDelta,
+    ) -> egui::ColorImage {
+        crate::profile_function!();
+        self.assert_not_destroyed();
+
+        let mut image = None;
+        let texture = self.paint_offscreen(
+            false,
+            screen_size_px,
+            pixels_per_point,
+            clipped_primitives,
+            textures_delta,
+            |painter| image = Some(painter.read_screen_rgba(screen_size_px)),
+        );
+        unsafe { self.gl.delete_texture(texture) };
+        image.unwrap_or_default()
+    }
+
+    /// Internal format and pixel type of render target textures.
+    ///
+    /// With `hdr` set, [`OutputMode::Hdr`] uses RGBA16F.
+    /// [`OutputMode::Linear`] uses sRGB textures, so that blending happens in linear space.
+    fn target_format(&self, hdr: bool) -> (u32, u32) {
+        match self.output_mode {
+            _ if self.is_webgl_1 => (glow::RGBA, glow::UNSIGNED_BYTE),
+            OutputMode::Hdr if hdr => (glow::RGBA16F, glow::HALF_FLOAT),
+            OutputMode::Linear => (glow::SRGB8_ALPHA8, glow::UNSIGNED_BYTE),
+            _ => (glow::RGBA8, glow::UNSIGNED_BYTE),
+        }
+    }
+
+    /// A texture that can be used as a render target of the given size.
+    unsafe fn create_target_texture(&self, [width, height]: [u32; 2], hdr: bool) -> glow::Texture {
+        let texture = self.gl.create_texture().unwrap();
+        self.gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+        for (parameter, value) in [
+            (glow::TEXTURE_MAG_FILTER, glow::LINEAR),
+            (glow::TEXTURE_MIN_FILTER, glow::LINEAR),
+            (glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE),
+            (glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE),
+        ] {
+            self.gl
+                .tex_parameter_i32(glow::TEXTURE_2D, parameter, value as i32);
+        }
+        let (internal_format, pixel_type) = self.target_format(hdr);
+        self.gl.tex_image_2d(
+            glow::TEXTURE_2D,
+            0,
+            internal_format as _,
+            width as _,
+            height as _,
+            0,
+            glow::RGBA,
+            pixel_type,
+            None,
+        );
+        self.gl.bind_texture(glow::TEXTURE_2D, None);
+        check_for_gl_error!(&self.gl, "create_target_texture");
+        texture
+    }
+
+    /// Paint into a new texture via a temporary framebuffer, calling `before_unbind`
+    /// while the framebuffer is still bound.
+    ///
+    /// `hdr` picks an RGBA16F texture when using [`OutputMode::Hdr`].
+    fn paint_offscreen(
+        &mut self,
+        hdr: bool,
+        screen_size_px: [u32; 2],
+        pixels_per_point: f32,
+        clipped_primitives: &[egui::ClippedPrimitive],
+        textures_delta: &egui::TexturesDelta,
+        before_unbind: impl FnOnce(&Self),
+    ) -> glow::Texture {
+        let gl_state = self.push_gl_state();
+        unsafe {
+            let previous_fbo = draw_framebuffer(&self.gl);
+            let texture = self.create_target_texture(screen_size_px, hdr);
+            let fbo = self.gl.create_framebuffer().unwrap();
+            self.gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
+            if self.debug {
+                object_label(&self.gl, fbo, "egui offscreen target");
+            }
+            self.gl.framebuffer_texture_2d(
+                glow::FRAMEBUFFER,
+                glow::COLOR_ATTACHMENT0,
+                glow::TEXTURE_2D,
+                Some(texture),
+                0,
+            );
+            let status = self.gl.check_framebuffer_status(glow::FRAMEBUFFER);
+            if status != glow::FRAMEBUFFER_COMPLETE {
+                log::error!("Offscreen framebuffer is incomplete: 0x{status:X}");
+            }
+
+            self.target_fbo = Some(fbo);
+            self.target_is_hdr = hdr && self.output_mode == OutputMode::Hdr;
+            let target_offset = std::mem::take(&mut self.target_offset);
+            // The new texture has nothing from previous frames:
+            let mut damage_tracker = self.damage_tracker.take();
+            let damage = std::mem::take(&mut self.damage);
+            self.clear(screen_size_px, [0.0; 4]);
+            self.paint_and_update_textures(
+                screen_size_px,
+                pixels_per_point,
+                clipped_primitives,
+                textures_delta,
+            );
+            before_unbind(self);
+            self.target_fbo = None;
+            self.target_is_hdr = false;
+            self.target_offset = target_offset;
+            if let Some(damage_tracker) = &mut damage_tracker {
+                // We may have painted over the intermediate target, and missed texture changes:
+                damage_tracker.invalidate();
+            }
+            self.damage_tracker = damage_tracker;
+            self.damage = damage;
+
+            self.gl.bind_framebuffer(glow::FRAMEBUFFER, previous_fbo);
+            self.gl.delete_framebuffer(fbo);
+            check_for_gl_error!(&self.gl, "paint_offscreen");
+            self.pop_gl_state(gl_state);
+            texture
+        }
+    }
+
+    /// Run [`CallbackTrait::prepare`] for all callbacks, before anything is painted.
+    fn prepare_callbacks(
+        &mut self,
+        screen_size_px: [u32; 2],
+        pixels_per_point: f32,
+        clipped_primitives: &[egui::ClippedPrimitive],
+    ) {
+        let mut resources = std::mem::take(&mut self.callback_resources);
+        for egui::ClippedPrimitive {
+            clip_rect,
+            primitive,
+        } in clipped_primitives
+        {
+            if let Primitive::Callback(callback) = primitive {
+                if !callback.rect.is_positive() {
+                    continue;
+                }
+                let info = egui::PaintCallbackInfo {
+                    viewport: callback.rect,
+                    clip_rect: *clip_rect,
+                    pixels_per_point,
+                    screen_size_px,
+                };
+                if let Some(callback) = callback_trait(callback) {
+                    crate::profile_scope!("prepare callback");
+                    let gl_state = self.capture_gl_state();
+                    // Whatever else `prepare` changes, `prepare_painting` sets up again,
+                    // but it only binds a framebuffer when painting offscreen:
+                    let framebuffer = unsafe { draw_framebuffer(&self.gl) };
+                    self.push_debug_group("egui callback prepare");
+                    callback.prepare(&info, self, &mut resources);
+                    check_for_gl_error!(&self.gl, "prepare callback");
+                    self.pop_debug_group();
+                    unsafe {
+                        self.gl.bind_framebuffer(glow::FRAMEBUFFER, framebuffer);
+                        self.restore_gl_state(gl_state);
+                    }
+                }
+            }
+        }
+        self.callback_resources = resources;
+    }
+
+    /// Main entry-point for painting a frame.
+    ///
+    /// You should call `target.clear_color(..)` before
+    /// and `target.finish()` after this.
+    ///
+    /// The following OpenGL features will be set:
+    /// - Scissor test will be enabled
     /// - Cull face will be disabled
     /// - Blend will be enabled
     ///
@@ -344,7 +1416,8 @@ impl Painter {
     /// - Program
     ///
     /// Please be mindful of these effects when integrating into your program, and also be mindful
-    /// of the effects your program might have on this code. Look at the source if in doubt.
+    /// of the effects your program might have on this code.
+    /// Use [`Self::set_preserve_gl_state`] to have all of this restored afterwards.
     pub fn paint_primitives(
         &mut self,
         screen_size_px: [u32; 2],
@@ -353,21 +1426,209 @@ impl Painter {
     ) {
         crate::profile_function!();
         self.assert_not_destroyed();
+        self.gl_errors_before_frame = crate::gl_error_count();
+        let gl_state = self.push_gl_state();
+
+        unsafe { self.readbacks.poll(&self.gl) };
+
+        if let Some(budget) = self.upload_budget {
+            for tex_id in self.texture_uploads.pending() {
+                self.mark_texture_changed(tex_id);
+            }
+            self.push_debug_group("egui texture uploads");
+            unsafe {
+                self.gpu_timer.begin(&self.gl, Section::TextureUploads);
+                self.frame_stats.texture_upload_bytes +=
+                    self.texture_uploads
+                        .process(&self.gl, &self.store.lock().textures, budget);
+                self.gpu_timer.end(&self.gl);
+            }
+            self.pop_debug_group();
+        }
+
+        self.prepare_callbacks(screen_size_px, pixels_per_point, clipped_primitives);
 
+        let uses_intermediate_target = self.uses_intermediate_target();
+        let blend_intermediate_target = !self.intermediate_cleared;
+        if uses_intermediate_target {
+            unsafe {
+                self.prepare_intermediate_target(screen_size_px);
+                if blend_intermediate_target {
+                    // Nobody called `Painter::clear`, so we composite egui on top of whatever is there:
+                    self.clear(screen_size_px, [0.0; 4]);
+                }
+            }
+            self.intermediate_fbo = self.intermediate.as_ref().map(|i| i.paint_fbo());
+        } else {
+            self.intermediate_fbo = self.target_fbo;
+        }
+
+        // With damage tracking, we only repaint what changed since the previous frame:
+        let [width_px, height_px] = screen_size_px.map(|side| side as f32);
+        let screen_px = Rect::from_min_size(Pos2::ZERO, egui::vec2(width_px, height_px));
+        let damage = if let Some(damage_tracker) = &mut self.damage_tracker {
+            let setup = FrameSetup {
+                screen_size_px,
+                pixels_per_point,
+                clear_color: self.damage_clear_color,
+            };
+            damage_tracker.damage(setup, clipped_primitives)
+        } else {
+            vec![screen_px]
+        };
+        // When blending, what is below egui may have changed:
+        self.damage = if blend_intermediate_target {
+            vec![screen_px]
+        } else {
+            damage.clone()
+        };
+
+        self.push_debug_group("egui meshes");
         let size_in_pixels = unsafe { self.prepare_painting(screen_size_px, pixels_per_point) };
+        let offset = self.viewport_offset();
+        unsafe { self.gpu_timer.begin(&self.gl, Section::Meshes) };
+
+        let regions: Vec<Rect> = if self.damage_tracker.is_some() {
+            damage
+                .iter()
+                .map(|&region_px| {
+                    unsafe { self.clear_damaged_region(region_px, size_in_pixels) };
+                    Rect::from_min_max(
+                        (region_px.min.to_vec2() / pixels_per_point).to_pos2(),
+                        (region_px.max.to_vec2() / pixels_per_point).to_pos2(),
+                    )
+                })
+                .collect()
+        } else {
+            vec![Rect::EVERYTHING]
+        };
+        self.paint_clipped_primitives(
+            screen_size_px,
+            pixels_per_point,
+            clipped_primitives,
+            size_in_pixels,
+            offset,
+            &regions,
+        );
+
+        unsafe {
+            self.gpu_timer.end(&self.gl);
+            self.vao.unbind(&self.gl);
+            self.gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, None);
+
+            self.gl.disable(glow::SCISSOR_TEST);
 
+            check_for_gl_error!(&self.gl, "painting");
+        }
+        self.pop_debug_group();
+
+        if uses_intermediate_target && !self.damage.is_empty() {
+            let region = if self.damage_tracker.is_some() {
+                self.region_to_present(screen_px)
+            } else {
+                None
+            };
+            unsafe { self.present_intermediate_target(blend_intermediate_target, region) };
+        }
+        self.intermediate_fbo = None;
+        self.intermediate_cleared = false;
+        unsafe { self.gpu_timer.end_frame(&self.gl) };
+        self.pop_gl_state(gl_state);
+        self.finish_frame_stats();
+    }
+
+    /// With damage tracking, the part of the target to update from the intermediate target,
+    /// or `None` for all of it: what changed since the target last showed this frame,
+    /// according to [`Self::set_target_buffer_age`].
+    fn region_to_present(&mut self, screen_px: Rect) -> Option<Rect> {
+        let damage = self
+            .damage
+            .iter()
+            .fold(Rect::NOTHING, |union, rect| union.union(*rect))
+            .intersect(screen_px);
+        self.recent_damage.push_front(damage);
+        self.recent_damage.truncate(MAX_TARGET_BUFFER_AGE as usize);
+
+        let age = self.target_buffer_age as usize;
+        if age == 0 || age > self.recent_damage.len() {
+            return None;
+        }
+        let region = self
+            .recent_damage
+            .iter()
+            .take(age)
+            .fold(Rect::NOTHING, |union, rect| union.union(*rect));
+        (region != screen_px).then_some(region)
+    }
+
+    /// Clear a damaged region of the intermediate target, in physical pixels from the top left.
+    unsafe fn clear_damaged_region(&self, region_px: Rect, size_in_pixels: (u32, u32)) {
+        let [r, g, b, a] = self.damage_clear_color;
+        self.gl.scissor(
+            region_px.min.x as i32,
+            size_in_pixels.1 as i32 - region_px.max.y as i32,
+            region_px.width() as i32,
+            region_px.height() as i32,
+        );
+        self.gl.clear_color(r, g, b, a);
+        self.gl.clear(glow::COLOR_BUFFER_BIT);
+    }
+
+    /// Paint the parts of `clipped_primitives` inside `regions` (in points).
+    ///
+    /// Each mesh is uploaded once and drawn once per region it reaches,
+    /// and each callback runs once, clipped to the regions it overlaps.
+    fn paint_clipped_primitives(
+        &mut self,
+        screen_size_px: [u32; 2],
+        pixels_per_point: f32,
+        clipped_primitives: &[egui::ClippedPrimitive],
+        size_in_pixels: (u32, u32),
+        offset: [i32; 2],
+        regions: &[Rect],
+    ) {
         for egui::ClippedPrimitive {
             clip_rect,
             primitive,
         } in clipped_primitives
         {
-            set_clip_rect(&self.gl, size_in_pixels, pixels_per_point, *clip_rect);
+            let clip_rects: Vec<Rect> = regions
+                .iter()
+                .map(|region| clip_rect.intersect(*region))
+                .filter(|clip_rect| clip_rect.is_positive())
+                .collect();
+            if clip_rects.is_empty() {
+                continue;
+            }
 
             match primitive {
                 Primitive::Mesh(mesh) => {
-                    self.paint_mesh(mesh);
+                    // Skip the regions the mesh doesn't reach:
+                    let bounds = if clip_rects.len() > 1 {
+                        mesh.calc_bounds()
+                    } else {
+                        Rect::EVERYTHING
+                    };
+                    let scissors: Vec<[i32; 4]> = clip_rects
+                        .iter()
+                        .filter(|clip_rect| clip_rect.intersects(bounds))
+                        .map(|&clip_rect| {
+                            scissor_box(offset, size_in_pixels, pixels_per_point, clip_rect)
+                        })
+                        .collect();
+                    self.paint_mesh(mesh, &scissors);
                 }
                 Primitive::Callback(callback) => {
+                    let clip_rect = clip_rects
+                        .iter()
+                        .fold(Rect::NOTHING, |union, &clip_rect| union.union(clip_rect));
+                    set_clip_rect(
+                        &self.gl,
+                        offset,
+                        size_in_pixels,
+                        pixels_per_point,
+                        clip_rect,
+                    );
                     if callback.rect.is_positive() {
                         crate::profile_scope!("callback");
                         // Transform callback rect to physical pixels:
@@ -383,8 +1644,8 @@ impl Painter {
 
                         unsafe {
                             self.gl.viewport(
-                                rect_min_x,
-                                size_in_pixels.1 as i32 - rect_max_y,
+                                offset[0] + rect_min_x,
+                                offset[1] + size_in_pixels.1 as i32 - rect_max_y,
                                 rect_max_x - rect_min_x,
                                 rect_max_y - rect_min_y,
                             );
@@ -392,15 +1653,22 @@ impl Painter {
 
                         let info = egui::PaintCallbackInfo {
                             viewport: callback.rect,
-                            clip_rect: *clip_rect,
+                            clip_rect,
                             pixels_per_point,
                             screen_size_px,
                         };
 
-                        if let Some(callback) = callback.callback.downcast_ref::<CallbackFn>() {
-                            (callback.f)(info, self);
+                        if let Some(callback) = callback_trait(callback) {
+                            self.frame_stats.callbacks += 1;
+                            let gl_state = self.capture_gl_state();
+                            self.push_debug_group("egui callback");
+                            unsafe { self.gpu_timer.begin(&self.gl, Section::Callback) };
+                            callback.paint(info, self, &self.callback_resources);
+                            unsafe { self.gpu_timer.begin(&self.gl, Section::Meshes) };
+                            self.pop_debug_group();
+                            unsafe { self.restore_gl_state(gl_state) };
                         } else {
-                            log::warn!("Warning: Unsupported render callback. Expected egui_glow_tao::CallbackFn");
+                            log::warn!("Warning: Unsupported render callback. Expected egui_glow_tao::CallbackFn or egui_glow_tao::Callback");
                         }
 
                         check_for_gl_error!(&self.gl, "callback");
@@ -411,21 +1679,138 @@ impl Painter {
                 }
             }
         }
+    }
 
-        unsafe {
-            self.vao.unbind(&self.gl);
-            self.gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, None);
+    /// Only repaint the parts of the screen that changed since the previous frame.
+    ///
+    /// egui is then painted into a retained offscreen target, and [`Self::paint_primitives`] compares
+    /// the primitives with those of the previous frame. Only the regions that differ
+    /// (see [`Self::damage`]) are cleared and repainted, with scissoring, before the target is copied to the screen.
+    /// When nothing changed, nothing is painted or copied, and you can skip presenting the frame.
+    ///
+    /// [`Self::clear`] no longer clears right away, but remembers the color for the damaged regions.
+    /// Paint callbacks are always repainted.
+    /// If you change the contents of a native texture yourself, tell the painter with [`Self::mark_texture_changed`].
+    pub fn set_damage_tracking(&mut self, enabled: bool) {
+        if enabled != self.damage_tracker.is_some() {
+            self.damage_tracker = enabled.then(DamageTracker::default);
+            self.damage.clear();
+            self.recent_damage.clear();
+        }
+    }
 
-            self.gl.disable(glow::SCISSOR_TEST);
+    /// How many frames ago the target last showed the frame about to be painted over, e.g. from `EGL_EXT_buffer_age`.
+    ///
+    /// With [damage tracking](Self::set_damage_tracking), only the parts that changed since then
+    /// are copied to the target. `0` means unknown (default), and the whole target is copied,
+    /// as it is when the target is older than a few frames.
+    /// Frames with no [`Self::damage`] are assumed not to be presented.
+    /// Set this before each [`Self::paint_primitives`].
+    pub fn set_target_buffer_age(&mut self, age: u32) {
+        self.target_buffer_age = age;
+    }
 
-            check_for_gl_error!(&self.gl, "painting");
+    /// See [`Self::set_damage_tracking`].
+    pub fn damage_tracking(&self) -> bool {
+        self.damage_tracker.is_some()
+    }
+
+    /// The regions that the last [`Self::paint_primitives`] changed, in physical pixels from the top left
+    /// of the painted area.
+    ///
+    /// With [damage tracking](Self::set_damage_tracking) off this is the whole screen.
+    /// Empty if nothing changed, in which case the screen was left untouched.
+    /// You can pass this to e.g. `eglSwapBuffersWithDamageKHR` (flipping the y axis).
+    pub fn damage(&self) -> &[Rect] {
+        &self.damage
+    }
+
+    /// Repaint the primitives using `tex_id` in the next frame,
+    /// because you changed the contents of the texture.
+    ///
+    /// Only needed with [damage tracking](Self::set_damage_tracking), and only for native textures
+    /// you update yourself; the painter knows about changes it makes.
+    pub fn mark_texture_changed(&mut self, tex_id: egui::TextureId) {
+        if let Some(damage_tracker) = &mut self.damage_tracker {
+            damage_tracker.changed_textures.insert(tex_id);
         }
     }
 
+    /// Repaint everything in the next frame, e.g. because you painted over egui.
+    pub fn invalidate_damage(&mut self) {
+        if let Some(damage_tracker) = &mut self.damage_tracker {
+            damage_tracker.invalidate();
+        }
+    }
+
+    /// What the painter did in the last frame.
+    ///
+    /// A frame ends with [`Self::paint_primitives`] (which [`Self::paint_and_update_textures`] calls),
+    /// and includes the texture uploads and callbacks since the previous one.
+    pub fn stats(&self) -> PainterStats {
+        self.stats
+    }
+
+    /// Measure how long the GPU spends on texture uploads, the egui meshes and each paint callback,
+    /// using `TIME_ELAPSED` timer queries.
+    ///
+    /// Results are read back without stalling, so [`Self::gpu_timings`] lags a few frames behind.
+    /// While this is on, paint callbacks must not use `TIME_ELAPSED` queries of their own.
+    ///
+    /// Returns `false`, and stays off, if the context doesn't support timer queries
+    /// (OpenGL 3.3 or `GL_ARB_timer_query`, or `EXT_disjoint_timer_query` on OpenGL ES 3.0 and WebGL2).
+    pub fn set_gpu_timing(&mut self, enabled: bool) -> bool {
+        let ok = self.gpu_timer.set_enabled(enabled);
+        if !ok {
+            log::warn!("GPU timing is not supported by this OpenGL context");
+        }
+        ok
+    }
+
+    /// Is GPU timing on? See [`Self::set_gpu_timing`].
+    pub fn gpu_timing(&self) -> bool {
+        self.gpu_timer.is_enabled()
+    }
+
+    /// Does the context support [`Self::set_gpu_timing`]?
+    pub fn supports_gpu_timing(&self) -> bool {
+        self.gpu_timer.is_supported()
+    }
+
+    /// The GPU time of the most recent frame whose results are in, a few frames ago.
+    ///
+    /// `None` until [`Self::set_gpu_timing`] has been on for a few frames,
+    /// and after the driver reports that the GPU was disturbed while measuring (`GL_GPU_DISJOINT_EXT`).
+    pub fn gpu_timings(&self) -> Option<&GpuTimings> {
+        self.gpu_timer.latest()
+    }
+
+    fn finish_frame_stats(&mut self) {
+        // Unlike `check_for_gl_error!`, this also checks in release builds:
+        crate::check_for_gl_error_even_in_release!(&self.gl, "paint_primitives");
+        self.frame_stats.gl_errors = crate::gl_error_count() - self.gl_errors_before_frame;
+        let store = self.store.lock();
+        self.frame_stats.textures = store.textures.len();
+        self.frame_stats.texture_bytes = store.texture_bytes.values().sum();
+        drop(store);
+        self.stats = std::mem::take(&mut self.frame_stats);
+    }
+
     #[inline(never)] // Easier profiling
-    fn paint_mesh(&mut self, mesh: &Mesh) {
+    /// Upload `mesh` and draw it once per scissor box (`[x, y, width, height]` in physical pixels).
+    fn paint_mesh(&mut self, mesh: &Mesh, scissors: &[[i32; 4]]) {
         debug_assert!(mesh.is_valid());
-        if let Some(texture) = self.texture(mesh.texture_id) {
+        let mut store = self.store.lock();
+        store.use_texture(mesh.texture_id, self.store_painter_id);
+        let texture = store.textures.get(&mesh.texture_id).copied();
+        let coverage = store.coverage_textures.contains(&mesh.texture_id);
+        let yuv = store.yuv_textures.get(&mesh.texture_id).copied();
+        drop(store);
+        if self.texture_uploads.is_incomplete(mesh.texture_id) {
+            // Drawn once it is uploaded, instead of showing garbage until then:
+            return;
+        }
+        if let Some(texture) = texture {
             unsafe {
                 self.gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
                 self.gl.buffer_data_u8_slice(
@@ -443,16 +1828,36 @@ impl Painter {
                 );
 
                 self.gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+
+                let texture_format = if coverage {
+                    TEXTURE_FORMAT_COVERAGE
+                } else if let Some(yuv) = yuv {
+                    self.bind_yuv_texture(yuv);
+                    match yuv.planes {
+                        YuvPlanes::I420 { .. } => TEXTURE_FORMAT_I420,
+                        YuvPlanes::Nv12 { .. } => TEXTURE_FORMAT_NV12,
+                    }
+                } else {
+                    TEXTURE_FORMAT_RGBA
+                };
+                self.gl
+                    .uniform_1_i32(Some(&self.u_texture_format), texture_format);
             }
 
-            unsafe {
-                self.gl.draw_elements(
-                    glow::TRIANGLES,
-                    mesh.indices.len() as i32,
-                    glow::UNSIGNED_INT,
-                    0,
-                );
+            for &[x, y, width, height] in scissors {
+                unsafe {
+                    self.gl.scissor(x, y, width, height);
+                    self.gl.draw_elements(
+                        glow::TRIANGLES,
+                        mesh.indices.len() as i32,
+                        glow::UNSIGNED_INT,
+                        0,
+                    );
+                }
+                self.frame_stats.draw_calls += 1;
             }
+            self.frame_stats.vertices += mesh.vertices.len();
+            self.frame_stats.indices += mesh.indices.len();
 
             check_for_gl_error!(&self.gl, "paint_mesh");
         } else {
@@ -460,32 +1865,88 @@ impl Painter {
         }
     }
 
+    /// Bind the chroma planes next to the luma plane, and set the conversion to RGB.
+    unsafe fn bind_yuv_texture(&self, yuv: YuvTexture) {
+        let (matrix, offset) = yuv.conversion();
+        self.gl
+            .uniform_matrix_3_f32_slice(Some(&self.u_yuv_matrix), false, &matrix);
+        self.gl
+            .uniform_3_f32_slice(Some(&self.u_yuv_offset), &offset);
+        for (unit, plane) in [glow::TEXTURE1, glow::TEXTURE2]
+            .into_iter()
+            .zip(yuv.planes.chroma())
+        {
+            if let Some(plane) = plane {
+                self.gl.active_texture(unit);
+                self.gl.bind_texture(glow::TEXTURE_2D, Some(plane));
+            }
+        }
+        self.gl.active_texture(glow::TEXTURE0);
+    }
+
     // ------------------------------------------------------------------------
 
     pub fn set_texture(&mut self, tex_id: egui::TextureId, delta: &egui::epaint::ImageDelta) {
+        self.set_texture_cow(tex_id, Cow::Borrowed(delta));
+    }
+
+    /// Like [`Self::set_texture`], but takes the image so a queued upload
+    /// (see [`Self::set_upload_budget`]) can keep it instead of copying it.
+    pub fn set_texture_owned(&mut self, tex_id: egui::TextureId, delta: egui::epaint::ImageDelta) {
+        self.set_texture_cow(tex_id, Cow::Owned(delta));
+    }
+
+    fn set_texture_cow(
+        &mut self,
+        tex_id: egui::TextureId,
+        delta: Cow<'_, egui::epaint::ImageDelta>,
+    ) {
         crate::profile_function!();
 
         self.assert_not_destroyed();
-
-        let glow_texture = *self
-            .textures
-            .entry(tex_id)
-            .or_insert_with(|| unsafe { self.gl.create_texture().unwrap() });
+        self.mark_texture_changed(tex_id);
+        let gl_state = self.push_gl_state();
+        self.push_debug_group("egui textures");
+        unsafe { self.gpu_timer.begin(&self.gl, Section::TextureUploads) };
+
+        let glow_texture = unsafe {
+            self.store
+                .lock()
+                .get_or_create(&self.gl, tex_id, self.store_painter_id)
+        };
         unsafe {
             self.gl.bind_texture(glow::TEXTURE_2D, Some(glow_texture));
+            if self.debug && delta.pos.is_none() {
+                object_label(&self.gl, glow_texture, &format!("egui texture {tex_id:?}"));
+            }
         }
 
-        match &delta.image {
-            egui::ImageData::Color(image) => {
+        let (pos, options) = (delta.pos, delta.options);
+        let image = match delta {
+            Cow::Borrowed(delta) => Cow::Borrowed(&delta.image),
+            Cow::Owned(delta) => Cow::Owned(delta.image),
+        };
+        match image.as_ref() {
+            egui::ImageData::Color(color_image) => {
                 assert_eq!(
-                    image.width() * image.height(),
-                    image.pixels.len(),
+                    color_image.width() * color_image.height(),
+                    color_image.pixels.len(),
                     "Mismatch between texture size and texel count"
                 );
 
-                let data: &[u8] = bytemuck::cast_slice(image.pixels.as_ref());
+                let size = color_image.size;
+                let data: Cow<'_, [u8]> = match image {
+                    Cow::Borrowed(egui::ImageData::Color(image)) => {
+                        Cow::Borrowed(bytemuck::cast_slice(&image.pixels))
+                    }
+                    Cow::Owned(egui::ImageData::Color(image)) => {
+                        Cow::Owned(bytemuck::allocation::cast_vec(image.pixels))
+                    }
+                    _ => unreachable!(),
+                };
 
-                self.upload_texture_srgb(delta.pos, image.size, delta.options, data);
+                self.store.lock().coverage_textures.remove(&tex_id);
+                self.upload_texture(tex_id, pos, size, options, false, data);
             }
             egui::ImageData::Font(image) => {
                 assert_eq!(
@@ -494,57 +1955,75 @@ impl Painter {
                     "Mismatch between texture size and texel count"
                 );
 
-                let data: Vec<u8> = image
-                    .srgba_pixels(None)
-                    .flat_map(|a| a.to_array())
-                    .collect();
+                // Only upload the coverage, which the shader turns into premultiplied white:
+                let mut data = std::mem::take(&mut self.coverage_buffer);
+                data.clear();
+                data.extend(image.srgba_pixels(None).map(|color| color.a()));
+
+                self.store.lock().coverage_textures.insert(tex_id);
+                self.upload_texture(tex_id, pos, image.size, options, true, Cow::Borrowed(&data));
 
-                self.upload_texture_srgb(delta.pos, image.size, delta.options, &data);
+                // Keep the buffer for the next glyphs, but don't hold on to a whole atlas:
+                if pos.is_some() {
+                    self.coverage_buffer = data;
+                }
             }
         };
+        unsafe { self.gpu_timer.end(&self.gl) };
+        self.pop_debug_group();
+        self.pop_gl_state(gl_state);
     }
 
-    fn upload_texture_srgb(
+    /// Upload sRGBA pixels, or a single coverage channel if `coverage` is set.
+    fn upload_texture(
         &mut self,
+        tex_id: egui::TextureId,
         pos: Option<[usize; 2]>,
         [w, h]: [usize; 2],
         options: egui::TextureOptions,
-        data: &[u8],
+        coverage: bool,
+        data: Cow<'_, [u8]>,
     ) {
-        assert_eq!(data.len(), w * h * 4);
+        let bytes_per_texel = if coverage { 1 } else { 4 };
+        assert_eq!(data.len(), w * h * bytes_per_texel);
         assert!(
             w <= self.max_texture_side && h <= self.max_texture_side,
             "Got a texture image of size {}x{}, but the maximum supported texture side is only {}",
             w,
-            h,
-            self.max_texture_side
-        );
-
-        unsafe {
-            self.gl.tex_parameter_i32(
-                glow::TEXTURE_2D,
-                glow::TEXTURE_MAG_FILTER,
-                options.magnification.glow_code() as i32,
-            );
-            self.gl.tex_parameter_i32(
-                glow::TEXTURE_2D,
-                glow::TEXTURE_MIN_FILTER,
-                options.minification.glow_code() as i32,
-            );
+            h,
+            self.max_texture_side
+        );
 
-            self.gl.tex_parameter_i32(
-                glow::TEXTURE_2D,
-                glow::TEXTURE_WRAP_S,
-                glow::CLAMP_TO_EDGE as i32,
-            );
-            self.gl.tex_parameter_i32(
-                glow::TEXTURE_2D,
-                glow::TEXTURE_WRAP_T,
-                glow::CLAMP_TO_EDGE as i32,
+        let sampler = {
+            let mut store = self.store.lock();
+            let entry = store.samplers.entry(tex_id).or_default();
+            entry.0 = options;
+            entry.1
+        };
+        if self.is_webgl_1
+            && pos.is_none()
+            && (sampler.wrap_mode != TextureWrapMode::ClampToEdge || sampler.mipmap_mode.is_some())
+            && !(w.is_power_of_two() && h.is_power_of_two())
+        {
+            log::warn!(
+                "WebGL1 can only repeat and mipmap power-of-two textures, but {tex_id:?} is {w}x{h}"
             );
-            check_for_gl_error!(&self.gl, "tex_parameter");
+        }
+
+        if pos.is_none() {
+            let mut bytes = data.len();
+            if sampler.mipmap_mode.is_some() {
+                bytes += bytes / 3;
+            }
+            self.store.lock().texture_bytes.insert(tex_id, bytes);
+        }
+
+        unsafe {
+            self.apply_sampler(options, sampler);
 
-            let (internal_format, src_format) = if self.is_webgl_1 {
+            let (internal_format, src_format) = if coverage {
+                self.coverage_format
+            } else if self.is_webgl_1 {
                 let format = if self.srgb_textures {
                     glow::SRGB_ALPHA
                 } else {
@@ -559,7 +2038,42 @@ impl Painter {
 
             self.gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
 
+            // Font updates are small, and needed right away:
+            let queue = self.upload_budget.is_some() && !coverage;
+
             let level = 0;
+            if queue {
+                if pos.is_none() {
+                    // Allocate the texture now, and fill it in over the next frames:
+                    self.gl.tex_image_2d(
+                        glow::TEXTURE_2D,
+                        level,
+                        internal_format as _,
+                        w as _,
+                        h as _,
+                        0,
+                        src_format,
+                        glow::UNSIGNED_BYTE,
+                        None,
+                    );
+                    check_for_gl_error!(&self.gl, "tex_image_2d");
+                }
+                // Counted when actually uploaded:
+                self.texture_uploads.push(PendingUpload {
+                    tex_id,
+                    pos: pos.unwrap_or_default(),
+                    size: [w, h],
+                    src_format,
+                    bytes_per_texel,
+                    data: data.into_owned(),
+                    whole_texture: pos.is_none(),
+                    generate_mipmaps: sampler.mipmap_mode.is_some(),
+                    rows_uploaded: 0,
+                });
+                return;
+            }
+
+            self.frame_stats.texture_upload_bytes += data.len();
             if let Some([x, y]) = pos {
                 self.gl.tex_sub_image_2d(
                     glow::TEXTURE_2D,
@@ -570,7 +2084,7 @@ impl Painter {
                     h as _,
                     src_format,
                     glow::UNSIGNED_BYTE,
-                    glow::PixelUnpackData::Slice(data),
+                    glow::PixelUnpackData::Slice(&data),
                 );
                 check_for_gl_error!(&self.gl, "tex_sub_image_2d");
             } else {
@@ -584,22 +2098,196 @@ impl Painter {
                     border,
                     src_format,
                     glow::UNSIGNED_BYTE,
-                    Some(data),
+                    Some(&data),
                 );
                 check_for_gl_error!(&self.gl, "tex_image_2d");
             }
+
+            if sampler.mipmap_mode.is_some() {
+                self.gl.generate_mipmap(glow::TEXTURE_2D);
+                check_for_gl_error!(&self.gl, "generate_mipmap");
+            }
+        }
+    }
+
+    /// Set the sampling parameters of the texture bound to `TEXTURE_2D`.
+    unsafe fn apply_sampler(&self, options: egui::TextureOptions, sampler: SamplerOptions) {
+        let min_filter = match sampler.mipmap_mode {
+            Some(mipmap) => mipmap_min_filter(options.minification, mipmap),
+            None => options.minification.glow_code(),
+        };
+        self.gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_MAG_FILTER,
+            options.magnification.glow_code() as i32,
+        );
+        self.gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_MIN_FILTER,
+            min_filter as i32,
+        );
+
+        let wrap_mode = sampler.wrap_mode.glow_code() as i32;
+        self.gl
+            .tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, wrap_mode);
+        self.gl
+            .tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, wrap_mode);
+
+        if let Some(anisotropy) = self.anisotropy(sampler) {
+            self.gl.tex_parameter_f32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MAX_ANISOTROPY_EXT,
+                anisotropy,
+            );
+        }
+        check_for_gl_error!(&self.gl, "tex_parameter");
+    }
+
+    /// The `TEXTURE_MAX_ANISOTROPY_EXT` to set for `sampler`, clamped to what the driver supports,
+    /// or `None` without `EXT_texture_filter_anisotropic`.
+    fn anisotropy(&self, sampler: SamplerOptions) -> Option<f32> {
+        (self.max_anisotropy > 1.0)
+            .then(|| (sampler.anisotropy as f32).clamp(1.0, self.max_anisotropy))
+    }
+
+    /// Change how a texture is sampled, e.g. to make it repeat or to use mipmaps.
+    ///
+    /// This works both for textures managed by egui and for native textures.
+    /// The options are kept for as long as the texture lives,
+    /// and you can call this before egui has uploaded the texture.
+    ///
+    /// For native textures registered without options,
+    /// this also resets the filters to [`egui::TextureOptions::default`].
+    pub fn set_sampler_options(&mut self, tex_id: egui::TextureId, sampler: SamplerOptions) {
+        self.assert_not_destroyed();
+        self.mark_texture_changed(tex_id);
+        let mut store = self.store.lock();
+        let entry = store.samplers.entry(tex_id).or_default();
+        entry.1 = sampler;
+        let options = entry.0;
+        let texture = store.textures.get(&tex_id).copied();
+        let chroma = store
+            .yuv_textures
+            .get(&tex_id)
+            .map_or([None, None], |yuv| yuv.planes.chroma());
+        drop(store);
+        if let Some(texture) = texture {
+            let gl_state = self.push_gl_state();
+            unsafe {
+                for texture in std::iter::once(texture).chain(chroma.into_iter().flatten()) {
+                    self.gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+                    self.apply_sampler(options, sampler);
+                    if sampler.mipmap_mode.is_some() {
+                        self.gl.generate_mipmap(glow::TEXTURE_2D);
+                    }
+                }
+                self.gl.bind_texture(glow::TEXTURE_2D, None);
+            }
+            self.pop_gl_state(gl_state);
+        }
+    }
+
+    /// The options set with [`Self::set_sampler_options`].
+    pub fn sampler_options(&self, tex_id: egui::TextureId) -> SamplerOptions {
+        self.store
+            .lock()
+            .samplers
+            .get(&tex_id)
+            .map(|(_, sampler)| *sampler)
+            .unwrap_or_default()
+    }
+
+    /// Spread the uploads of egui-managed color textures over several frames,
+    /// uploading about `bytes_per_frame` each time egui is painted (but at least one row).
+    ///
+    /// Uploads are staged through a pixel buffer object on OpenGL 2.1, OpenGL ES 3.0 and WebGL2,
+    /// so the driver can copy them to the texture in the background.
+    /// Until a texture is done its contents are undefined; use [`Self::texture_upload_progress`]
+    /// to show a placeholder instead.
+    /// Font texture updates are always uploaded right away.
+    ///
+    /// `None` (the default) uploads everything immediately, blocking until done.
+    /// Turning the budget off finishes the pending uploads.
+    pub fn set_upload_budget(&mut self, bytes_per_frame: Option<usize>) {
+        self.upload_budget = bytes_per_frame;
+        if bytes_per_frame.is_none() && !self.texture_uploads.is_empty() {
+            let gl_state = self.push_gl_state();
+            self.frame_stats.texture_upload_bytes += unsafe {
+                self.texture_uploads
+                    .process(&self.gl, &self.store.lock().textures, usize::MAX)
+            };
+            self.pop_gl_state(gl_state);
         }
     }
 
+    /// The budget set with [`Self::set_upload_budget`].
+    pub fn upload_budget(&self) -> Option<usize> {
+        self.upload_budget
+    }
+
+    /// How much of the pending data of a texture has been uploaded, from 0 to 1.
+    ///
+    /// `None` if the texture is not waiting for any uploads.
+    pub fn texture_upload_progress(&self, tex_id: egui::TextureId) -> Option<f32> {
+        self.texture_uploads.progress(tex_id)
+    }
+
+    /// The textures waiting for uploads, with their progress from 0 to 1.
+    pub fn pending_texture_uploads(&self) -> Vec<(egui::TextureId, f32)> {
+        self.texture_uploads
+            .pending()
+            .into_iter()
+            .filter_map(|tex_id| Some((tex_id, self.texture_uploads.progress(tex_id)?)))
+            .collect()
+    }
+
+    /// True if there are uploads left for the next frames, which you should then paint soon.
+    pub fn has_pending_texture_uploads(&self) -> bool {
+        !self.texture_uploads.is_empty()
+    }
+
+    /// Delete a texture, or with [`SharedTextures`] drop a reference to it.
     pub fn free_texture(&mut self, tex_id: egui::TextureId) {
-        if let Some(old_tex) = self.textures.remove(&tex_id) {
-            unsafe { self.gl.delete_texture(old_tex) };
+        let textures = self.store.lock().release(tex_id, self.store_painter_id);
+        if !textures.is_empty() {
+            self.texture_uploads.remove(tex_id);
+        }
+        for texture in textures {
+            unsafe { self.gl.delete_texture(texture) };
         }
     }
 
     /// Get the [`glow::Texture`] bound to a [`egui::TextureId`].
+    ///
+    /// Font textures only have a coverage channel, stored as `R8` (or `LUMINANCE` on WebGL1 and OpenGL 2.1).
+    /// For YUV textures this is the luma plane.
     pub fn texture(&self, texture_id: egui::TextureId) -> Option<glow::Texture> {
-        self.textures.get(&texture_id).copied()
+        self.store.texture(texture_id)
+    }
+
+    /// A handle to the textures of this painter, to share them with other painters,
+    /// see [`SharedTextures`].
+    pub fn shared_textures(&self) -> SharedTextures {
+        self.store.clone()
+    }
+
+    /// Use `shared` for all textures, together with the other painters using it.
+    ///
+    /// The painters must be on OpenGL contexts that share objects, e.g. created with
+    /// glutin's `ContextAttributesBuilder::with_sharing`.
+    /// The textures this painter already has are moved into `shared`;
+    /// call this right after creating the painter to avoid clashing ids.
+    /// If they are already shared with other painters, they are left to those instead.
+    pub fn set_shared_textures(&mut self, shared: SharedTextures) {
+        self.assert_not_destroyed();
+        if self.store.ptr_eq(&shared) {
+            return;
+        }
+        let painter_id = shared.attach();
+        let old = std::mem::replace(&mut self.store, shared);
+        let old_painter_id = std::mem::replace(&mut self.store_painter_id, painter_id);
+        self.store.lock().merge(&mut old.lock(), painter_id);
+        unsafe { old.detach(&self.gl, old_painter_id) };
     }
 
     #[deprecated = "renamed 'texture'"]
@@ -610,40 +2298,164 @@ impl Painter {
     #[allow(clippy::needless_pass_by_value)] // False positive
     pub fn register_native_texture(&mut self, native: glow::Texture) -> egui::TextureId {
         self.assert_not_destroyed();
-        let id = egui::TextureId::User(self.next_native_tex_id);
-        self.next_native_tex_id += 1;
-        self.textures.insert(id, native);
+        self.store
+            .lock()
+            .insert_native(native, Some(self.store_painter_id))
+    }
+
+    /// Like [`Self::register_native_texture`], but also sets the filtering, wrap mode and mipmaps.
+    ///
+    /// If [`SamplerOptions::mipmap_mode`] is set, mipmaps are generated from the current contents
+    /// of `native`. Call [`Self::set_sampler_options`] again after changing its contents
+    /// to regenerate them.
+    pub fn register_native_texture_with_options(
+        &mut self,
+        native: glow::Texture,
+        options: egui::TextureOptions,
+        sampler: SamplerOptions,
+    ) -> egui::TextureId {
+        let id = self.register_native_texture(native);
+        self.store
+            .lock()
+            .samplers
+            .insert(id, (options, SamplerOptions::default()));
+        self.set_sampler_options(id, sampler);
         id
     }
 
     #[allow(clippy::needless_pass_by_value)] // False positive
     pub fn replace_native_texture(&mut self, id: egui::TextureId, replacing: glow::Texture) {
-        if let Some(old_tex) = self.textures.insert(id, replacing) {
+        self.mark_texture_changed(id);
+        let mut store = self.store.lock();
+        if let Some(old_tex) = store.replace(id, replacing, self.store_painter_id) {
             self.textures_to_destroy.push(old_tex);
         }
+        store.coverage_textures.remove(&id);
+        store.texture_bytes.remove(&id);
+        if let Some(yuv) = store.yuv_textures.remove(&id) {
+            self.textures_to_destroy
+                .extend(yuv.planes.chroma().into_iter().flatten());
+        }
+        let sampler = store.samplers.get(&id).map(|&(_, sampler)| sampler);
+        drop(store);
+        self.texture_uploads.remove(id);
+        if let Some(sampler) = sampler {
+            self.set_sampler_options(id, sampler);
+        }
+    }
+
+    /// Register the planes of a YUV image, which you can then show like any other egui texture.
+    ///
+    /// The planes are converted to RGB in the shader, using `color_space` and `range`.
+    /// Like with [`Self::register_native_texture`], the painter takes ownership of the planes:
+    /// they are deleted by [`Self::free_texture`] and [`Self::replace_yuv_texture`].
+    ///
+    /// The planes are sampled with [`egui::TextureOptions::LINEAR`]; change that with [`Self::set_sampler_options`].
+    pub fn register_yuv_texture(
+        &mut self,
+        planes: YuvPlanes,
+        color_space: YuvColorSpace,
+        range: YuvRange,
+    ) -> egui::TextureId {
+        let id = self.register_native_texture(planes.y());
+        let mut store = self.store.lock();
+        store.yuv_textures.insert(
+            id,
+            YuvTexture {
+                planes,
+                color_space,
+                range,
+            },
+        );
+        store.samplers.insert(
+            id,
+            (egui::TextureOptions::LINEAR, SamplerOptions::default()),
+        );
+        drop(store);
+        self.set_sampler_options(id, SamplerOptions::default());
+        id
+    }
+
+    /// Replace the planes of a texture registered with [`Self::register_yuv_texture`],
+    /// keeping its color space and range.
+    ///
+    /// To show a new frame, you can also upload it to the existing planes instead.
+    pub fn replace_yuv_texture(&mut self, id: egui::TextureId, planes: YuvPlanes) {
+        self.mark_texture_changed(id);
+        let yuv = self.store.lock().yuv_textures.get(&id).copied();
+        let Some(YuvTexture {
+            color_space, range, ..
+        }) = yuv
+        else {
+            log::warn!("{id:?} is not a YUV texture");
+            return;
+        };
+        self.replace_native_texture(id, planes.y());
+        let mut store = self.store.lock();
+        store.yuv_textures.insert(
+            id,
+            YuvTexture {
+                planes,
+                color_space,
+                range,
+            },
+        );
+        let sampler = store.samplers.get(&id).map(|&(_, sampler)| sampler);
+        drop(store);
+        if let Some(sampler) = sampler {
+            self.set_sampler_options(id, sampler);
+        }
     }
 
+    /// Read back the pixels of the currently bound framebuffer, with the first row at the top.
     pub fn read_screen_rgba(&self, [w, h]: [u32; 2]) -> egui::ColorImage {
-        let mut pixels = vec![0_u8; (w * h * 4) as usize];
+        unsafe { read_pixels_rgba(&self.gl, [0, 0, w, h]) }
+    }
+
+    /// Read back part of the currently bound framebuffer, with the first row at the top.
+    ///
+    /// `region` is in points, like egui coordinates, and is clamped to the screen.
+    pub fn read_screen_region_rgba(
+        &self,
+        screen_size_px: [u32; 2],
+        pixels_per_point: f32,
+        region: egui::Rect,
+    ) -> egui::ColorImage {
+        let region = region_in_pixels(screen_size_px, pixels_per_point, region);
+        unsafe { read_pixels_rgba(&self.gl, region) }
+    }
+
+    /// Start reading back the currently bound framebuffer (or the `region` of it, in points)
+    /// without waiting for the GPU to finish drawing.
+    ///
+    /// The pixels are copied into a pixel buffer guarded by a fence, and `callback` gets the image
+    /// (first row at the top) during a later paint call, once the GPU is done.
+    /// Keep painting while [`Self::has_pending_readbacks`] is true.
+    ///
+    /// Without OpenGL 3.2, OpenGL ES 3.0 or WebGL2 the pixels are read right away,
+    /// but `callback` is still called on the next paint.
+    pub fn read_screen_rgba_async(
+        &mut self,
+        screen_size_px: [u32; 2],
+        pixels_per_point: f32,
+        region: Option<egui::Rect>,
+        callback: impl FnOnce(egui::ColorImage) + crate::SendIfNative + 'static,
+    ) {
+        self.assert_not_destroyed();
+        let [width_px, height_px] = screen_size_px;
+        let region = region.map_or([0, 0, width_px, height_px], |region| {
+            region_in_pixels(screen_size_px, pixels_per_point, region)
+        });
+        let gl_state = self.push_gl_state();
         unsafe {
-            self.gl.read_pixels(
-                0,
-                0,
-                w as _,
-                h as _,
-                glow::RGBA,
-                glow::UNSIGNED_BYTE,
-                glow::PixelPackData::Slice(&mut pixels),
-            );
-        }
-        let mut flipped = Vec::with_capacity((w * h * 4) as usize);
-        for row in pixels.chunks_exact((w * 4) as usize).rev() {
-            flipped.extend_from_slice(bytemuck::cast_slice(row));
-        }
-        egui::ColorImage {
-            size: [w as usize, h as usize],
-            pixels: flipped,
+            self.readbacks.request(&self.gl, region, Box::new(callback));
         }
+        self.pop_gl_state(gl_state);
+    }
+
+    /// True while callbacks of [`Self::read_screen_rgba_async`] are waiting for the GPU.
+    pub fn has_pending_readbacks(&self) -> bool {
+        !self.readbacks.is_empty()
     }
 
     pub fn read_screen_rgb(&self, [w, h]: [u32; 2]) -> Vec<u8> {
@@ -664,20 +2476,41 @@ impl Painter {
 
     unsafe fn destroy_gl(&self) {
         self.gl.delete_program(self.program);
-        for tex in self.textures.values() {
-            self.gl.delete_texture(*tex);
-        }
+        self.store.detach(&self.gl, self.store_painter_id);
         self.gl.delete_buffer(self.vbo);
         self.gl.delete_buffer(self.element_array_buffer);
         for t in &self.textures_to_destroy {
             self.gl.delete_texture(*t);
         }
+        self.texture_uploads.destroy(&self.gl);
+        self.readbacks.destroy(&self.gl);
+        self.gpu_timer.destroy(&self.gl);
+        self.vao.destroy(&self.gl);
+        for post_process in &self.post_processes {
+            post_process.destroy(&self.gl);
+        }
+        if let Some((_, post_process)) = &self.colorblind_simulation {
+            post_process.destroy(&self.gl);
+        }
+        if let Some(copy_pass) = &self.copy_pass {
+            copy_pass.destroy(&self.gl);
+        }
+        if let Some(encode_pass) = &self.encode_pass {
+            encode_pass.destroy(&self.gl);
+        }
+        if let Some(quad) = &self.quad {
+            quad.destroy(&self.gl);
+        }
+        if let Some(intermediate) = &self.intermediate {
+            intermediate.destroy(&self.gl);
+        }
     }
 
     /// This function must be called before [`Painter`] is dropped, as [`Painter`] has some OpenGL objects
     /// that should be deleted.
     pub fn destroy(&mut self) {
         if !self.destroyed {
+            self.callback_resources.destroy(&self.gl);
             unsafe {
                 self.destroy_gl();
             }
@@ -723,10 +2556,24 @@ impl Drop for Painter {
 
 fn set_clip_rect(
     gl: &glow::Context,
+    offset: [i32; 2],
     size_in_pixels: (u32, u32),
     pixels_per_point: f32,
     clip_rect: Rect,
 ) {
+    let [x, y, width, height] = scissor_box(offset, size_in_pixels, pixels_per_point, clip_rect);
+    unsafe {
+        gl.scissor(x, y, width, height);
+    }
+}
+
+/// The scissor box (`[x, y, width, height]`, from the bottom left) for a clip rect in points.
+fn scissor_box(
+    [offset_x, offset_y]: [i32; 2],
+    size_in_pixels: (u32, u32),
+    pixels_per_point: f32,
+    clip_rect: Rect,
+) -> [i32; 4] {
     // Transform clip rect to physical pixels:
     let clip_min_x = pixels_per_point * clip_rect.min.x;
     let clip_min_y = pixels_per_point * clip_rect.min.y;
@@ -745,12 +2592,653 @@ fn set_clip_rect(
     let clip_max_x = clip_max_x.clamp(clip_min_x, size_in_pixels.0 as i32);
     let clip_max_y = clip_max_y.clamp(clip_min_y, size_in_pixels.1 as i32);
 
-    unsafe {
-        gl.scissor(
-            clip_min_x,
-            size_in_pixels.1 as i32 - clip_max_y,
-            clip_max_x - clip_min_x,
-            clip_max_y - clip_min_y,
+    [
+        offset_x + clip_min_x,
+        offset_y + size_in_pixels.1 as i32 - clip_max_y,
+        clip_max_x - clip_min_x,
+        clip_max_y - clip_min_y,
+    ]
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::headless;
+    use egui::Color32;
+
+    fn painter(gl: &Arc<glow::Context>) -> Painter {
+        Painter::new(gl.clone(), "", None).unwrap()
+    }
+
+    #[test]
+    fn paint_to_image_reads_back_what_was_painted() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let mut painter = painter(&headless.gl);
+        let rect = Rect::from_min_max(egui::pos2(8.0, 4.0), egui::pos2(24.0, 12.0));
+        let image = painter.paint_to_image(
+            [32, 16],
+            1.0,
+            &headless::colored_rect(rect, Color32::RED),
+            &headless::font_texture(),
+        );
+
+        assert_eq!(image.size, [32, 16]);
+        // The first row is at the top:
+        assert_eq!(image[(10, 6)], Color32::RED);
+        assert_eq!(image[(2, 2)], Color32::TRANSPARENT);
+        assert_eq!(image[(10, 14)], Color32::TRANSPARENT);
+        painter.destroy();
+    }
+
+    #[test]
+    fn glsl_330_shaders_paint() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let gl = &headless.gl;
+        let glsl = unsafe { gl.get_parameter_string(glow::SHADING_LANGUAGE_VERSION) };
+        if !matches!(
+            ShaderVersion::parse(&glsl),
+            Ok(ShaderVersion::Gl330 | ShaderVersion::Gl410)
+        ) {
+            eprintln!("Skipping: the context doesn't support GLSL 3.30: {glsl:?}");
+            return;
+        }
+        let mut painter = Painter::new(gl.clone(), "", Some(ShaderVersion::Gl330)).unwrap();
+        assert_eq!(painter.shader_version, ShaderVersion::Gl330);
+        let rect = Rect::from_min_size(Pos2::ZERO, egui::vec2(4.0, 4.0));
+        let image = painter.paint_to_image(
+            [8, 8],
+            1.0,
+            &headless::colored_rect(rect, Color32::RED),
+            &headless::font_texture(),
+        );
+        assert_eq!(image[(1, 1)], Color32::RED);
+        assert_eq!(image[(6, 6)], Color32::TRANSPARENT);
+        assert_eq!(painter.stats().gl_errors, 0);
+        painter.destroy();
+    }
+
+    #[test]
+    fn paint_to_texture_keeps_the_bound_framebuffer() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let gl = &headless.gl;
+        let mut painter = painter(gl);
+        unsafe {
+            let fbo = gl.create_framebuffer().unwrap();
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
+
+            let texture = painter.paint_to_texture(
+                [16, 16],
+                1.0,
+                &headless::colored_rect(
+                    Rect::from_min_size(Pos2::ZERO, egui::vec2(16.0, 16.0)),
+                    Color32::RED,
+                ),
+                &headless::font_texture(),
+            );
+
+            assert_eq!(draw_framebuffer(gl), Some(fbo));
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            gl.delete_framebuffer(fbo);
+            gl.delete_texture(texture);
+        }
+        painter.destroy();
+    }
+
+    #[test]
+    fn textures_are_not_drawn_until_uploaded() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let mut painter = painter(&headless.gl);
+        // One row of the red 2x2 image per frame:
+        painter.set_upload_budget(Some(8));
+        let tex_id = egui::TextureId::User(1);
+        let image = egui::ColorImage::new([2, 2], Color32::RED);
+        painter.set_texture_owned(
+            tex_id,
+            egui::epaint::ImageDelta::full(image, egui::TextureOptions::NEAREST),
+        );
+
+        let rect = Rect::from_min_size(Pos2::ZERO, egui::vec2(4.0, 4.0));
+        let mut mesh = Mesh::with_texture(tex_id);
+        let uv = Rect::from_min_max(Pos2::ZERO, egui::pos2(1.0, 1.0));
+        mesh.add_rect_with_uv(rect, uv, Color32::WHITE);
+        let primitives = [egui::ClippedPrimitive {
+            clip_rect: Rect::EVERYTHING,
+            primitive: Primitive::Mesh(mesh),
+        }];
+
+        let no_textures = egui::TexturesDelta::default();
+        let image = painter.paint_to_image([4, 4], 1.0, &primitives, &no_textures);
+        assert_eq!(painter.texture_upload_progress(tex_id), Some(0.5));
+        assert_eq!(image[(1, 1)], Color32::TRANSPARENT);
+
+        let image = painter.paint_to_image([4, 4], 1.0, &primitives, &no_textures);
+        assert_eq!(painter.texture_upload_progress(tex_id), None);
+        assert_eq!(image[(1, 1)], Color32::RED);
+        painter.destroy();
+    }
+
+    #[test]
+    fn gl_errors_are_counted_per_painter() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let gl = &headless.gl;
+        let mut faulty = painter(gl);
+        let mut other = painter(gl);
+        let rect = Rect::from_min_size(Pos2::ZERO, egui::vec2(16.0, 16.0));
+        let primitives = [egui::ClippedPrimitive {
+            clip_rect: rect,
+            primitive: Primitive::Callback(egui::PaintCallback {
+                rect,
+                callback: Arc::new(crate::CallbackFn::new(|_, painter| unsafe {
+                    // GL_INVALID_ENUM:
+                    painter.gl().enable(glow::RGBA);
+                })),
+            }),
+        }];
+        unsafe {
+            let (fbo, texture) = headless::render_target(gl, [16, 16]);
+            faulty.paint_and_update_textures([16, 16], 1.0, &primitives, &headless::font_texture());
+            other.paint_and_update_textures([16, 16], 1.0, &[], &headless::font_texture());
+            assert_eq!(faulty.stats().gl_errors, 1);
+            assert_eq!(other.stats().gl_errors, 0);
+
+            faulty.paint_and_update_textures([16, 16], 1.0, &[], &headless::font_texture());
+            assert_eq!(faulty.stats().gl_errors, 0);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            gl.delete_framebuffer(fbo);
+            gl.delete_texture(texture);
+        }
+        faulty.destroy();
+        other.destroy();
+    }
+
+    #[test]
+    fn gpu_timings_have_meshes_and_callbacks() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let gl = &headless.gl;
+        let mut painter = painter(gl);
+        if !painter.set_gpu_timing(true) {
+            eprintln!("Skipping: the context doesn't support timer queries");
+            painter.destroy();
+            return;
+        }
+        let rect = Rect::from_min_size(Pos2::ZERO, egui::vec2(16.0, 16.0));
+        let mut primitives = headless::colored_rect(rect, Color32::RED);
+        primitives.push(egui::ClippedPrimitive {
+            clip_rect: rect,
+            primitive: Primitive::Callback(egui::PaintCallback {
+                rect,
+                callback: Arc::new(crate::CallbackFn::new(|_, painter| unsafe {
+                    painter.gl().clear(glow::COLOR_BUFFER_BIT);
+                })),
+            }),
+        });
+        unsafe {
+            let (fbo, texture) = headless::render_target(gl, [16, 16]);
+            let mut frames = 0;
+            while painter.gpu_timings().is_none() && frames < 100 {
+                painter.paint_and_update_textures(
+                    [16, 16],
+                    1.0,
+                    &primitives,
+                    &headless::font_texture(),
+                );
+                gl.finish();
+                frames += 1;
+            }
+            let timings = painter
+                .gpu_timings()
+                .expect("no GPU timings after 100 frames");
+            assert_eq!(timings.callbacks.len(), 1);
+            assert!(timings.meshes >= 0.0 && timings.meshes < 1.0, "{timings:?}");
+            assert!(timings.total() >= timings.meshes);
+
+            painter.set_gpu_timing(false);
+            assert_eq!(painter.gpu_timings(), None);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            gl.delete_framebuffer(fbo);
+            gl.delete_texture(texture);
+        }
+        painter.destroy();
+    }
+
+    #[test]
+    fn clip_rect_is_offset_into_the_region() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let gl = &headless.gl;
+        let clip_rect = Rect::from_min_max(egui::pos2(1.0, 2.0), egui::pos2(5.0, 20.0));
+        set_clip_rect(gl, [8, 4], (16, 12), 2.0, clip_rect);
+        let mut scissor = [0; 4];
+        unsafe { gl.get_parameter_i32_slice(glow::SCISSOR_BOX, &mut scissor) };
+        // Clamped to the 16x12 region, and flipped to count from its bottom:
+        assert_eq!(scissor, [8 + 2, 4, 8, 12 - 4]);
+    }
+
+    #[test]
+    fn painting_with_an_offset_stays_in_the_region() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let gl = &headless.gl;
+        let mut painter = painter(gl);
+        painter.set_target_offset([8, 8]);
+        let region = Rect::from_min_size(Pos2::ZERO, egui::vec2(16.0, 16.0));
+        let mut primitives = headless::colored_rect(region, Color32::RED);
+        primitives[0].clip_rect = Rect::from_min_size(Pos2::ZERO, egui::vec2(8.0, 8.0));
+        unsafe {
+            let (fbo, texture) = headless::render_target(gl, [32, 32]);
+            painter.clear([16, 16], [0.0, 0.0, 1.0, 1.0]);
+            painter.paint_and_update_textures(
+                [16, 16],
+                1.0,
+                &primitives,
+                &headless::font_texture(),
+            );
+
+            // The top left quarter of the region, which is 8..24 from the bottom left:
+            assert_eq!(headless::read_pixel(gl, fbo, [10, 20]), Color32::RED);
+            // Cleared, but clipped:
+            assert_eq!(headless::read_pixel(gl, fbo, [20, 20]), Color32::BLUE);
+            assert_eq!(headless::read_pixel(gl, fbo, [10, 10]), Color32::BLUE);
+            // Outside of the region:
+            assert_eq!(headless::read_pixel(gl, fbo, [4, 4]), Color32::TRANSPARENT);
+            assert_eq!(
+                headless::read_pixel(gl, fbo, [28, 28]),
+                Color32::TRANSPARENT
+            );
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            gl.delete_framebuffer(fbo);
+            gl.delete_texture(texture);
+        }
+        painter.destroy();
+    }
+
+    #[test]
+    fn viewport_offset_is_only_applied_when_painting_straight_to_the_target() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let gl = &headless.gl;
+        let offsets = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let rect = Rect::from_min_size(Pos2::ZERO, egui::vec2(16.0, 16.0));
+        let primitives = [egui::ClippedPrimitive {
+            clip_rect: rect,
+            primitive: Primitive::Callback(egui::PaintCallback {
+                rect,
+                callback: Arc::new(crate::CallbackFn::new({
+                    let offsets = offsets.clone();
+                    move |_, painter| offsets.lock().unwrap().push(painter.viewport_offset())
+                })),
+            }),
+        }];
+
+        let mut painter = painter(gl);
+        painter.set_target_offset([8, 4]);
+        assert_eq!(painter.viewport_offset(), [8, 4]);
+        unsafe {
+            let (fbo, texture) = headless::render_target(gl, [32, 32]);
+            painter.paint_and_update_textures(
+                [16, 16],
+                1.0,
+                &primitives,
+                &headless::font_texture(),
+            );
+            // Via a multisampled target, which egui's screen fills:
+            painter.set_multisampling(4);
+            painter.clear([16, 16], [0.0; 4]);
+            painter.paint_and_update_textures(
+                [16, 16],
+                1.0,
+                &primitives,
+                &headless::font_texture(),
+            );
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            gl.delete_framebuffer(fbo);
+            gl.delete_texture(texture);
+        }
+        assert_eq!(*offsets.lock().unwrap(), [[8, 4], [0, 0]]);
+        painter.destroy();
+    }
+
+    #[test]
+    fn shared_textures_outlive_the_painter_that_freed_them() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let gl = &headless.gl;
+        let mut uploader = painter(gl);
+        let mut other = painter(gl);
+        other.set_shared_textures(uploader.shared_textures());
+
+        let tex_id = egui::TextureId::User(1);
+        let image = egui::ColorImage::new([1, 1], Color32::RED);
+        uploader.set_texture(
+            tex_id,
+            &egui::epaint::ImageDelta::full(image, egui::TextureOptions::NEAREST),
+        );
+        let mut mesh = Mesh::with_texture(tex_id);
+        let rect = Rect::from_min_size(Pos2::ZERO, egui::vec2(4.0, 4.0));
+        mesh.add_rect_with_uv(rect, rect, Color32::WHITE);
+        let primitives = [egui::ClippedPrimitive {
+            clip_rect: Rect::EVERYTHING,
+            primitive: Primitive::Mesh(mesh),
+        }];
+        let no_textures = egui::TexturesDelta::default();
+        other.paint_to_image([4, 4], 1.0, &primitives, &no_textures);
+
+        uploader.free_texture(tex_id);
+        assert!(other.texture(tex_id).is_some());
+        let image = other.paint_to_image([4, 4], 1.0, &primitives, &no_textures);
+        assert_eq!(image[(1, 1)], Color32::RED);
+
+        other.free_texture(tex_id);
+        assert!(uploader.texture(tex_id).is_none());
+        uploader.destroy();
+        other.destroy();
+    }
+
+    #[test]
+    fn sampler_options_are_applied_to_the_texture() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let gl = &headless.gl;
+        let mut painter = painter(gl);
+        let tex_id = egui::TextureId::User(1);
+        let red = egui::ColorImage::new([4, 4], Color32::RED);
+        painter.set_texture(
+            tex_id,
+            &egui::epaint::ImageDelta::full(red, egui::TextureOptions::NEAREST),
+        );
+        painter.set_sampler_options(
+            tex_id,
+            SamplerOptions {
+                wrap_mode: TextureWrapMode::Repeat,
+                mipmap_mode: Some(egui::TextureFilter::Nearest),
+                anisotropy: u8::MAX,
+            },
+        );
+
+        let texture = painter.texture(tex_id).unwrap();
+        unsafe {
+            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            let parameter = |name| gl.get_tex_parameter_i32(glow::TEXTURE_2D, name);
+            assert_eq!(parameter(glow::TEXTURE_WRAP_S) as u32, glow::REPEAT);
+            assert_eq!(parameter(glow::TEXTURE_WRAP_T) as u32, glow::REPEAT);
+            assert_eq!(
+                parameter(glow::TEXTURE_MIN_FILTER) as u32,
+                glow::NEAREST_MIPMAP_NEAREST
+            );
+            if painter.max_anisotropy > 1.0 {
+                assert_eq!(
+                    parameter(glow::TEXTURE_MAX_ANISOTROPY_EXT),
+                    painter.max_anisotropy as i32
+                );
+            }
+            gl.bind_texture(glow::TEXTURE_2D, None);
+        }
+
+        // A partial update regenerates the mipmaps, so the 1x1 level turns blue too:
+        let blue = egui::ColorImage::new([4, 4], Color32::BLUE);
+        painter.set_texture(
+            tex_id,
+            &egui::epaint::ImageDelta::partial([0, 0], blue, egui::TextureOptions::NEAREST),
         );
+        let mut mesh = Mesh::with_texture(tex_id);
+        mesh.add_rect_with_uv(
+            Rect::from_min_size(Pos2::ZERO, egui::vec2(1.0, 1.0)),
+            Rect::from_min_max(Pos2::ZERO, egui::pos2(1.0, 1.0)),
+            Color32::WHITE,
+        );
+        let primitives = [egui::ClippedPrimitive {
+            clip_rect: Rect::EVERYTHING,
+            primitive: Primitive::Mesh(mesh),
+        }];
+        let image = painter.paint_to_image([1, 1], 1.0, &primitives, &Default::default());
+        assert_eq!(image[(0, 0)], Color32::BLUE);
+        painter.destroy();
+    }
+
+    #[test]
+    fn anisotropy_is_clamped_to_what_the_driver_supports() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let mut painter = painter(&headless.gl);
+        let sampler = |anisotropy| SamplerOptions {
+            anisotropy,
+            ..Default::default()
+        };
+        painter.max_anisotropy = 4.0;
+        assert_eq!(painter.anisotropy(sampler(0)), Some(1.0));
+        assert_eq!(painter.anisotropy(sampler(2)), Some(2.0));
+        assert_eq!(painter.anisotropy(sampler(16)), Some(4.0));
+        // Without `EXT_texture_filter_anisotropic`:
+        painter.max_anisotropy = 1.0;
+        assert_eq!(painter.anisotropy(sampler(16)), None);
+        painter.destroy();
+    }
+
+    #[test]
+    fn painters_not_using_a_shared_texture_cannot_free_it() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let gl = &headless.gl;
+        let mut user = painter(gl);
+        let mut bystander = painter(gl);
+        let shared = user.shared_textures();
+        bystander.set_shared_textures(shared.clone());
+
+        let tex_id = egui::TextureId::User(1);
+        let image = egui::ColorImage::new([1, 1], Color32::RED);
+        user.set_texture(
+            tex_id,
+            &egui::epaint::ImageDelta::full(image, egui::TextureOptions::NEAREST),
+        );
+        assert!(shared.retain(tex_id));
+
+        // Both painters forward the same `TexturesDelta::free`:
+        bystander.free_texture(tex_id);
+        bystander.free_texture(tex_id);
+        user.free_texture(tex_id);
+        assert!(user.texture(tex_id).is_some());
+
+        shared.free_texture(gl, tex_id);
+        assert!(user.texture(tex_id).is_none());
+        user.destroy();
+        bystander.destroy();
+    }
+
+    #[test]
+    fn damaged_regions_are_painted_in_one_pass() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let gl = &headless.gl;
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let callback_rect = Rect::from_min_size(egui::pos2(6.0, 6.0), egui::vec2(2.0, 2.0));
+        let frame = |color: Color32| {
+            let mut primitives = headless::colored_rect(
+                Rect::from_min_size(Pos2::ZERO, egui::vec2(2.0, 2.0)),
+                color,
+            );
+            primitives.extend(headless::colored_rect(
+                Rect::from_min_size(egui::pos2(12.0, 12.0), egui::vec2(2.0, 2.0)),
+                color,
+            ));
+            primitives.push(egui::ClippedPrimitive {
+                clip_rect: Rect::EVERYTHING,
+                primitive: Primitive::Callback(egui::PaintCallback {
+                    rect: callback_rect,
+                    callback: Arc::new(crate::CallbackFn::new({
+                        let calls = calls.clone();
+                        move |_, _| {
+                            calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        }
+                    })),
+                }),
+            });
+            primitives
+        };
+
+        let mut painter = painter(gl);
+        painter.set_damage_tracking(true);
+        unsafe {
+            let (fbo, texture) = headless::render_target(gl, [16, 16]);
+            painter.clear([16, 16], [0.0, 0.0, 1.0, 1.0]);
+            painter.paint_and_update_textures(
+                [16, 16],
+                1.0,
+                &frame(Color32::RED),
+                &headless::font_texture(),
+            );
+            painter.clear([16, 16], [0.0, 0.0, 1.0, 1.0]);
+            painter.paint_and_update_textures(
+                [16, 16],
+                1.0,
+                &frame(Color32::GREEN),
+                &egui::TexturesDelta::default(),
+            );
+            // Both rectangles and the callback, far enough apart not to be merged:
+            assert_eq!(painter.damage().len(), 3);
+            assert_eq!(painter.stats().draw_calls, 2);
+            // The retained target, which keeps what wasn't damaged:
+            let retained = painter.intermediate.as_ref().unwrap().paint_fbo();
+            assert_eq!(headless::read_pixel(gl, retained, [1, 14]), Color32::GREEN);
+            assert_eq!(headless::read_pixel(gl, retained, [13, 2]), Color32::GREEN);
+            assert_eq!(headless::read_pixel(gl, retained, [8, 2]), Color32::BLUE);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            gl.delete_framebuffer(fbo);
+            gl.delete_texture(texture);
+        }
+        // Once per frame, not once per damaged region:
+        assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 2);
+        painter.destroy();
+    }
+
+    #[test]
+    fn only_recent_damage_is_copied_to_the_target() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let gl = &headless.gl;
+        let frame = |color: Color32| {
+            headless::colored_rect(Rect::from_min_size(Pos2::ZERO, egui::vec2(4.0, 4.0)), color)
+        };
+        let mut painter = painter(gl);
+        painter.set_damage_tracking(true);
+        unsafe {
+            let (fbo, texture) = headless::render_target(gl, [16, 16]);
+            painter.target_fbo = Some(fbo);
+            let paint = |painter: &mut Painter, color, buffer_age| {
+                // Stands in for the contents of an older back buffer:
+                gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
+                gl.clear_color(1.0, 1.0, 1.0, 1.0);
+                gl.clear(glow::COLOR_BUFFER_BIT);
+                painter.set_target_buffer_age(buffer_age);
+                painter.clear([16, 16], [0.0, 0.0, 1.0, 1.0]);
+                painter.paint_and_update_textures(
+                    [16, 16],
+                    1.0,
+                    &frame(color),
+                    &headless::font_texture(),
+                );
+            };
+
+            paint(&mut painter, Color32::RED, 1);
+            // Everything is new:
+            assert_eq!(headless::read_pixel(gl, fbo, [8, 8]), Color32::BLUE);
+
+            paint(&mut painter, Color32::GREEN, 1);
+            assert_eq!(
+                painter.damage(),
+                [Rect::from_min_size(Pos2::ZERO, egui::vec2(4.0, 4.0))]
+            );
+            // Only the changed rectangle, at the top left, was copied:
+            assert_eq!(headless::read_pixel(gl, fbo, [1, 14]), Color32::GREEN);
+            assert_eq!(headless::read_pixel(gl, fbo, [8, 8]), Color32::WHITE);
+
+            // An unknown buffer age means copying everything:
+            paint(&mut painter, Color32::RED, 0);
+            assert_eq!(headless::read_pixel(gl, fbo, [1, 14]), Color32::RED);
+            assert_eq!(headless::read_pixel(gl, fbo, [8, 8]), Color32::BLUE);
+
+            painter.target_fbo = None;
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            gl.delete_framebuffer(fbo);
+            gl.delete_texture(texture);
+        }
+        painter.destroy();
+    }
+
+    struct PrepareIntoOwnFramebuffer(glow::Framebuffer);
+
+    impl CallbackTrait for PrepareIntoOwnFramebuffer {
+        fn prepare(&self, _: &PaintCallbackInfo, painter: &Painter, _: &mut CallbackResources) {
+            unsafe {
+                painter
+                    .gl()
+                    .bind_framebuffer(glow::FRAMEBUFFER, Some(self.0));
+                painter.gl().clear_color(0.0, 1.0, 0.0, 1.0);
+                painter.gl().clear(glow::COLOR_BUFFER_BIT);
+            }
+        }
+
+        fn paint(&self, _: PaintCallbackInfo, _: &Painter, _: &CallbackResources) {}
+    }
+
+    #[test]
+    fn prepare_binding_a_framebuffer_does_not_redirect_egui() {
+        let Some(headless) = headless::gl() else {
+            return;
+        };
+        let gl = &headless.gl;
+        let size = [16, 16];
+        let rect = Rect::from_min_size(Pos2::ZERO, egui::vec2(16.0, 16.0));
+        let mut painter = painter(gl);
+        unsafe {
+            let (callback_fbo, callback_texture) = headless::render_target(gl, size);
+            let (screen_fbo, screen_texture) = headless::render_target(gl, size);
+
+            let mut primitives = vec![egui::ClippedPrimitive {
+                clip_rect: rect,
+                primitive: Primitive::Callback(crate::Callback::new_paint_callback(
+                    rect,
+                    PrepareIntoOwnFramebuffer(callback_fbo),
+                )),
+            }];
+            primitives.extend(headless::colored_rect(rect, Color32::RED));
+            painter.paint_and_update_textures(size, 1.0, &primitives, &headless::font_texture());
+
+            assert_eq!(headless::read_pixel(gl, screen_fbo, [8, 8]), Color32::RED);
+            assert_eq!(
+                headless::read_pixel(gl, callback_fbo, [8, 8]),
+                Color32::GREEN
+            );
+            assert_eq!(draw_framebuffer(gl), Some(screen_fbo));
+
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            for fbo in [callback_fbo, screen_fbo] {
+                gl.delete_framebuffer(fbo);
+            }
+            for texture in [callback_texture, screen_texture] {
+                gl.delete_texture(texture);
+            }
+        }
+        painter.destroy();
     }
 }
@@ -0,0 +1,472 @@
+#![allow(unsafe_code)]
+
+use glow::HasContext as _;
+
+use crate::check_for_gl_error;
+use crate::misc_util::compile_and_link;
+use crate::shader_version::ShaderVersion;
+use crate::vao;
+
+const VERT_SRC: &str = include_str!("shader/post_process_vertex.glsl");
+const FRAG_SRC: &str = include_str!("shader/post_process_fragment.glsl");
+
+#[cfg(not(target_arch = "wasm32"))]
+type SetUniformsFn = Box<dyn Fn(&glow::Context, glow::Program) + Send + Sync>;
+
+#[cfg(target_arch = "wasm32")]
+type SetUniformsFn = Box<dyn Fn(&glow::Context, glow::Program)>;
+
+/// A full-screen pass that is run on the output of egui before it is shown.
+///
+/// Register it with [`crate::Painter::add_post_process`].
+///
+/// The fragment source must define a function `vec4 post_process(vec2 uv)`,
+/// which is called for each pixel of the screen.
+/// It can sample the current image with `texture2D(u_sampler, uv)`,
+/// and use `u_texel_size` (the size of one pixel in uv units) for things like blurs.
+///
+/// The image is premultiplied gamma-space RGBA, and so should the return value be.
+/// With [`crate::OutputMode::Linear`] and [`crate::OutputMode::Hdr`] it is premultiplied linear RGBA instead.
+///
+/// ```
+/// let invert = "vec4 post_process(vec2 uv) {
+///     vec4 color = texture2D(u_sampler, uv);
+///     return vec4(color.a - color.rgb, color.a);
+/// }";
+/// ```
+pub struct PostProcess {
+    program: glow::Program,
+    u_sampler: Option<glow::UniformLocation>,
+    u_texel_size: Option<glow::UniformLocation>,
+    set_uniforms: Option<SetUniformsFn>,
+}
+
+impl PostProcess {
+    /// Compile a post-processing pass.
+    ///
+    /// # Errors
+    /// If the shader fails to compile or link.
+    pub fn new(
+        gl: &glow::Context,
+        shader_version: ShaderVersion,
+        fragment_source: &str,
+    ) -> Result<Self, String> {
+        crate::profile_function!();
+        let header = format!(
+            "{}\n#define NEW_SHADER_INTERFACE {}\n",
+            shader_version.version_declaration(),
+            shader_version.is_new_shader_interface() as i32,
+        );
+
+        unsafe {
+            let vertex_source = format!("{header}{VERT_SRC}");
+            let fragment_source = format!("{header}{FRAG_SRC}\n{fragment_source}");
+            let program = compile_and_link(
+                gl,
+                &[
+                    (glow::VERTEX_SHADER, &vertex_source),
+                    (glow::FRAGMENT_SHADER, &fragment_source),
+                ],
+                |program| {
+                    // So all passes can share one vertex array:
+                    gl.bind_attrib_location(program, A_POS_LOCATION, "a_pos");
+                },
+            )?;
+
+            Ok(Self {
+                program,
+                u_sampler: gl.get_uniform_location(program, "u_sampler"),
+                u_texel_size: gl.get_uniform_location(program, "u_texel_size"),
+                set_uniforms: None,
+            })
+        }
+    }
+
+    /// Set a function that sets any extra uniforms of the pass, e.g. the strength of a color grade.
+    ///
+    /// It is called each frame, with the program already in use.
+    #[inline]
+    pub fn with_uniforms(
+        mut self,
+        set_uniforms: impl Fn(&glow::Context, glow::Program) + crate::SendSyncIfNative + 'static,
+    ) -> Self {
+        self.set_uniforms = Some(Box::new(set_uniforms));
+        self
+    }
+
+    /// The linked shader program.
+    pub fn program(&self) -> glow::Program {
+        self.program
+    }
+
+    /// Draw a full-screen quad sampling from `texture`.
+    ///
+    /// The [`Quad`] and the target framebuffer must already be bound.
+    pub(crate) unsafe fn draw(&self, gl: &glow::Context, texture: glow::Texture, [w, h]: [u32; 2]) {
+        gl.use_program(Some(self.program));
+        gl.active_texture(glow::TEXTURE0);
+        gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+        gl.uniform_1_i32(self.u_sampler.as_ref(), 0);
+        gl.uniform_2_f32(self.u_texel_size.as_ref(), 1.0 / w as f32, 1.0 / h as f32);
+        if let Some(set_uniforms) = &self.set_uniforms {
+            set_uniforms(gl, self.program);
+        }
+        gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
+        check_for_gl_error!(gl, "post_process");
+    }
+
+    /// Call to release the allocated graphics resources.
+    ///
+    /// [`crate::Painter::destroy`] does this for all passes it owns.
+    pub fn destroy(&self, gl: &glow::Context) {
+        unsafe { gl.delete_program(self.program) };
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// A built-in pass that simulates how people with color vision deficiencies see the screen.
+///
+/// Enable it with [`crate::Painter::set_colorblind_simulation`].
+///
+/// Uses the full-severity matrices of Machado, Oliveira & Fernandes (2009).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ColorblindSimulation {
+    /// Missing L-cones (red-blind).
+    Protanopia,
+
+    /// Missing M-cones (green-blind).
+    Deuteranopia,
+
+    /// Missing S-cones (blue-blind).
+    Tritanopia,
+
+    /// No color vision at all.
+    Achromatopsia,
+}
+
+impl ColorblindSimulation {
+    pub const ALL: [Self; 4] = [
+        Self::Protanopia,
+        Self::Deuteranopia,
+        Self::Tritanopia,
+        Self::Achromatopsia,
+    ];
+
+    /// Rows of the matrix that maps linear RGB to the simulated linear RGB.
+    ///
+    /// `severity` goes from `0.0` (normal vision) to `1.0` (full deficiency).
+    /// In between, this interpolates linearly from the identity matrix,
+    /// which is close to the per-severity matrices in the paper.
+    fn matrix(self, severity: f32) -> [[f32; 3]; 3] {
+        let severity = severity.clamp(0.0, 1.0);
+        let full = self.full_matrix();
+        let mut matrix = [[0.0; 3]; 3];
+        for (row, (matrix_row, full_row)) in matrix.iter_mut().zip(full).enumerate() {
+            for (column, (value, full_value)) in matrix_row.iter_mut().zip(full_row).enumerate() {
+                let identity = if row == column { 1.0 } else { 0.0 };
+                *value = identity + severity * (full_value - identity);
+            }
+        }
+        matrix
+    }
+
+    fn full_matrix(self) -> [[f32; 3]; 3] {
+        match self {
+            Self::Protanopia => [
+                [0.152_286, 1.052_583, -0.204_868],
+                [0.114_503, 0.786_281, 0.099_216],
+                [-0.003_882, -0.048_116, 1.051_998],
+            ],
+            Self::Deuteranopia => [
+                [0.367_322, 0.860_646, -0.227_968],
+                [0.280_085, 0.672_501, 0.047_413],
+                [-0.011_820, 0.042_940, 0.968_881],
+            ],
+            Self::Tritanopia => [
+                [1.255_528, -0.076_749, -0.178_779],
+                [-0.078_411, 0.930_809, 0.147_602],
+                [0.004_733, 0.691_367, 0.303_900],
+            ],
+            Self::Achromatopsia => [[0.2126, 0.7152, 0.0722]; 3],
+        }
+    }
+
+    /// Fragment source for use with [`PostProcess::new`].
+    pub fn fragment_source(self) -> String {
+        self.fragment_source_with_severity(1.0)
+    }
+
+    /// Like [`Self::fragment_source`], for a milder form of the deficiency:
+    /// `severity` goes from `0.0` (normal vision) to `1.0` (full deficiency).
+    pub fn fragment_source_with_severity(self, severity: f32) -> String {
+        let [r, g, b] = self.matrix(severity);
+        let row = |[x, y, z]: [f32; 3]| format!("vec3({x:?}, {y:?}, {z:?})");
+        format!(
+            r#"
+vec4 post_process(vec2 uv) {{
+    vec4 color = texture2D(u_sampler, uv);
+    // Colors are premultiplied in linear space, and the matrix is linear,
+    // so we can apply it to the premultiplied color directly.
+#ifdef LINEAR_COLORS
+    vec3 rgb = color.rgb;
+#else
+    vec3 rgb = linear_from_srgb_gamma(color.rgb);
+#endif
+    rgb = vec3(dot({r}, rgb), dot({g}, rgb), dot({b}, rgb));
+#ifdef LINEAR_COLORS
+    return vec4(max(rgb, 0.0), color.a);
+#else
+    return vec4(srgb_gamma_from_linear(clamp(rgb, 0.0, 1.0)), color.a);
+#endif
+}}
+"#,
+            r = row(r),
+            g = row(g),
+            b = row(b),
+        )
+    }
+}
+
+impl std::fmt::Display for ColorblindSimulation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Protanopia => "Protanopia".fmt(f),
+            Self::Deuteranopia => "Deuteranopia".fmt(f),
+            Self::Tritanopia => "Tritanopia".fmt(f),
+            Self::Achromatopsia => "Achromatopsia".fmt(f),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+const A_POS_LOCATION: u32 = 0;
+
+/// The full-screen quad drawn by every [`PostProcess`].
+pub(crate) struct Quad {
+    vbo: glow::Buffer,
+    vao: vao::VertexArrayObject,
+}
+
+impl Quad {
+    pub(crate) unsafe fn new(gl: &glow::Context) -> Result<Self, String> {
+        let positions: [f32; 8] = [-1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0];
+        let vbo = gl.create_buffer()?;
+        gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
+        gl.buffer_data_u8_slice(
+            glow::ARRAY_BUFFER,
+            bytemuck::cast_slice(&positions),
+            glow::STATIC_DRAW,
+        );
+        gl.bind_buffer(glow::ARRAY_BUFFER, None);
+
+        let buffer_infos = vec![vao::BufferInfo {
+            location: A_POS_LOCATION,
+            vector_size: 2,
+            data_type: glow::FLOAT,
+            normalized: false,
+            stride: 0,
+            offset: 0,
+        }];
+        let vao = vao::VertexArrayObject::new(gl, vbo, buffer_infos);
+        Ok(Self { vbo, vao })
+    }
+
+    pub(crate) unsafe fn bind(&self, gl: &glow::Context) {
+        self.vao.bind(gl);
+    }
+
+    pub(crate) unsafe fn unbind(&self, gl: &glow::Context) {
+        self.vao.unbind(gl);
+    }
+
+    pub(crate) unsafe fn destroy(&self, gl: &glow::Context) {
+        self.vao.destroy(gl);
+        gl.delete_buffer(self.vbo);
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// A color texture with a framebuffer for rendering into it.
+pub(crate) struct TextureTarget {
+    pub fbo: glow::Framebuffer,
+    pub texture: glow::Texture,
+}
+
+impl TextureTarget {
+    pub(crate) unsafe fn new(gl: &glow::Context, texture: glow::Texture) -> Self {
+        let fbo = gl.create_framebuffer().unwrap();
+        gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
+        gl.framebuffer_texture_2d(
+            glow::FRAMEBUFFER,
+            glow::COLOR_ATTACHMENT0,
+            glow::TEXTURE_2D,
+            Some(texture),
+            0,
+        );
+        let status = gl.check_framebuffer_status(glow::FRAMEBUFFER);
+        if status != glow::FRAMEBUFFER_COMPLETE {
+            log::error!("Offscreen framebuffer is incomplete: 0x{status:X}");
+        }
+        Self { fbo, texture }
+    }
+
+    pub(crate) unsafe fn destroy(&self, gl: &glow::Context) {
+        gl.delete_framebuffer(self.fbo);
+        gl.delete_texture(self.texture);
+    }
+}
+
+/// The framebuffers egui is painted into before being resolved,
+/// post-processed and shown.
+pub(crate) struct IntermediateTarget {
+    pub size: [u32; 2],
+    pub samples: u8,
+
+    /// Multisampled render target, if MSAA is on.
+    msaa: Option<(glow::Framebuffer, glow::Renderbuffer)>,
+
+    /// MSAA is resolved into this, and post-processing ping-pongs between these two.
+    pub color: [TextureTarget; 2],
+}
+
+impl IntermediateTarget {
+    pub(crate) unsafe fn new(
+        gl: &glow::Context,
+        size: [u32; 2],
+        samples: u8,
+        internal_format: u32,
+        create_texture: impl Fn([u32; 2]) -> glow::Texture,
+    ) -> Self {
+        let msaa = (samples > 1).then(|| {
+            let renderbuffer = gl.create_renderbuffer().unwrap();
+            gl.bind_renderbuffer(glow::RENDERBUFFER, Some(renderbuffer));
+            gl.renderbuffer_storage_multisample(
+                glow::RENDERBUFFER,
+                samples as i32,
+                internal_format,
+                size[0] as i32,
+                size[1] as i32,
+            );
+            gl.bind_renderbuffer(glow::RENDERBUFFER, None);
+
+            let fbo = gl.create_framebuffer().unwrap();
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
+            gl.framebuffer_renderbuffer(
+                glow::FRAMEBUFFER,
+                glow::COLOR_ATTACHMENT0,
+                glow::RENDERBUFFER,
+                Some(renderbuffer),
+            );
+            let status = gl.check_framebuffer_status(glow::FRAMEBUFFER);
+            if status != glow::FRAMEBUFFER_COMPLETE {
+                log::error!("Multisampled framebuffer is incomplete: 0x{status:X}");
+            }
+            (fbo, renderbuffer)
+        });
+
+        let color = [
+            TextureTarget::new(gl, create_texture(size)),
+            TextureTarget::new(gl, create_texture(size)),
+        ];
+        check_for_gl_error!(gl, "IntermediateTarget::new");
+
+        Self {
+            size,
+            samples,
+            msaa,
+            color,
+        }
+    }
+
+    /// The framebuffer egui should be painted into.
+    pub(crate) fn paint_fbo(&self) -> glow::Framebuffer {
+        self.msaa.map_or(self.color[0].fbo, |(fbo, _)| fbo)
+    }
+
+    /// Resolve MSAA (if any) into `color[0]`.
+    pub(crate) unsafe fn resolve(&self, gl: &glow::Context) {
+        if let Some((msaa_fbo, _)) = self.msaa {
+            let [w, h] = self.size;
+            gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(msaa_fbo));
+            gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, Some(self.color[0].fbo));
+            gl.blit_framebuffer(
+                0,
+                0,
+                w as i32,
+                h as i32,
+                0,
+                0,
+                w as i32,
+                h as i32,
+                glow::COLOR_BUFFER_BIT,
+                glow::NEAREST,
+            );
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            check_for_gl_error!(gl, "resolve MSAA");
+        }
+    }
+
+    pub(crate) unsafe fn destroy(&self, gl: &glow::Context) {
+        if let Some((fbo, renderbuffer)) = self.msaa {
+            gl.delete_framebuffer(fbo);
+            gl.delete_renderbuffer(renderbuffer);
+        }
+        for target in &self.color {
+            target.destroy(gl);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(matrix: [[f32; 3]; 3], rgb: [f32; 3]) -> [f32; 3] {
+        matrix.map(|row| row.iter().zip(rgb).map(|(m, c)| m * c).sum())
+    }
+
+    #[test]
+    fn severity_zero_is_identity() {
+        for simulation in ColorblindSimulation::ALL {
+            assert_eq!(
+                simulation.matrix(0.0),
+                [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+                "{simulation}"
+            );
+        }
+    }
+
+    #[test]
+    fn grays_stay_gray() {
+        for simulation in ColorblindSimulation::ALL {
+            for severity in [0.0, 0.5, 1.0] {
+                for gray in [0.0, 0.5, 1.0] {
+                    let [r, g, b] = apply(simulation.matrix(severity), [gray; 3]);
+                    for c in [r, g, b] {
+                        assert!(
+                            (c - gray).abs() < 1e-3,
+                            "{simulation} {severity}: {c} != {gray}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn full_severity_uses_the_published_matrices() {
+        assert_eq!(
+            ColorblindSimulation::Protanopia.matrix(1.0),
+            ColorblindSimulation::Protanopia.full_matrix()
+        );
+        // Protanopes can't tell red from green:
+        let red = apply(
+            ColorblindSimulation::Protanopia.matrix(1.0),
+            [1.0, 0.0, 0.0],
+        );
+        assert!(red[0] < 0.2, "{red:?}");
+    }
+}
@@ -0,0 +1,394 @@
+#![allow(unsafe_code)]
+
+//! Caching of linked shader programs with `glGetProgramBinary`/`glProgramBinary`.
+
+use std::{
+    ffi::{c_void, CStr},
+    path::PathBuf,
+};
+
+use glow::HasContext as _;
+
+use crate::misc_util::compile_and_link;
+
+/// Where [`ProgramCache`] keeps program binaries between runs.
+pub trait ProgramCacheStorage {
+    /// The data stored under `key`, if any.
+    fn load(&self, key: &str) -> Option<Vec<u8>>;
+
+    /// Store `data` under `key`, replacing anything already there.
+    fn store(&mut self, key: &str, data: &[u8]);
+}
+
+impl<T: ProgramCacheStorage + ?Sized> ProgramCacheStorage for Box<T> {
+    fn load(&self, key: &str) -> Option<Vec<u8>> {
+        (**self).load(key)
+    }
+
+    fn store(&mut self, key: &str, data: &[u8]) {
+        (**self).store(key, data);
+    }
+}
+
+/// Stores each program binary as a file in a directory, which is created when needed.
+#[derive(Clone, Debug)]
+pub struct DirectoryStorage {
+    directory: PathBuf,
+}
+
+impl DirectoryStorage {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.directory.join(format!("{key}.bin"))
+    }
+}
+
+impl ProgramCacheStorage for DirectoryStorage {
+    fn load(&self, key: &str) -> Option<Vec<u8>> {
+        std::fs::read(self.path(key)).ok()
+    }
+
+    fn store(&mut self, key: &str, data: &[u8]) {
+        let result = std::fs::create_dir_all(&self.directory)
+            .and_then(|()| std::fs::write(self.path(key), data));
+        if let Err(err) = result {
+            log::warn!(
+                "Failed to write program binary to {:?}: {err}",
+                self.directory
+            );
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+type GetProgramiv = unsafe extern "system" fn(program: u32, pname: u32, params: *mut i32);
+type GetProgramBinary = unsafe extern "system" fn(
+    program: u32,
+    buf_size: i32,
+    length: *mut i32,
+    binary_format: *mut u32,
+    binary: *mut c_void,
+);
+type ProgramBinary =
+    unsafe extern "system" fn(program: u32, binary_format: u32, binary: *const c_void, length: i32);
+type ProgramParameteri = unsafe extern "system" fn(program: u32, pname: u32, value: i32);
+
+/// Makes [`crate::Painter::new_with_program_cache`] skip compiling egui's shaders when it has
+/// linked them before with the same driver.
+///
+/// The linked program is saved with `glGetProgramBinary` and loaded with `glProgramBinary`.
+/// Binaries are keyed by the OpenGL vendor, renderer and version, and by the shader sources,
+/// so a driver update or a new egui version simply misses the cache.
+/// Drivers may still reject a binary, in which case the shaders are compiled as usual
+/// and the binary is replaced.
+pub struct ProgramCache<'a> {
+    get_programiv: GetProgramiv,
+    get_program_binary: GetProgramBinary,
+    program_binary: ProgramBinary,
+    program_parameteri: Option<ProgramParameteri>,
+    storage: Box<dyn ProgramCacheStorage + 'a>,
+}
+
+impl<'a> ProgramCache<'a> {
+    /// Load the program binary functions with `get_proc_address`,
+    /// the same function you created the [`glow::Context`] with.
+    ///
+    /// Returns `None` if the context doesn't support program binaries
+    /// (OpenGL 4.1, OpenGL ES 3.0, or `GL_ARB_get_program_binary`/`GL_OES_get_program_binary`)
+    /// or doesn't offer any binary format.
+    pub fn new(
+        gl: &glow::Context,
+        mut get_proc_address: impl FnMut(&CStr) -> *const c_void,
+        storage: impl ProgramCacheStorage + 'a,
+    ) -> Option<Self> {
+        let version = gl.version();
+        let supported = if version.is_embedded {
+            version.major >= 3
+        } else {
+            (version.major, version.minor) >= (4, 1)
+        } || gl
+            .supported_extensions()
+            .iter()
+            .any(|extension| extension.ends_with("get_program_binary"));
+        if !supported {
+            log::debug!("Program binaries are not supported by this OpenGL context");
+            return None;
+        }
+        if unsafe { gl.get_parameter_i32(glow::NUM_PROGRAM_BINARY_FORMATS) } <= 0 {
+            log::debug!("The OpenGL driver offers no program binary formats");
+            return None;
+        }
+
+        let mut load = |names: &[&[u8]]| {
+            names
+                .iter()
+                .map(|name| get_proc_address(CStr::from_bytes_with_nul(name).unwrap()))
+                .find(|ptr| !ptr.is_null())
+        };
+        let get_programiv = load(&[b"glGetProgramiv\0"])?;
+        let get_program_binary = load(&[b"glGetProgramBinary\0", b"glGetProgramBinaryOES\0"])?;
+        let program_binary = load(&[b"glProgramBinary\0", b"glProgramBinaryOES\0"])?;
+        let program_parameteri = load(&[b"glProgramParameteri\0"]);
+
+        // SAFETY: these are the signatures from the OpenGL specification.
+        unsafe {
+            use std::mem::transmute;
+            Some(Self {
+                get_programiv: transmute::<*const c_void, GetProgramiv>(get_programiv),
+                get_program_binary: transmute::<*const c_void, GetProgramBinary>(
+                    get_program_binary,
+                ),
+                program_binary: transmute::<*const c_void, ProgramBinary>(program_binary),
+                program_parameteri: program_parameteri
+                    .map(|ptr| transmute::<*const c_void, ProgramParameteri>(ptr)),
+                storage: Box::new(storage),
+            })
+        }
+    }
+
+    /// Load the program from the cache, or else compile and link it and add it to the cache.
+    pub(crate) unsafe fn link_program(
+        &mut self,
+        gl: &glow::Context,
+        sources: &[(u32, &str)],
+    ) -> Result<glow::Program, String> {
+        crate::profile_function!();
+        let key = cache_key(gl, sources);
+
+        if let Some(data) = self.storage.load(&key) {
+            if let Some(program) = self.load_binary(gl, &data) {
+                log::debug!("Loaded program binary {key}");
+                return Ok(program);
+            }
+            log::debug!("The driver rejected program binary {key}; compiling instead");
+        }
+
+        let program = compile_and_link(gl, sources, |program| {
+            if let Some(program_parameteri) = self.program_parameteri {
+                program_parameteri(
+                    program.0.get(),
+                    glow::PROGRAM_BINARY_RETRIEVABLE_HINT,
+                    glow::TRUE as i32,
+                );
+            }
+        })?;
+        self.store_binary(program, &key);
+        Ok(program)
+    }
+
+    unsafe fn load_binary(&self, gl: &glow::Context, data: &[u8]) -> Option<glow::Program> {
+        if data.len() <= 4 {
+            return None;
+        }
+        let (format, binary) = data.split_at(4);
+        let format = u32::from_le_bytes(format.try_into().ok()?);
+
+        let program = gl.create_program().ok()?;
+        (self.program_binary)(
+            program.0.get(),
+            format,
+            binary.as_ptr().cast(),
+            binary.len() as i32,
+        );
+        if gl.get_program_link_status(program) {
+            Some(program)
+        } else {
+            // Unknown formats are reported as `GL_INVALID_ENUM`, which we don't want to leak:
+            gl.get_error();
+            gl.delete_program(program);
+            None
+        }
+    }
+
+    unsafe fn store_binary(&mut self, program: glow::Program, key: &str) {
+        let mut length = 0;
+        (self.get_programiv)(program.0.get(), glow::PROGRAM_BINARY_LENGTH, &mut length);
+        if length <= 0 {
+            return;
+        }
+
+        // The first four bytes are the binary format:
+        let mut data = vec![0_u8; 4 + length as usize];
+        let mut format = 0;
+        let mut written = 0;
+        (self.get_program_binary)(
+            program.0.get(),
+            length,
+            &mut written,
+            &mut format,
+            data[4..].as_mut_ptr().cast(),
+        );
+        if written <= 0 {
+            return;
+        }
+        data.truncate(4 + written as usize);
+        data[..4].copy_from_slice(&format.to_le_bytes());
+        self.storage.store(key, &data);
+        log::debug!("Stored program binary {key} ({written} bytes)");
+    }
+}
+
+/// A key that changes with the driver and with the shader sources.
+fn cache_key(gl: &glow::Context, sources: &[(u32, &str)]) -> String {
+    let [vendor, renderer, version] = [glow::VENDOR, glow::RENDERER, glow::VERSION]
+        .map(|parameter| unsafe { gl.get_parameter_string(parameter) });
+    cache_key_for(&[&vendor, &renderer, &version], sources)
+}
+
+/// [`cache_key`] for the given vendor, renderer and version strings.
+fn cache_key_for(driver: &[&str; 3], sources: &[(u32, &str)]) -> String {
+    // FNV-1a, which unlike `DefaultHasher` is the same across Rust versions.
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut write = |bytes: &[u8]| {
+        for &byte in bytes.iter().chain(&[0]) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    for string in driver {
+        write(string.as_bytes());
+    }
+    for (shader_type, source) in sources {
+        write(&shader_type.to_le_bytes());
+        write(source.as_bytes());
+    }
+    format!("egui_glow_program_{hash:016x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    use super::*;
+
+    const DRIVER: [&str; 3] = ["Mesa", "llvmpipe", "4.5 (Core Profile) Mesa 23.1"];
+
+    #[test]
+    fn cache_key_is_stable() {
+        // Keys must not change between Rust versions, or every cache would miss:
+        assert_eq!(
+            cache_key_for(&DRIVER, &[(glow::VERTEX_SHADER, "void main() {}")]),
+            cache_key_for(&DRIVER, &[(glow::VERTEX_SHADER, "void main() {}")]),
+        );
+        assert_eq!(
+            cache_key_for(&["", "", ""], &[]),
+            format!("egui_glow_program_{:016x}", {
+                // FNV-1a of three terminating zero bytes:
+                let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+                for _ in 0..3 {
+                    hash = hash.wrapping_mul(0x0100_0000_01b3);
+                }
+                hash
+            })
+        );
+    }
+
+    #[test]
+    fn cache_key_changes_with_driver_and_sources() {
+        let sources = [
+            (glow::VERTEX_SHADER, "vertex"),
+            (glow::FRAGMENT_SHADER, "fragment"),
+        ];
+        let key = cache_key_for(&DRIVER, &sources);
+        let updated_driver = ["Mesa", "llvmpipe", "4.5 (Core Profile) Mesa 23.2"];
+        assert_ne!(key, cache_key_for(&updated_driver, &sources));
+        assert_ne!(
+            key,
+            cache_key_for(
+                &DRIVER,
+                &[
+                    (glow::VERTEX_SHADER, "vertex"),
+                    (glow::FRAGMENT_SHADER, "fragment2"),
+                ]
+            )
+        );
+        assert_ne!(
+            key,
+            cache_key_for(
+                &DRIVER,
+                &[
+                    (glow::FRAGMENT_SHADER, "vertex"),
+                    (glow::VERTEX_SHADER, "fragment"),
+                ]
+            )
+        );
+        // Moving text from one string to the next is a different key:
+        assert_ne!(
+            cache_key_for(&["ab", "c", ""], &[]),
+            cache_key_for(&["a", "bc", ""], &[])
+        );
+    }
+
+    #[test]
+    fn directory_storage_round_trip() {
+        let directory = std::env::temp_dir().join(format!(
+            "egui_glow_program_cache_test_{}",
+            std::process::id()
+        ));
+        let mut storage = DirectoryStorage::new(&directory);
+        assert_eq!(storage.load("key"), None);
+        storage.store("key", &[0, 1, 2, 255]);
+        assert_eq!(storage.load("key"), Some(vec![0, 1, 2, 255]));
+        storage.store("key", &[3]);
+        assert_eq!(storage.load("key"), Some(vec![3]));
+        std::fs::remove_dir_all(directory).ok();
+    }
+
+    /// Counts what the cache loads, to see whether it hit.
+    #[derive(Clone, Default)]
+    struct MemoryStorage {
+        binaries: Rc<RefCell<HashMap<String, Vec<u8>>>>,
+        hits: Rc<RefCell<usize>>,
+    }
+
+    impl ProgramCacheStorage for MemoryStorage {
+        fn load(&self, key: &str) -> Option<Vec<u8>> {
+            let binary = self.binaries.borrow().get(key).cloned();
+            *self.hits.borrow_mut() += binary.is_some() as usize;
+            binary
+        }
+
+        fn store(&mut self, key: &str, data: &[u8]) {
+            self.binaries
+                .borrow_mut()
+                .insert(key.to_owned(), data.to_vec());
+        }
+    }
+
+    #[test]
+    fn second_painter_loads_the_program_binary() {
+        let Some(headless) = crate::headless::gl() else {
+            return;
+        };
+        let storage = MemoryStorage::default();
+        let Some(mut cache) = ProgramCache::new(
+            &headless.gl,
+            |symbol| headless.get_proc_address(symbol),
+            storage.clone(),
+        ) else {
+            eprintln!("Skipping: the context doesn't support program binaries");
+            return;
+        };
+        for _ in 0..2 {
+            let mut painter = crate::Painter::new_with_program_cache(
+                headless.gl.clone(),
+                "",
+                None,
+                Default::default(),
+                &mut cache,
+            )
+            .unwrap();
+            painter.destroy();
+        }
+        assert_eq!(storage.binaries.borrow().len(), 1);
+        assert_eq!(*storage.hits.borrow(), 1);
+    }
+}
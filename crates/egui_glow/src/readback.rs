@@ -0,0 +1,274 @@
+#![allow(unsafe_code)]
+
+//! Reading back the screen, for [`crate::Painter::read_screen_rgba_async`] and friends.
+
+use glow::HasContext as _;
+
+use crate::check_for_gl_error;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) type ReadbackFn = Box<dyn FnOnce(egui::ColorImage) + Send>;
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) type ReadbackFn = Box<dyn FnOnce(egui::ColorImage)>;
+
+/// A region of the framebuffer in pixels: `[x, y, width, height]`, with `y` counted from the bottom.
+pub(crate) type PixelRegion = [u32; 4];
+
+/// Convert a region in points, with the origin at the top left, to pixels in the framebuffer.
+///
+/// The region is clamped to the screen.
+pub(crate) fn region_in_pixels(
+    [width_px, height_px]: [u32; 2],
+    pixels_per_point: f32,
+    region: egui::Rect,
+) -> PixelRegion {
+    let to_pixels =
+        |points: f32, max: u32| (points * pixels_per_point).round().clamp(0.0, max as f32) as u32;
+    let min_x = to_pixels(region.min.x, width_px);
+    let max_x = to_pixels(region.max.x, width_px).max(min_x);
+    let min_y = to_pixels(region.min.y, height_px);
+    let max_y = to_pixels(region.max.y, height_px).max(min_y);
+    [min_x, height_px - max_y, max_x - min_x, max_y - min_y]
+}
+
+/// Turn bottom-up RGBA rows, as OpenGL returns them, into an image with the first row at the top.
+fn image_from_gl_rows([width, height]: [u32; 2], pixels: &[u8]) -> egui::ColorImage {
+    let mut flipped = Vec::with_capacity((width * height) as usize);
+    if width > 0 {
+        for row in pixels.chunks_exact((width * 4) as usize).rev() {
+            flipped.extend_from_slice(bytemuck::cast_slice(row));
+        }
+    }
+    egui::ColorImage {
+        size: [width as usize, height as usize],
+        pixels: flipped,
+    }
+}
+
+/// Read a region of the currently bound framebuffer, blocking until the GPU is done.
+pub(crate) unsafe fn read_pixels_rgba(
+    gl: &glow::Context,
+    [x, y, width, height]: PixelRegion,
+) -> egui::ColorImage {
+    let mut pixels = vec![0_u8; (width * height * 4) as usize];
+    gl.read_pixels(
+        x as _,
+        y as _,
+        width as _,
+        height as _,
+        glow::RGBA,
+        glow::UNSIGNED_BYTE,
+        glow::PixelPackData::Slice(&mut pixels),
+    );
+    check_for_gl_error!(gl, "read_pixels");
+    image_from_gl_rows([width, height], &pixels)
+}
+
+enum Readback {
+    /// Copied into a pixel buffer, which can be read once the fence is signaled.
+    InFlight {
+        buffer: glow::Buffer,
+        fence: glow::Fence,
+        size: [u32; 2],
+    },
+
+    /// Read right away, because the context can't do it asynchronously.
+    Done(egui::ColorImage),
+}
+
+/// Readbacks whose callbacks are waiting for the GPU.
+pub(crate) struct Readbacks {
+    /// Pixel buffers, fences and buffer mapping: OpenGL 3.2, OpenGL ES 3.0 or WebGL2.
+    supports_async: bool,
+    pending: Vec<(Readback, ReadbackFn)>,
+}
+
+impl Readbacks {
+    pub(crate) fn new(gl: &glow::Context, is_webgl_1: bool) -> Self {
+        let version = gl.version();
+        let supports_async = !is_webgl_1
+            && if version.is_embedded {
+                version.major >= 3
+            } else {
+                (version.major, version.minor) >= (3, 2)
+                    || (version.major >= 3 && gl.supported_extensions().contains("GL_ARB_sync"))
+            };
+        Self {
+            supports_async,
+            pending: Vec::new(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Start reading `region` of the currently bound framebuffer.
+    ///
+    /// `callback` is called by a later [`Self::poll`] once the pixels are available.
+    pub(crate) unsafe fn request(
+        &mut self,
+        gl: &glow::Context,
+        region @ [x, y, width, height]: PixelRegion,
+        callback: ReadbackFn,
+    ) {
+        crate::profile_function!();
+        let readback = if self.supports_async && width > 0 && height > 0 {
+            let buffer = gl.create_buffer().expect("failed to create pixel buffer");
+            gl.bind_buffer(glow::PIXEL_PACK_BUFFER, Some(buffer));
+            gl.buffer_data_size(
+                glow::PIXEL_PACK_BUFFER,
+                (width * height * 4) as i32,
+                glow::STREAM_READ,
+            );
+            gl.read_pixels(
+                x as _,
+                y as _,
+                width as _,
+                height as _,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelPackData::BufferOffset(0),
+            );
+            gl.bind_buffer(glow::PIXEL_PACK_BUFFER, None);
+            let fence = gl
+                .fence_sync(glow::SYNC_GPU_COMMANDS_COMPLETE, 0)
+                .expect("failed to create fence");
+            // Make sure the fence is submitted, so it will eventually be signaled:
+            gl.flush();
+            check_for_gl_error!(gl, "readback");
+            Readback::InFlight {
+                buffer,
+                fence,
+                size: [width, height],
+            }
+        } else {
+            Readback::Done(read_pixels_rgba(gl, region))
+        };
+        self.pending.push((readback, callback));
+    }
+
+    /// Call the callbacks of the readbacks that the GPU is done with.
+    pub(crate) unsafe fn poll(&mut self, gl: &glow::Context) {
+        if self.pending.is_empty() {
+            return;
+        }
+        crate::profile_function!();
+
+        let mut finished = Vec::new();
+        for (readback, callback) in std::mem::take(&mut self.pending) {
+            match readback {
+                Readback::InFlight { fence, .. } if gl.get_sync_status(fence) != glow::SIGNALED => {
+                    self.pending.push((readback, callback));
+                }
+                Readback::InFlight {
+                    buffer,
+                    fence,
+                    size,
+                } => {
+                    let pixels = read_buffer(gl, buffer, (size[0] * size[1] * 4) as usize);
+                    gl.delete_buffer(buffer);
+                    gl.delete_sync(fence);
+                    finished.push((image_from_gl_rows(size, &pixels), callback));
+                }
+                Readback::Done(image) => finished.push((image, callback)),
+            }
+        }
+        check_for_gl_error!(gl, "readback");
+
+        for (image, callback) in finished {
+            callback(image);
+        }
+    }
+
+    /// Drop the pending readbacks without calling their callbacks.
+    pub(crate) unsafe fn destroy(&self, gl: &glow::Context) {
+        for (readback, _) in &self.pending {
+            if let Readback::InFlight { buffer, fence, .. } = *readback {
+                gl.delete_buffer(buffer);
+                gl.delete_sync(fence);
+            }
+        }
+    }
+}
+
+/// The contents of a pixel pack buffer.
+unsafe fn read_buffer(gl: &glow::Context, buffer: glow::Buffer, len: usize) -> Vec<u8> {
+    gl.bind_buffer(glow::PIXEL_PACK_BUFFER, Some(buffer));
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let pixels = {
+        // `glGetBufferSubData` is not available on OpenGL ES:
+        let mapped =
+            gl.map_buffer_range(glow::PIXEL_PACK_BUFFER, 0, len as i32, glow::MAP_READ_BIT);
+        if mapped.is_null() {
+            log::warn!("Failed to map the screen readback buffer");
+            vec![0; len]
+        } else {
+            let pixels = std::slice::from_raw_parts(mapped, len).to_vec();
+            gl.unmap_buffer(glow::PIXEL_PACK_BUFFER);
+            pixels
+        }
+    };
+
+    #[cfg(target_arch = "wasm32")]
+    let pixels = {
+        let mut pixels = vec![0; len];
+        gl.get_buffer_sub_data(glow::PIXEL_PACK_BUFFER, 0, &mut pixels);
+        pixels
+    };
+
+    gl.bind_buffer(glow::PIXEL_PACK_BUFFER, None);
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::{pos2, Rect};
+
+    #[test]
+    fn region_is_flipped_to_count_from_the_bottom() {
+        let region = Rect::from_min_max(pos2(10.0, 20.0), pos2(30.0, 25.0));
+        assert_eq!(region_in_pixels([100, 50], 1.0, region), [10, 25, 20, 5]);
+    }
+
+    #[test]
+    fn region_is_scaled_and_rounded_to_pixels() {
+        let region = Rect::from_min_max(pos2(10.2, 20.0), pos2(30.0, 24.9));
+        assert_eq!(region_in_pixels([200, 100], 2.0, region), [20, 50, 40, 10]);
+    }
+
+    #[test]
+    fn region_is_clamped_to_the_screen() {
+        let region = Rect::from_min_max(pos2(-10.0, -10.0), pos2(200.0, 30.0));
+        assert_eq!(region_in_pixels([100, 50], 1.0, region), [0, 20, 100, 30]);
+
+        let outside = Rect::from_min_max(pos2(150.0, 60.0), pos2(200.0, 80.0));
+        assert_eq!(region_in_pixels([100, 50], 1.0, outside), [100, 0, 0, 0]);
+
+        assert_eq!(
+            region_in_pixels([100, 50], 1.0, Rect::NOTHING),
+            [100, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn gl_rows_are_flipped_to_the_top() {
+        let bottom = [1, 2, 3, 4, 5, 6, 7, 8];
+        let top = [9, 10, 11, 12, 13, 14, 15, 16];
+        let image = image_from_gl_rows([2, 2], &[bottom, top].concat());
+        assert_eq!(image.size, [2, 2]);
+        assert_eq!(
+            image.pixels,
+            [
+                egui::Color32::from_rgba_premultiplied(9, 10, 11, 12),
+                egui::Color32::from_rgba_premultiplied(13, 14, 15, 16),
+                egui::Color32::from_rgba_premultiplied(1, 2, 3, 4),
+                egui::Color32::from_rgba_premultiplied(5, 6, 7, 8),
+            ]
+        );
+        assert_eq!(image_from_gl_rows([0, 3], &[]).pixels, []);
+    }
+}
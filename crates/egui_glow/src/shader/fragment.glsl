@@ -4,10 +4,31 @@
 
 uniform sampler2D u_sampler;
 
+// What `u_sampler` holds:
+#define TEXTURE_FORMAT_RGBA 0
+// A single coverage channel, i.e. the font atlas.
+#define TEXTURE_FORMAT_COVERAGE 1
+// The luma plane, with interleaved chroma in `u_sampler_u`.
+#define TEXTURE_FORMAT_NV12 2
+// The luma plane, with chroma in `u_sampler_u` and `u_sampler_v`.
+#define TEXTURE_FORMAT_I420 3
+uniform int u_texture_format;
+
+uniform sampler2D u_sampler_u;
+uniform sampler2D u_sampler_v;
+// rgb = u_yuv_matrix * (yuv - u_yuv_offset)
+uniform mat3 u_yuv_matrix;
+uniform vec3 u_yuv_offset;
+
 #if NEW_SHADER_INTERFACE
     in vec4 v_rgba_in_gamma;
     in vec2 v_tc;
-    out vec4 f_color;
+    #if __VERSION__ >= 300
+        // GLSL 3.30 core and ES 3.00 and later can pin the output:
+        layout(location = 0) out vec4 f_color;
+    #else
+        out vec4 f_color;
+    #endif
     // a dirty hack applied to support webGL2
     #define gl_FragColor f_color
     #define texture2D texture
@@ -29,13 +50,48 @@ vec4 srgba_gamma_from_linear(vec4 rgba) {
     return vec4(srgb_gamma_from_linear(rgba.rgb), rgba.a);
 }
 
+#if LINEAR_OUTPUT
+// 0-1 linear  from  0-1 sRGB gamma
+vec3 linear_from_srgb_gamma(vec3 srgb) {
+    bvec3 cutoff = lessThan(srgb, vec3(0.04045));
+    vec3 lower = srgb / vec3(12.92);
+    vec3 higher = pow((srgb + vec3(0.055)) / vec3(1.055), vec3(2.4));
+    return mix(higher, lower, vec3(cutoff));
+}
+
+// 0-1 linear  from  0-1 sRGBA gamma
+vec4 linear_from_srgba_gamma(vec4 rgba) {
+    return vec4(linear_from_srgb_gamma(rgba.rgb), rgba.a);
+}
+#endif
+
 void main() {
+    vec4 texel = texture2D(u_sampler, v_tc);
+    vec4 texture_in_gamma;
+    if (u_texture_format == TEXTURE_FORMAT_COVERAGE) {
+        // R8 or LUMINANCE: premultiplied white with the coverage (in gamma) in the red channel.
+        texture_in_gamma = texel.rrrr;
+    } else if (u_texture_format == TEXTURE_FORMAT_NV12 || u_texture_format == TEXTURE_FORMAT_I420) {
+        vec3 yuv = vec3(texel.r, texture2D(u_sampler_u, v_tc).UV_CHANNELS);
+        if (u_texture_format == TEXTURE_FORMAT_I420) {
+            yuv.z = texture2D(u_sampler_v, v_tc).r;
+        }
+        // Video is gamma-encoded, just like egui colors:
+        texture_in_gamma = vec4(clamp(u_yuv_matrix * (yuv - u_yuv_offset), 0.0, 1.0), 1.0);
+    } else {
 #if SRGB_TEXTURES
-    vec4 texture_in_gamma = srgba_gamma_from_linear(texture2D(u_sampler, v_tc));
+        texture_in_gamma = srgba_gamma_from_linear(texel);
 #else
-    vec4 texture_in_gamma = texture2D(u_sampler, v_tc);
+        texture_in_gamma = texel;
 #endif
+    }
 
     // We multiply the colors in gamma space, because that's the only way to get text to look right.
+#if LINEAR_OUTPUT
+    // Blending then happens in linear space.
+    // egui premultiplies alpha in linear space, so we can convert the premultiplied color directly.
+    gl_FragColor = linear_from_srgba_gamma(v_rgba_in_gamma * texture_in_gamma);
+#else
     gl_FragColor = v_rgba_in_gamma * texture_in_gamma;
+#endif
 }
@@ -0,0 +1,44 @@
+#ifdef GL_ES
+    precision mediump float;
+#endif
+
+uniform sampler2D u_sampler;
+uniform vec2 u_texel_size; // size of one pixel in uv units
+
+#if NEW_SHADER_INTERFACE
+    in vec2 v_tc;
+    #if __VERSION__ >= 300
+        // GLSL 3.30 core and ES 3.00 and later can pin the output:
+        layout(location = 0) out vec4 f_color;
+    #else
+        out vec4 f_color;
+    #endif
+    // a dirty hack applied to support webGL2
+    #define gl_FragColor f_color
+    #define texture2D texture
+#else
+    varying vec2 v_tc;
+#endif
+
+// 0-1 sRGB gamma  from  0-1 linear
+vec3 srgb_gamma_from_linear(vec3 rgb) {
+    bvec3 cutoff = lessThan(rgb, vec3(0.0031308));
+    vec3 lower = rgb * vec3(12.92);
+    vec3 higher = vec3(1.055) * pow(rgb, vec3(1.0 / 2.4)) - vec3(0.055);
+    return mix(higher, lower, vec3(cutoff));
+}
+
+// 0-1 linear  from  0-1 sRGB gamma
+vec3 linear_from_srgb_gamma(vec3 srgb) {
+    bvec3 cutoff = lessThan(srgb, vec3(0.04045));
+    vec3 lower = srgb / vec3(12.92);
+    vec3 higher = pow((srgb + vec3(0.055)) / vec3(1.055), vec3(2.4));
+    return mix(higher, lower, vec3(cutoff));
+}
+
+// Defined by the user of the post-process pass:
+vec4 post_process(vec2 uv);
+
+void main() {
+    gl_FragColor = post_process(v_tc);
+}
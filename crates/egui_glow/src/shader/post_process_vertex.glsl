@@ -0,0 +1,19 @@
+#if NEW_SHADER_INTERFACE
+    #define I in
+    #define O out
+#else
+    #define I attribute
+    #define O varying
+#endif
+
+#ifdef GL_ES
+    precision mediump float;
+#endif
+
+I vec2 a_pos;
+O vec2 v_tc;
+
+void main() {
+    gl_Position = vec4(a_pos, 0.0, 1.0);
+    v_tc = a_pos * 0.5 + 0.5;
+}
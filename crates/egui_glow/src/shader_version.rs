@@ -1,6 +1,6 @@
 #![allow(unsafe_code)]
 
-use std::convert::TryInto;
+use std::str::FromStr;
 
 /// Helper for parsing and interpreting the OpenGL shader version.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -11,19 +11,63 @@ pub enum ShaderVersion {
     /// OpenGL 1.4 or later
     Gl140,
 
+    /// GLSL 3.30 core, e.g. OpenGL 3.3 core profile. Never detected, see [`Self::get`].
+    Gl330,
+
+    /// GLSL 4.10 core, e.g. the OpenGL 4.1 core profile on macOS. Never detected, see [`Self::get`].
+    Gl410,
+
     /// e.g. WebGL1
     Es100,
 
     /// e.g. WebGL2
     Es300,
+
+    /// OpenGL ES 3.1 or later. Never detected, see [`Self::get`].
+    Es310,
 }
 
 impl ShaderVersion {
+    /// Environment variable that overrides the detected shader version, e.g. `EGUI_GLOW_SHADER_VERSION="300 es"`.
+    ///
+    /// See [`Self::from_str`] for the accepted values.
+    pub const ENV_VAR: &'static str = "EGUI_GLOW_SHADER_VERSION";
+
+    /// The shader version set with [`Self::ENV_VAR`], if any.
+    ///
+    /// Invalid values are ignored with a warning.
+    pub fn from_env() -> Option<Self> {
+        let value = std::env::var(Self::ENV_VAR).ok()?;
+        match value.parse() {
+            Ok(shader_version) => {
+                log::debug!("Shader version {shader_version:?} set by {}", Self::ENV_VAR);
+                Some(shader_version)
+            }
+            Err(err) => {
+                log::warn!("Ignoring {}: {err}", Self::ENV_VAR);
+                None
+            }
+        }
+    }
+
+    /// The override in [`Self::ENV_VAR`], or else the version detected from the context.
+    ///
+    /// Detection picks [`Self::Gl120`], [`Self::Gl140`], [`Self::Es100`] or [`Self::Es300`],
+    /// like it always has; the newer versions are opt-in, via [`Self::ENV_VAR`]
+    /// or the `shader_version` argument of [`crate::Painter::new`].
+    ///
+    /// If the driver reports a version we can't parse, this falls back to the oldest version,
+    /// [`Self::Gl120`] or [`Self::Es100`].
     pub fn get(gl: &glow::Context) -> Self {
+        Self::from_env().unwrap_or_else(|| Self::detect(gl))
+    }
+
+    /// Like [`Self::get`], but ignoring [`Self::ENV_VAR`].
+    pub(crate) fn detect(gl: &glow::Context) -> Self {
         use glow::HasContext as _;
         let shading_lang_string =
             unsafe { gl.get_parameter_string(glow::SHADING_LANGUAGE_VERSION) };
-        let shader_version = Self::parse(&shading_lang_string);
+        let shader_version = Self::detect_from(&shading_lang_string, gl.version().is_embedded);
         log::debug!(
             "Shader version: {:?} ({:?}).",
             shader_version,
@@ -32,31 +76,65 @@ impl ShaderVersion {
         shader_version
     }
 
-    #[inline]
-    pub(crate) fn parse(glsl_ver: &str) -> Self {
-        let start = glsl_ver.find(|c| char::is_ascii_digit(&c)).unwrap();
+    /// The default version for a `GL_SHADING_LANGUAGE_VERSION` string, see [`Self::get`].
+    fn detect_from(glsl_ver: &str, is_embedded: bool) -> Self {
+        match Self::parse(glsl_ver) {
+            // Switching everyone to the newer versions would change the shaders of existing apps,
+            // including their own shaders that use `get`:
+            Ok(Self::Gl330 | Self::Gl410) => Self::Gl140,
+            Ok(Self::Es310) => Self::Es300,
+            Ok(shader_version) => shader_version,
+            Err(err) => {
+                let fallback = if is_embedded {
+                    Self::Es100
+                } else {
+                    Self::Gl120
+                };
+                log::warn!("{err}. Falling back to {fallback:?}.");
+                fallback
+            }
+        }
+    }
+
+    /// Parse a `GL_SHADING_LANGUAGE_VERSION` string, e.g. `"4.10"` or `"OpenGL ES GLSL ES 3.00"`,
+    /// into the newest version it supports.
+    pub fn parse(glsl_ver: &str) -> Result<Self, String> {
+        let invalid = || format!("Failed to parse the shading language version {glsl_ver:?}");
+        let start = glsl_ver
+            .find(|c| char::is_ascii_digit(&c))
+            .ok_or_else(invalid)?;
         let es = glsl_ver[..start].contains(" ES ");
         let ver = glsl_ver[start..]
             .split_once(' ')
             .map_or(&glsl_ver[start..], |x| x.0);
-        let [maj, min]: [u8; 2] = ver
-            .splitn(3, '.')
+        let (maj, min) = ver.split_once('.').unwrap_or((ver, ""));
+        let maj: u32 = maj.parse().map_err(|_err| invalid())?;
+        // Only the first two digits matter, e.g. "1.00", "4.60.0" or "1.0":
+        let min: u32 = min
+            .chars()
+            .take_while(char::is_ascii_digit)
+            .chain("00".chars())
             .take(2)
-            .map(|x| x.parse().unwrap_or_default())
-            .collect::<Vec<u8>>()
-            .try_into()
-            .unwrap();
-        if es {
-            if maj >= 3 {
+            .collect::<String>()
+            .parse()
+            .unwrap_or_default();
+        Ok(if es {
+            if (maj, min) >= (3, 10) {
+                Self::Es310
+            } else if maj >= 3 {
                 Self::Es300
             } else {
                 Self::Es100
             }
-        } else if maj > 1 || (maj == 1 && min >= 40) {
+        } else if (maj, min) >= (4, 10) {
+            Self::Gl410
+        } else if (maj, min) >= (3, 30) {
+            Self::Gl330
+        } else if (maj, min) >= (1, 40) {
             Self::Gl140
         } else {
             Self::Gl120
-        }
+        })
     }
 
     /// Goes on top of the shader.
@@ -64,8 +142,11 @@ impl ShaderVersion {
         match self {
             Self::Gl120 => "#version 120\n",
             Self::Gl140 => "#version 140\n",
+            Self::Gl330 => "#version 330 core\n",
+            Self::Gl410 => "#version 410 core\n",
             Self::Es100 => "#version 100\n",
             Self::Es300 => "#version 300 es\n",
+            Self::Es310 => "#version 310 es\n",
         }
     }
 
@@ -73,21 +154,43 @@ impl ShaderVersion {
     pub fn is_new_shader_interface(&self) -> bool {
         match self {
             Self::Gl120 | Self::Es100 => false,
-            Self::Es300 | Self::Gl140 => true,
+            Self::Es300 | Self::Es310 | Self::Gl140 | Self::Gl330 | Self::Gl410 => true,
         }
     }
 
     pub fn is_embedded(&self) -> bool {
         match self {
-            Self::Gl120 | Self::Gl140 => false,
-            Self::Es100 | Self::Es300 => true,
+            Self::Gl120 | Self::Gl140 | Self::Gl330 | Self::Gl410 => false,
+            Self::Es100 | Self::Es300 | Self::Es310 => true,
+        }
+    }
+}
+
+impl FromStr for ShaderVersion {
+    type Err = String;
+
+    /// Parse what goes after `#version`, e.g. `"330"`, `"330 core"` or `"300 es"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        let s = s.strip_prefix("#version").unwrap_or(&s).trim();
+        match s {
+            "120" => Ok(Self::Gl120),
+            "140" => Ok(Self::Gl140),
+            "330" | "330 core" => Ok(Self::Gl330),
+            "410" | "410 core" => Ok(Self::Gl410),
+            "100" => Ok(Self::Es100),
+            "300 es" => Ok(Self::Es300),
+            "310 es" => Ok(Self::Es310),
+            _ => Err(format!(
+                "Unknown shader version {s:?}, expected one of 120, 140, 330, 410, 100, 300 es or 310 es"
+            )),
         }
     }
 }
 
 #[test]
 fn test_shader_version() {
-    use ShaderVersion::{Es100, Es300, Gl120, Gl140};
+    use ShaderVersion::{Es100, Es300, Es310, Gl120, Gl140, Gl330, Gl410};
     for (s, v) in [
         ("1.2 OpenGL foo bar", Gl120),
         ("3.0", Gl140),
@@ -98,7 +201,53 @@ fn test_shader_version() {
         ("WebGL GLSL ES 3.00 foo bar", Es300),
         ("WebGL GLSL ES 3.00", Es300),
         ("WebGL GLSL ES 1.0 foo bar", Es100),
+        // Mesa:
+        ("4.60", Gl410),
+        ("4.50", Gl410),
+        ("1.30", Gl120),
+        ("OpenGL ES GLSL ES 3.20", Es310),
+        ("OpenGL ES GLSL ES 3.10", Es310),
+        // ANGLE and browsers:
+        (
+            "OpenGL ES GLSL ES 3.00 (ANGLE 2.1.13739 git hash: 385fb40fd460)",
+            Es300,
+        ),
+        ("OpenGL ES GLSL ES 1.00 (ANGLE 2.1.0.9512a0ef062a)", Es100),
+        ("WebGL GLSL ES 3.00 (OpenGL ES GLSL ES 3.0 Chromium)", Es300),
+        // Apple:
+        ("4.10", Gl410),
+        ("1.20", Gl120),
+        ("OpenGL ES GLSL ES 3.00", Es300),
+        // VirtualBox (VMSVGA):
+        ("3.30", Gl330),
+        ("1.20 Chromium", Gl120),
+        // Others:
+        ("4.60 NVIDIA", Gl410),
+        ("4.60.0", Gl410),
+        ("4", Gl330),
+    ] {
+        assert_eq!(ShaderVersion::parse(s), Ok(v), "{s:?}");
+    }
+
+    for s in ["", "OpenGL ES GLSL ES", "garbage", "unknown ?.??"] {
+        assert!(ShaderVersion::parse(s).is_err(), "{s:?}");
+    }
+
+    for v in [Gl120, Gl140, Gl330, Gl410, Es100, Es300, Es310] {
+        assert_eq!(v.version_declaration().parse(), Ok(v));
+    }
+    assert!("200".parse::<ShaderVersion>().is_err());
+
+    // Detection keeps the versions it picked before the newer ones existed:
+    for (s, v) in [
+        ("4.60", Gl140),
+        ("3.30", Gl140),
+        ("1.30", Gl120),
+        ("OpenGL ES GLSL ES 3.20", Es300),
+        ("OpenGL ES GLSL ES 1.00", Es100),
+        ("garbage", Gl120),
     ] {
-        assert_eq!(ShaderVersion::parse(s), v);
+        assert_eq!(ShaderVersion::detect_from(s, false), v, "{s:?}");
     }
+    assert_eq!(ShaderVersion::detect_from("garbage", true), Es100);
 }
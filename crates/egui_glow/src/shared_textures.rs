@@ -0,0 +1,485 @@
+#![allow(unsafe_code)]
+
+//! Textures shared by several painters, see [`SharedTextures`].
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use egui::mutex::{Mutex, MutexGuard};
+use glow::HasContext as _;
+
+use crate::painter::SamplerOptions;
+use crate::yuv::YuvTexture;
+
+/// The textures of one or more [`crate::Painter`]s, and what the painters need to know about them.
+pub(crate) struct TextureStore {
+    pub textures: HashMap<egui::TextureId, glow::Texture>,
+
+    /// How to sample each texture. Missing entries use the defaults.
+    pub samplers: HashMap<egui::TextureId, (egui::TextureOptions, SamplerOptions)>,
+
+    /// Textures uploaded from [`egui::FontImage`], which only have a coverage channel.
+    pub coverage_textures: HashSet<egui::TextureId>,
+
+    /// Textures registered with [`crate::Painter::register_yuv_texture`]. The luma plane is in `textures`.
+    pub yuv_textures: HashMap<egui::TextureId, YuvTexture>,
+
+    /// Estimated GPU memory of each texture egui uploaded.
+    pub texture_bytes: HashMap<egui::TextureId, usize>,
+
+    /// Who keeps each texture alive.
+    refs: HashMap<egui::TextureId, Refs>,
+
+    next_native_tex_id: u64,
+
+    /// Painters using these textures that have not been destroyed yet.
+    painters: usize,
+
+    /// The id [`SharedTextures::attach`] gives the next painter.
+    next_painter_id: usize,
+}
+
+/// The references to a texture. It is deleted once there are none left.
+#[derive(Default)]
+struct Refs {
+    /// The painters that created, registered or painted it, until they free it.
+    painters: HashSet<usize>,
+
+    /// Taken by [`SharedTextures::register_native_texture`] and [`SharedTextures::retain`].
+    retained: usize,
+}
+
+impl Default for TextureStore {
+    fn default() -> Self {
+        Self {
+            textures: Default::default(),
+            samplers: Default::default(),
+            coverage_textures: Default::default(),
+            yuv_textures: Default::default(),
+            texture_bytes: Default::default(),
+            refs: Default::default(),
+            // Out of the way of ids picked by users:
+            next_native_tex_id: 1 << 32,
+            painters: 0,
+            next_painter_id: 0,
+        }
+    }
+}
+
+impl TextureStore {
+    /// The texture for `tex_id`, created if there is none yet, for use by `painter`.
+    pub(crate) unsafe fn get_or_create(
+        &mut self,
+        gl: &glow::Context,
+        tex_id: egui::TextureId,
+        painter: usize,
+    ) -> glow::Texture {
+        self.refs
+            .entry(tex_id)
+            .or_default()
+            .painters
+            .insert(painter);
+        *self
+            .textures
+            .entry(tex_id)
+            .or_insert_with(|| gl.create_texture().unwrap())
+    }
+
+    /// `painter` paints `tex_id`, and keeps it alive until it frees it,
+    /// unless it is only kept alive by [`SharedTextures::retain`].
+    pub(crate) fn use_texture(&mut self, tex_id: egui::TextureId, painter: usize) {
+        if let Some(refs) = self.refs.get_mut(&tex_id) {
+            if !refs.painters.is_empty() {
+                refs.painters.insert(painter);
+            }
+        }
+    }
+
+    /// A new id for a native texture, unique among all painters using this store.
+    ///
+    /// It is kept alive by `painter`, or if `None` by one [`SharedTextures::retain`].
+    pub(crate) fn insert_native(
+        &mut self,
+        native: glow::Texture,
+        painter: Option<usize>,
+    ) -> egui::TextureId {
+        let id = egui::TextureId::User(self.next_native_tex_id);
+        self.next_native_tex_id += 1;
+        self.textures.insert(id, native);
+        let refs = self.refs.entry(id).or_default();
+        match painter {
+            Some(painter) => {
+                refs.painters.insert(painter);
+            }
+            None => refs.retained += 1,
+        }
+        id
+    }
+
+    /// Set the texture of `tex_id` for `painter`, returning the one it replaces.
+    pub(crate) fn replace(
+        &mut self,
+        tex_id: egui::TextureId,
+        texture: glow::Texture,
+        painter: usize,
+    ) -> Option<glow::Texture> {
+        self.refs
+            .entry(tex_id)
+            .or_default()
+            .painters
+            .insert(painter);
+        self.textures.insert(tex_id, texture)
+    }
+
+    /// Move the textures of `other` into `self`, handing the references of its only painter to `painter`.
+    ///
+    /// On clashing ids the texture of `other` wins, but the one in `self` is not deleted.
+    /// Returns `false`, and leaves both alone, if other painters still use `other`.
+    pub(crate) fn merge(&mut self, other: &mut Self, painter: usize) -> bool {
+        if other.painters > 1 {
+            log::warn!("Not moving the textures of a painter into `SharedTextures`, because its old textures are shared with other painters");
+            return false;
+        }
+        if other
+            .textures
+            .keys()
+            .any(|id| self.textures.contains_key(id))
+        {
+            log::warn!("Texture ids clash while sharing textures; call `Painter::set_shared_textures` before creating any textures");
+        }
+        self.textures.extend(other.textures.drain());
+        self.samplers.extend(other.samplers.drain());
+        self.coverage_textures
+            .extend(other.coverage_textures.drain());
+        self.yuv_textures.extend(other.yuv_textures.drain());
+        self.texture_bytes.extend(other.texture_bytes.drain());
+        for (id, refs) in other.refs.drain() {
+            let painters = if refs.painters.is_empty() {
+                HashSet::new()
+            } else {
+                HashSet::from([painter])
+            };
+            let retained = refs.retained;
+            self.refs.insert(id, Refs { painters, retained });
+        }
+        self.next_native_tex_id = self.next_native_tex_id.max(other.next_native_tex_id);
+        true
+    }
+
+    /// Drop the reference of `painter` to `tex_id`. Does nothing if it has none,
+    /// so painters forwarding the same [`egui::TexturesDelta::free`] can't delete textures others use.
+    /// Returns the textures to delete if it was the last reference.
+    pub(crate) fn release(
+        &mut self,
+        tex_id: egui::TextureId,
+        painter: usize,
+    ) -> Vec<glow::Texture> {
+        let Some(refs) = self.refs.get_mut(&tex_id) else {
+            return self.remove(tex_id);
+        };
+        if refs.painters.remove(&painter) {
+            self.remove_if_unused(tex_id)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Drop a reference taken with [`SharedTextures::retain`].
+    /// Returns the textures to delete if it was the last reference.
+    pub(crate) fn unretain(&mut self, tex_id: egui::TextureId) -> Vec<glow::Texture> {
+        let Some(refs) = self.refs.get_mut(&tex_id) else {
+            return self.remove(tex_id);
+        };
+        if refs.retained == 0 {
+            return Vec::new();
+        }
+        refs.retained -= 1;
+        self.remove_if_unused(tex_id)
+    }
+
+    fn remove_if_unused(&mut self, tex_id: egui::TextureId) -> Vec<glow::Texture> {
+        match self.refs.get(&tex_id) {
+            Some(refs) if refs.painters.is_empty() && refs.retained == 0 => self.remove(tex_id),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Drop all references of `painter`, returning the textures to delete.
+    pub(crate) fn release_painter(&mut self, painter: usize) -> Vec<glow::Texture> {
+        let mut unused = Vec::new();
+        for (&id, refs) in &mut self.refs {
+            if refs.painters.remove(&painter) && refs.painters.is_empty() && refs.retained == 0 {
+                unused.push(id);
+            }
+        }
+        unused.into_iter().flat_map(|id| self.remove(id)).collect()
+    }
+
+    /// Forget everything about `tex_id`, and return its textures (including YUV planes).
+    pub(crate) fn remove(&mut self, tex_id: egui::TextureId) -> Vec<glow::Texture> {
+        self.refs.remove(&tex_id);
+        self.samplers.remove(&tex_id);
+        self.coverage_textures.remove(&tex_id);
+        self.texture_bytes.remove(&tex_id);
+        let mut textures: Vec<_> = self.textures.remove(&tex_id).into_iter().collect();
+        if let Some(yuv) = self.yuv_textures.remove(&tex_id) {
+            textures.extend(yuv.planes.chroma().into_iter().flatten());
+        }
+        textures
+    }
+}
+
+/// Textures that several [`crate::Painter`]s use together,
+/// e.g. one per window on OpenGL contexts that share objects.
+///
+/// Get the handle of one painter with [`crate::Painter::shared_textures`],
+/// and pass it to [`crate::Painter::set_shared_textures`] of the others.
+/// Then a texture uploaded or registered with any of the painters can be painted by all of them,
+/// and is only uploaded once.
+///
+/// egui's own textures (like the font atlas) belong to an [`egui::Context`],
+/// so only share them between painters that paint the same context,
+/// e.g. one [`egui::Context`] that is run for each window in turn.
+/// Native textures get ids that are unique among all the painters.
+///
+/// Each painter that uploads, registers or paints a texture keeps it alive until it frees it
+/// (with [`crate::Painter::free_texture`]) or is destroyed, and so does each [`Self::retain`]
+/// until [`Self::free_texture`]. Freeing a texture with a painter that holds no reference to it does nothing.
+/// A texture is deleted once all of these are gone.
+/// So with one [`egui::Context`] for several painters, pass its [`egui::TexturesDelta::free`] to each of them.
+/// Textures registered with [`Self::register_native_texture`] are only kept alive by retains,
+/// not by the painters painting them.
+/// Whatever is left is deleted when the last painter is destroyed.
+#[derive(Clone, Default)]
+pub struct SharedTextures(Arc<Mutex<TextureStore>>);
+
+impl SharedTextures {
+    /// Keep `tex_id` alive until one more [`Self::free_texture`],
+    /// e.g. while something other than a painter still uses it.
+    ///
+    /// Returns `false` if there is no such texture.
+    pub fn retain(&self, tex_id: egui::TextureId) -> bool {
+        let mut store = self.lock();
+        if let Some(refs) = store.refs.get_mut(&tex_id) {
+            refs.retained += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Like [`crate::Painter::register_native_texture`], but callable from any thread,
+    /// e.g. one that uploaded `native` with its own context sharing objects with the painters'.
+    ///
+    /// The texture is [retained](Self::retain) once, instead of by a painter.
+    /// Make sure the GPU has finished writing to `native` first (e.g. with a fence).
+    pub fn register_native_texture(&self, native: glow::Texture) -> egui::TextureId {
+        self.lock().insert_native(native, None)
+    }
+
+    /// Drop a reference taken with [`Self::retain`] or [`Self::register_native_texture`],
+    /// deleting the texture with `gl` if it was the last one.
+    /// `gl` must be current on this thread and share objects with the painters' contexts.
+    pub fn free_texture(&self, gl: &glow::Context, tex_id: egui::TextureId) {
+        let textures = self.lock().unretain(tex_id);
+        for texture in textures {
+            unsafe { gl.delete_texture(texture) };
+        }
+    }
+
+    /// The [`glow::Texture`] of a [`egui::TextureId`], if it is alive.
+    pub fn texture(&self, tex_id: egui::TextureId) -> Option<glow::Texture> {
+        self.lock().textures.get(&tex_id).copied()
+    }
+
+    /// The number of textures alive.
+    pub fn len(&self) -> usize {
+        self.lock().textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Are `self` and `other` handles to the same textures?
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<'_, TextureStore> {
+        self.0.lock()
+    }
+
+    /// A painter starts using these textures. Returns its id for the references it takes.
+    pub(crate) fn attach(&self) -> usize {
+        let mut store = self.lock();
+        store.painters += 1;
+        store.next_painter_id += 1;
+        store.next_painter_id - 1
+    }
+
+    /// A painter stops using these textures, dropping its references.
+    /// The last one deletes whatever is left.
+    pub(crate) unsafe fn detach(&self, gl: &glow::Context, painter: usize) {
+        let mut store = self.lock();
+        store.painters = store.painters.saturating_sub(1);
+        let textures = if store.painters == 0 {
+            let ids: Vec<_> = store.textures.keys().copied().collect();
+            ids.into_iter().flat_map(|id| store.remove(id)).collect()
+        } else {
+            store.release_painter(painter)
+        };
+        for texture in textures {
+            gl.delete_texture(texture);
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::{YuvColorSpace, YuvPlanes, YuvRange};
+
+    fn texture(name: u32) -> glow::Texture {
+        glow::NativeTexture(std::num::NonZeroU32::new(name).unwrap())
+    }
+
+    const ID: egui::TextureId = egui::TextureId::Managed(1);
+
+    #[test]
+    fn texture_lives_until_every_painter_freed_it() {
+        let mut store = TextureStore::default();
+        store.replace(ID, texture(1), 0);
+        store.use_texture(ID, 1);
+        store.use_texture(ID, 1);
+
+        assert_eq!(store.release(ID, 0), []);
+        assert!(store.textures.contains_key(&ID));
+        assert_eq!(store.release(ID, 1), [texture(1)]);
+        assert!(store.textures.is_empty());
+        assert!(store.refs.is_empty());
+    }
+
+    #[test]
+    fn painters_not_using_a_texture_cannot_delete_it() {
+        let mut store = TextureStore::default();
+        store.replace(ID, texture(1), 0);
+        assert_eq!(store.release(ID, 1), []);
+        assert_eq!(store.release(ID, 1), []);
+        assert!(store.textures.contains_key(&ID));
+        assert_eq!(store.release(ID, 0), [texture(1)]);
+    }
+
+    #[test]
+    fn retained_textures_need_another_free() {
+        let mut store = TextureStore::default();
+        store.replace(ID, texture(1), 0);
+        store.refs.get_mut(&ID).unwrap().retained += 1;
+
+        assert_eq!(store.release(ID, 0), []);
+        // A painter that isn't using it can't drop the retain:
+        assert_eq!(store.release(ID, 1), []);
+        assert_eq!(store.unretain(ID), [texture(1)]);
+        assert_eq!(store.unretain(ID), []);
+    }
+
+    #[test]
+    fn unretaining_keeps_painter_references() {
+        let mut store = TextureStore::default();
+        store.replace(ID, texture(1), 0);
+        // Nothing to unretain:
+        assert_eq!(store.unretain(ID), []);
+        store.refs.get_mut(&ID).unwrap().retained += 1;
+        assert_eq!(store.unretain(ID), []);
+        assert!(store.textures.contains_key(&ID));
+        assert_eq!(store.release(ID, 0), [texture(1)]);
+    }
+
+    #[test]
+    fn painting_does_not_keep_retained_textures_alive() {
+        let mut store = TextureStore::default();
+        let id = store.insert_native(texture(1), None);
+        store.use_texture(id, 0);
+        assert_eq!(store.release(id, 0), []);
+        assert_eq!(store.unretain(id), [texture(1)]);
+    }
+
+    #[test]
+    fn release_painter_drops_only_its_references() {
+        let mut store = TextureStore::default();
+        let mine = store.insert_native(texture(1), Some(0));
+        let ours = store.insert_native(texture(2), Some(0));
+        store.use_texture(ours, 1);
+        let retained = store.insert_native(texture(3), None);
+        store.use_texture(retained, 0);
+
+        assert_eq!(store.release_painter(0), [texture(1)]);
+        assert!(!store.textures.contains_key(&mine));
+        assert!(store.textures.contains_key(&ours));
+        assert!(store.textures.contains_key(&retained));
+    }
+
+    #[test]
+    fn remove_returns_yuv_planes() {
+        let mut store = TextureStore::default();
+        let planes = YuvPlanes::I420 {
+            y: texture(1),
+            u: texture(2),
+            v: texture(3),
+        };
+        let id = store.insert_native(planes.y(), Some(0));
+        store.yuv_textures.insert(
+            id,
+            YuvTexture {
+                planes,
+                color_space: YuvColorSpace::Bt709,
+                range: YuvRange::Limited,
+            },
+        );
+        store.texture_bytes.insert(id, 16);
+
+        assert_eq!(store.remove(id), [texture(1), texture(2), texture(3)]);
+        assert!(store.yuv_textures.is_empty());
+        assert!(store.texture_bytes.is_empty());
+        assert_eq!(store.remove(id), []);
+    }
+
+    #[test]
+    fn merge_hands_references_to_the_new_painter() {
+        let mut shared = TextureStore {
+            painters: 1,
+            ..Default::default()
+        };
+        shared.replace(egui::TextureId::Managed(2), texture(2), 0);
+
+        let mut old = TextureStore {
+            painters: 1,
+            ..Default::default()
+        };
+        old.replace(ID, texture(1), 0);
+        let retained = old.insert_native(texture(3), None);
+
+        assert!(shared.merge(&mut old, 1));
+        assert!(old.textures.is_empty());
+        assert_eq!(shared.textures.len(), 3);
+        assert_eq!(shared.release(ID, 0), []);
+        assert_eq!(shared.release(ID, 1), [texture(1)]);
+        assert_eq!(shared.unretain(retained), [texture(3)]);
+        let native = shared.insert_native(texture(4), Some(1));
+        assert_ne!(native, retained);
+    }
+
+    #[test]
+    fn merge_leaves_stores_of_other_painters_alone() {
+        let mut shared = TextureStore::default();
+        let mut old = TextureStore {
+            painters: 2,
+            ..Default::default()
+        };
+        old.replace(ID, texture(1), 0);
+
+        assert!(!shared.merge(&mut old, 1));
+        assert!(shared.textures.is_empty());
+        assert!(old.textures.contains_key(&ID));
+    }
+}
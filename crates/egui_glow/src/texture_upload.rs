@@ -0,0 +1,312 @@
+#![allow(unsafe_code)]
+
+//! Spreading texture uploads over several frames, for [`crate::Painter::set_upload_budget`].
+
+use std::collections::{HashMap, VecDeque};
+
+use glow::HasContext as _;
+
+use crate::check_for_gl_error;
+use crate::debug::object_label;
+
+/// Part of a texture that is yet to be uploaded.
+pub(crate) struct PendingUpload {
+    pub tex_id: egui::TextureId,
+
+    /// Where in the texture `data` goes.
+    pub pos: [usize; 2],
+
+    /// Width and height of `data`.
+    pub size: [usize; 2],
+
+    pub src_format: u32,
+    pub bytes_per_texel: usize,
+    pub data: Vec<u8>,
+
+    /// This sets the whole texture, so anything queued for it before doesn't matter.
+    pub whole_texture: bool,
+
+    /// Generate mipmaps once all of `data` is uploaded.
+    pub generate_mipmaps: bool,
+
+    /// How many rows of `data` have been uploaded so far.
+    pub rows_uploaded: usize,
+}
+
+impl PendingUpload {
+    fn row_bytes(&self) -> usize {
+        self.size[0] * self.bytes_per_texel
+    }
+}
+
+/// Uploads waiting for their turn, in the order they were made.
+pub(crate) struct TextureUploads {
+    queue: VecDeque<PendingUpload>,
+
+    /// Uploads are staged in a pixel buffer object where supported (OpenGL 2.1, OpenGL ES 3.0, WebGL2),
+    /// so the driver can copy them to the texture without stalling.
+    supports_pixel_buffer: bool,
+    pixel_buffer: Option<glow::Buffer>,
+    debug: bool,
+}
+
+impl TextureUploads {
+    pub(crate) fn new(gl: &glow::Context, is_webgl_1: bool, debug: bool) -> Self {
+        let version = gl.version();
+        let supports_pixel_buffer = !is_webgl_1
+            && (version.major >= 3
+                || (!version.is_embedded && (version.major, version.minor) >= (2, 1)));
+        Self {
+            queue: VecDeque::new(),
+            supports_pixel_buffer,
+            pixel_buffer: None,
+            debug,
+        }
+    }
+
+    /// Queue an upload. An upload of a whole texture cancels what was queued for it before.
+    pub(crate) fn push(&mut self, upload: PendingUpload) {
+        if upload.whole_texture {
+            self.remove(upload.tex_id);
+        }
+        if !upload.data.is_empty() {
+            self.queue.push_back(upload);
+        }
+    }
+
+    pub(crate) fn remove(&mut self, tex_id: egui::TextureId) {
+        self.queue.retain(|upload| upload.tex_id != tex_id);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Is `tex_id` allocated but not yet filled in, so sampling it would give undefined texels?
+    pub(crate) fn is_incomplete(&self, tex_id: egui::TextureId) -> bool {
+        self.queue
+            .iter()
+            .any(|upload| upload.tex_id == tex_id && upload.whole_texture)
+    }
+
+    /// The fraction of the queued data for `tex_id` that has been uploaded,
+    /// or `None` if nothing is queued for it.
+    pub(crate) fn progress(&self, tex_id: egui::TextureId) -> Option<f32> {
+        let (uploaded, total) = self
+            .queue
+            .iter()
+            .filter(|upload| upload.tex_id == tex_id)
+            .fold((0, 0), |(uploaded, total), upload| {
+                (
+                    uploaded + upload.rows_uploaded * upload.row_bytes(),
+                    total + upload.data.len(),
+                )
+            });
+        (total > 0).then(|| uploaded as f32 / total as f32)
+    }
+
+    /// The textures with queued uploads, in the order they were queued.
+    pub(crate) fn pending(&self) -> Vec<egui::TextureId> {
+        let mut pending = Vec::new();
+        for upload in &self.queue {
+            if !pending.contains(&upload.tex_id) {
+                pending.push(upload.tex_id);
+            }
+        }
+        pending
+    }
+
+    /// Upload about `budget` bytes, in whole rows and at least one row.
+    /// Returns the number of bytes uploaded.
+    ///
+    /// Leaves `TEXTURE_2D` unbound.
+    pub(crate) unsafe fn process(
+        &mut self,
+        gl: &glow::Context,
+        textures: &HashMap<egui::TextureId, glow::Texture>,
+        budget: usize,
+    ) -> usize {
+        if self.queue.is_empty() {
+            return 0;
+        }
+        crate::profile_function!();
+
+        if self.supports_pixel_buffer && self.pixel_buffer.is_none() {
+            self.pixel_buffer = gl.create_buffer().ok();
+            if let (true, Some(buffer)) = (self.debug, self.pixel_buffer) {
+                gl.bind_buffer(glow::PIXEL_UNPACK_BUFFER, Some(buffer));
+                object_label(gl, buffer, "egui texture uploads");
+            }
+        }
+        if let Some(buffer) = self.pixel_buffer {
+            gl.bind_buffer(glow::PIXEL_UNPACK_BUFFER, Some(buffer));
+        }
+        gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+
+        let mut uploaded = 0;
+        let mut remaining = budget;
+        while let Some(upload) = self.queue.front_mut() {
+            let Some(&texture) = textures.get(&upload.tex_id) else {
+                self.queue.pop_front();
+                continue;
+            };
+
+            let row_bytes = upload.row_bytes();
+            let rows_left = upload.size[1] - upload.rows_uploaded;
+            let Some(rows) = rows_to_upload(row_bytes, rows_left, remaining, budget) else {
+                break;
+            };
+            let start = upload.rows_uploaded * row_bytes;
+            let chunk = &upload.data[start..start + rows * row_bytes];
+
+            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            let pixels = if self.pixel_buffer.is_some() {
+                // Replaces (orphans) the previous chunk, so we don't wait for it to be copied:
+                gl.buffer_data_u8_slice(glow::PIXEL_UNPACK_BUFFER, chunk, glow::STREAM_DRAW);
+                glow::PixelUnpackData::BufferOffset(0)
+            } else {
+                glow::PixelUnpackData::Slice(chunk)
+            };
+            gl.tex_sub_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                upload.pos[0] as _,
+                (upload.pos[1] + upload.rows_uploaded) as _,
+                upload.size[0] as _,
+                rows as _,
+                upload.src_format,
+                glow::UNSIGNED_BYTE,
+                pixels,
+            );
+            check_for_gl_error!(gl, "texture upload");
+
+            upload.rows_uploaded += rows;
+            uploaded += chunk.len();
+            remaining = remaining.saturating_sub(chunk.len());
+            if upload.rows_uploaded == upload.size[1] {
+                if upload.generate_mipmaps {
+                    gl.generate_mipmap(glow::TEXTURE_2D);
+                }
+                self.queue.pop_front();
+            }
+        }
+
+        if self.pixel_buffer.is_some() {
+            gl.bind_buffer(glow::PIXEL_UNPACK_BUFFER, None);
+        }
+        gl.bind_texture(glow::TEXTURE_2D, None);
+        uploaded
+    }
+
+    pub(crate) unsafe fn destroy(&self, gl: &glow::Context) {
+        if let Some(buffer) = self.pixel_buffer {
+            gl.delete_buffer(buffer);
+        }
+    }
+}
+
+/// How many of `rows_left` rows of `row_bytes` each fit in the `remaining` part of the `budget`.
+///
+/// The first upload of a frame gets at least one row, so a budget smaller than a row still makes progress.
+/// Returns `None` once the budget is used up.
+fn rows_to_upload(
+    row_bytes: usize,
+    rows_left: usize,
+    remaining: usize,
+    budget: usize,
+) -> Option<usize> {
+    match (remaining / row_bytes.max(1)).min(rows_left) {
+        0 if remaining < budget => None,
+        0 => Some(1),
+        rows => Some(rows),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uploads() -> TextureUploads {
+        TextureUploads {
+            queue: VecDeque::new(),
+            supports_pixel_buffer: false,
+            pixel_buffer: None,
+            debug: false,
+        }
+    }
+
+    fn upload(id: u64, size: [usize; 2], whole_texture: bool) -> PendingUpload {
+        PendingUpload {
+            tex_id: egui::TextureId::Managed(id),
+            pos: [0, 0],
+            size,
+            src_format: glow::RGBA,
+            bytes_per_texel: 4,
+            data: vec![0; size[0] * size[1] * 4],
+            whole_texture,
+            generate_mipmaps: false,
+            rows_uploaded: 0,
+        }
+    }
+
+    #[test]
+    fn whole_texture_upload_replaces_queued_ones() {
+        let mut uploads = uploads();
+        uploads.push(upload(1, [4, 4], true));
+        uploads.push(upload(1, [2, 2], false));
+        uploads.push(upload(2, [2, 2], false));
+        assert_eq!(uploads.queue.len(), 3);
+
+        uploads.push(upload(1, [8, 8], true));
+        assert_eq!(uploads.queue.len(), 2);
+        assert_eq!(
+            uploads.pending(),
+            vec![egui::TextureId::Managed(2), egui::TextureId::Managed(1)]
+        );
+    }
+
+    #[test]
+    fn empty_uploads_are_not_queued() {
+        let mut uploads = uploads();
+        uploads.push(upload(1, [0, 4], true));
+        assert!(uploads.is_empty());
+    }
+
+    #[test]
+    fn progress_counts_uploaded_rows() {
+        let mut uploads = uploads();
+        let id = egui::TextureId::Managed(1);
+        assert_eq!(uploads.progress(id), None);
+
+        uploads.push(upload(1, [4, 4], true));
+        uploads.push(upload(1, [4, 4], false));
+        assert_eq!(uploads.progress(id), Some(0.0));
+
+        uploads.queue[0].rows_uploaded = 4;
+        uploads.queue[1].rows_uploaded = 2;
+        assert_eq!(uploads.progress(id), Some(0.75));
+
+        uploads.remove(id);
+        assert_eq!(uploads.progress(id), None);
+    }
+
+    #[test]
+    fn only_whole_texture_uploads_leave_textures_incomplete() {
+        let mut uploads = uploads();
+        uploads.push(upload(1, [4, 4], false));
+        assert!(!uploads.is_incomplete(egui::TextureId::Managed(1)));
+        uploads.push(upload(2, [4, 4], true));
+        assert!(uploads.is_incomplete(egui::TextureId::Managed(2)));
+    }
+
+    #[test]
+    fn budget_is_spent_in_whole_rows() {
+        let row = 16;
+        assert_eq!(rows_to_upload(row, 10, 100, 100), Some(6));
+        assert_eq!(rows_to_upload(row, 3, 100, 100), Some(3));
+        // Less than a row left of the budget:
+        assert_eq!(rows_to_upload(row, 10, 4, 100), None);
+        // A budget smaller than a row still uploads one:
+        assert_eq!(rows_to_upload(row, 10, 4, 4), Some(1));
+    }
+}
@@ -104,11 +104,24 @@ impl VertexArrayObject {
             }
         }
     }
+
+    /// Label the vertex array object, if we have a real one, see [`crate::debug::object_label`].
+    pub(crate) unsafe fn set_label(&self, gl: &glow::Context, label: &str) {
+        if let Some(vao) = self.vao {
+            crate::debug::object_label(gl, vao, label);
+        }
+    }
+
+    pub(crate) unsafe fn destroy(&self, gl: &glow::Context) {
+        if let Some(vao) = self.vao {
+            gl.delete_vertex_array(vao);
+        }
+    }
 }
 
 // ----------------------------------------------------------------------------
 
-fn supports_vao(gl: &glow::Context) -> bool {
+pub(crate) fn supports_vao(gl: &glow::Context) -> bool {
     const WEBGL_PREFIX: &str = "WebGL ";
     const OPENGL_ES_PREFIX: &str = "OpenGL ES ";
 
@@ -11,6 +11,12 @@ pub struct EguiGlow {
 
     shapes: Vec<egui::epaint::ClippedShape>,
     textures_delta: egui::TexturesDelta,
+
+    /// See [`Self::set_viewport`].
+    viewport: Option<egui::Rect>,
+
+    /// Keeps pointer events outside of [`Self::viewport`] from egui.
+    pointer_filter: PointerFilter,
 }
 
 impl EguiGlow {
@@ -20,7 +26,17 @@ impl EguiGlow {
         gl: std::sync::Arc<glow::Context>,
         shader_version: Option<ShaderVersion>,
     ) -> Self {
-        let painter = crate::Painter::new(gl, "", shader_version)
+        Self::new_with_options(event_loop, gl, shader_version, Default::default())
+    }
+
+    /// Like [`Self::new`], with [`crate::PainterOptions`].
+    pub fn new_with_options<E>(
+        event_loop: &winit::event_loop::EventLoopWindowTarget<E>,
+        gl: std::sync::Arc<glow::Context>,
+        shader_version: Option<ShaderVersion>,
+        options: crate::PainterOptions,
+    ) -> Self {
+        let painter = crate::Painter::new_with_options(gl, "", shader_version, options)
             .map_err(|err| {
                 log::error!("error occurred in initializing painter:\n{err}");
             })
@@ -32,9 +48,36 @@ impl EguiGlow {
             painter,
             shapes: Default::default(),
             textures_delta: Default::default(),
+            viewport: None,
+            pointer_filter: Default::default(),
         }
     }
 
+    /// Show egui in a region of the window, e.g. one player's part of a split screen.
+    ///
+    /// `viewport` is in physical pixels from the top left of the window.
+    /// It is rounded to whole pixels.
+    /// egui's screen becomes the size of the region, pointer and touch positions are made relative to it,
+    /// and [`Self::paint`] only paints there (see [`crate::Painter::set_target_offset`]).
+    /// The pointer leaves egui when it leaves the region, unless it is dragging something.
+    /// `None` means the whole window (default).
+    ///
+    /// You can use one [`EguiGlow`] per region, and pass all window events to each.
+    pub fn set_viewport(&mut self, viewport: Option<egui::Rect>) {
+        self.viewport = viewport;
+    }
+
+    /// The region set with [`Self::set_viewport`].
+    pub fn viewport(&self) -> Option<egui::Rect> {
+        self.viewport
+    }
+
+    /// [`Self::viewport`] in whole pixels, which is what we paint.
+    fn viewport_px(&self) -> Option<egui::Rect> {
+        self.viewport
+            .map(|viewport| egui::Rect::from_min_max(viewport.min.round(), viewport.max.round()))
+    }
+
     pub fn on_event(&mut self, event: &winit::event::WindowEvent<'_>) -> EventResponse {
         self.egui_winit.on_event(&self.egui_ctx, event)
     }
@@ -47,7 +90,24 @@ impl EguiGlow {
         window: &winit::window::Window,
         run_ui: impl FnMut(&egui::Context),
     ) -> std::time::Duration {
-        let raw_input = self.egui_winit.take_egui_input(window);
+        let mut raw_input = self.egui_winit.take_egui_input(window);
+        if let Some(viewport) = self.viewport_px() {
+            let pixels_per_point = self.egui_winit.pixels_per_point();
+            let offset = viewport.min.to_vec2() / pixels_per_point;
+            let screen_rect =
+                egui::Rect::from_min_size(egui::Pos2::ZERO, viewport.size() / pixels_per_point);
+            raw_input.screen_rect = Some(screen_rect);
+            for event in &mut raw_input.events {
+                match event {
+                    egui::Event::PointerMoved(pos)
+                    | egui::Event::PointerButton { pos, .. }
+                    | egui::Event::Touch { pos, .. } => *pos -= offset,
+                    _ => {}
+                }
+            }
+            let events = std::mem::take(&mut raw_input.events);
+            raw_input.events = self.pointer_filter.filter(events, screen_rect);
+        }
         let egui::FullOutput {
             platform_output,
             repaint_after,
@@ -69,11 +129,20 @@ impl EguiGlow {
         let mut textures_delta = std::mem::take(&mut self.textures_delta);
 
         for (id, image_delta) in textures_delta.set {
-            self.painter.set_texture(id, &image_delta);
+            self.painter.set_texture_owned(id, image_delta);
         }
 
         let clipped_primitives = self.egui_ctx.tessellate(shapes);
-        let dimensions: [u32; 2] = window.inner_size().into();
+        let window_size: [u32; 2] = window.inner_size().into();
+        let dimensions = if let Some(viewport) = self.viewport_px() {
+            let (min, max) = (viewport.min, viewport.max);
+            self.painter
+                .set_target_offset([min.x as i32, window_size[1] as i32 - max.y as i32]);
+            [(max.x - min.x) as u32, (max.y - min.y) as u32]
+        } else {
+            self.painter.set_target_offset([0, 0]);
+            window_size
+        };
         self.painter.paint_primitives(
             dimensions,
             self.egui_ctx.pixels_per_point(),
@@ -90,3 +159,136 @@ impl EguiGlow {
         self.painter.destroy();
     }
 }
+
+/// Drops pointer events outside of a viewport, so each [`EguiGlow`] of a split screen
+/// only sees the pointer while it is over its own region.
+#[derive(Default)]
+struct PointerFilter {
+    /// Does egui know where the pointer is, i.e. has it not been sent [`egui::Event::PointerGone`]?
+    pointer_present: bool,
+
+    /// Was a button pressed in the viewport and not released yet?
+    /// The pointer is then followed outside the viewport, so drags don't get stuck.
+    dragging: bool,
+}
+
+impl PointerFilter {
+    fn filter(&mut self, events: Vec<egui::Event>, screen_rect: egui::Rect) -> Vec<egui::Event> {
+        events
+            .into_iter()
+            .filter_map(|event| match event {
+                egui::Event::PointerMoved(pos) => {
+                    if screen_rect.contains(pos) || self.dragging {
+                        self.pointer_present = true;
+                        Some(event)
+                    } else if std::mem::take(&mut self.pointer_present) {
+                        Some(egui::Event::PointerGone)
+                    } else {
+                        None
+                    }
+                }
+                egui::Event::PointerButton {
+                    pos, pressed: true, ..
+                } => screen_rect.contains(pos).then(|| {
+                    self.dragging = true;
+                    self.pointer_present = true;
+                    event
+                }),
+                egui::Event::PointerButton { pressed: false, .. } => {
+                    std::mem::take(&mut self.dragging).then_some(event)
+                }
+                egui::Event::PointerGone => {
+                    std::mem::take(&mut self.pointer_present).then_some(event)
+                }
+                egui::Event::Touch {
+                    pos,
+                    phase: egui::TouchPhase::Start,
+                    ..
+                } if !screen_rect.contains(pos) => None,
+                event => Some(event),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::{pos2, Event, PointerButton, Rect};
+
+    fn button(x: f32, pressed: bool) -> Event {
+        Event::PointerButton {
+            pos: pos2(x, 5.0),
+            button: PointerButton::Primary,
+            pressed,
+            modifiers: Default::default(),
+        }
+    }
+
+    #[test]
+    fn pointer_leaving_the_viewport_is_gone() {
+        let screen_rect = Rect::from_min_max(pos2(0.0, 0.0), pos2(10.0, 10.0));
+        let mut filter = PointerFilter::default();
+        let events = vec![
+            Event::PointerMoved(pos2(-5.0, 5.0)),
+            Event::PointerMoved(pos2(5.0, 5.0)),
+            Event::PointerMoved(pos2(15.0, 5.0)),
+            Event::PointerMoved(pos2(20.0, 5.0)),
+            Event::PointerGone,
+        ];
+        assert_eq!(
+            filter.filter(events, screen_rect),
+            [Event::PointerMoved(pos2(5.0, 5.0)), Event::PointerGone]
+        );
+    }
+
+    #[test]
+    fn clicks_outside_the_viewport_are_dropped() {
+        let screen_rect = Rect::from_min_max(pos2(0.0, 0.0), pos2(10.0, 10.0));
+        let mut filter = PointerFilter::default();
+        let events = vec![button(15.0, true), button(15.0, false)];
+        assert_eq!(filter.filter(events, screen_rect), []);
+    }
+
+    #[test]
+    fn drags_are_followed_out_of_the_viewport() {
+        let screen_rect = Rect::from_min_max(pos2(0.0, 0.0), pos2(10.0, 10.0));
+        let mut filter = PointerFilter::default();
+        let events = vec![
+            button(5.0, true),
+            Event::PointerMoved(pos2(15.0, 5.0)),
+            button(15.0, false),
+            Event::PointerMoved(pos2(16.0, 5.0)),
+        ];
+        assert_eq!(
+            filter.filter(events, screen_rect),
+            [
+                button(5.0, true),
+                Event::PointerMoved(pos2(15.0, 5.0)),
+                button(15.0, false),
+                Event::PointerGone,
+            ]
+        );
+    }
+
+    #[test]
+    fn touches_starting_outside_the_viewport_are_dropped() {
+        let screen_rect = Rect::from_min_max(pos2(0.0, 0.0), pos2(10.0, 10.0));
+        let mut filter = PointerFilter::default();
+        let touch = |x, phase| Event::Touch {
+            device_id: egui::TouchDeviceId(0),
+            id: egui::TouchId(0),
+            phase,
+            pos: pos2(x, 5.0),
+            force: 0.0,
+        };
+        let events = vec![
+            touch(15.0, egui::TouchPhase::Start),
+            touch(5.0, egui::TouchPhase::Start),
+        ];
+        assert_eq!(
+            filter.filter(events, screen_rect),
+            [touch(5.0, egui::TouchPhase::Start)]
+        );
+    }
+}
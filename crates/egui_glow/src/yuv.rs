@@ -0,0 +1,215 @@
+//! YUV textures, converted to RGB in the egui shader.
+
+/// The planes of a YUV image, each a [`glow::Texture`] with 8-bit unsigned normalized channels.
+///
+/// Register them with [`crate::Painter::register_yuv_texture`].
+///
+/// Single-channel planes can be `R8`, or `LUMINANCE` where `R8` is not supported
+/// (WebGL1, and OpenGL 2.1 without `GL_ARB_texture_rg`).
+/// Chroma planes are usually half the width and height of the luma plane.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum YuvPlanes {
+    /// Three single-channel planes, e.g. from `AV_PIX_FMT_YUV420P`.
+    I420 {
+        y: glow::Texture,
+        u: glow::Texture,
+        v: glow::Texture,
+    },
+
+    /// A single-channel luma plane, and a plane with U in the first and V in the second channel.
+    ///
+    /// `uv` is `RG8`, or `LUMINANCE_ALPHA` where `RG8` is not supported.
+    Nv12 { y: glow::Texture, uv: glow::Texture },
+}
+
+impl YuvPlanes {
+    /// The luma plane.
+    pub fn y(&self) -> glow::Texture {
+        match *self {
+            Self::I420 { y, .. } | Self::Nv12 { y, .. } => y,
+        }
+    }
+
+    /// The textures for texture units 1 and 2, next to the luma plane on unit 0.
+    pub(crate) fn chroma(&self) -> [Option<glow::Texture>; 2] {
+        match *self {
+            Self::I420 { u, v, .. } => [Some(u), Some(v)],
+            Self::Nv12 { uv, .. } => [Some(uv), None],
+        }
+    }
+}
+
+/// Which matrix converts YUV to RGB.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum YuvColorSpace {
+    /// ITU-R BT.601, used by standard definition video and JPEG.
+    Bt601,
+
+    /// ITU-R BT.709, used by HD video (default).
+    #[default]
+    Bt709,
+
+    /// ITU-R BT.2020 (non-constant luminance), used by UHD video.
+    Bt2020,
+}
+
+/// The range of the YUV values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum YuvRange {
+    /// Luma in `16..=235` and chroma in `16..=240`, as in most video (default).
+    #[default]
+    Limited,
+
+    /// Luma and chroma in `0..=255`, as in JPEG.
+    Full,
+}
+
+/// How to convert the YUV planes of a texture to RGB.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct YuvTexture {
+    pub planes: YuvPlanes,
+    pub color_space: YuvColorSpace,
+    pub range: YuvRange,
+}
+
+impl YuvTexture {
+    /// The column-major matrix and offset for `rgb = matrix * (yuv - offset)`.
+    pub(crate) fn conversion(&self) -> ([f32; 9], [f32; 3]) {
+        let (kr, kb) = match self.color_space {
+            YuvColorSpace::Bt601 => (0.299, 0.114),
+            YuvColorSpace::Bt709 => (0.2126, 0.0722),
+            YuvColorSpace::Bt2020 => (0.2627, 0.0593),
+        };
+        let kg = 1.0 - kr - kb;
+        let (y_scale, uv_scale, y_offset) = match self.range {
+            YuvRange::Limited => (255.0 / 219.0, 255.0 / 224.0, 16.0 / 255.0),
+            YuvRange::Full => (1.0, 1.0, 0.0),
+        };
+        let matrix = [
+            // Y:
+            y_scale,
+            y_scale,
+            y_scale,
+            // U:
+            0.0,
+            -uv_scale * 2.0 * kb * (1.0 - kb) / kg,
+            uv_scale * 2.0 * (1.0 - kb),
+            // V:
+            uv_scale * 2.0 * (1.0 - kr),
+            -uv_scale * 2.0 * kr * (1.0 - kr) / kg,
+            0.0,
+        ];
+        (matrix, [y_offset, 128.0 / 255.0, 128.0 / 255.0])
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    /// Convert 8-bit YUV like the shader does.
+    fn to_rgb(color_space: YuvColorSpace, range: YuvRange, yuv: [u8; 3]) -> [f32; 3] {
+        let texture = YuvTexture {
+            planes: YuvPlanes::Nv12 {
+                y: glow::NativeTexture(std::num::NonZeroU32::new(1).unwrap()),
+                uv: glow::NativeTexture(std::num::NonZeroU32::new(2).unwrap()),
+            },
+            color_space,
+            range,
+        };
+        let (matrix, offset) = texture.conversion();
+        let yuv = [0, 1, 2].map(|i| yuv[i] as f32 / 255.0 - offset[i]);
+        [0, 1, 2].map(|row| {
+            (0..3)
+                .map(|column| matrix[3 * column + row] * yuv[column])
+                .sum()
+        })
+    }
+
+    fn assert_close(rgb: [f32; 3], expected: [f32; 3], what: &str) {
+        for (c, e) in rgb.iter().zip(expected) {
+            assert!((c - e).abs() < 0.01, "{what}: {rgb:?} != {expected:?}");
+        }
+    }
+
+    const COLOR_SPACES: [YuvColorSpace; 3] = [
+        YuvColorSpace::Bt601,
+        YuvColorSpace::Bt709,
+        YuvColorSpace::Bt2020,
+    ];
+
+    #[test]
+    fn limited_range_black_and_white() {
+        for color_space in COLOR_SPACES {
+            let black = to_rgb(color_space, YuvRange::Limited, [16, 128, 128]);
+            let white = to_rgb(color_space, YuvRange::Limited, [235, 128, 128]);
+            assert_close(black, [0.0; 3], &format!("{color_space:?} black"));
+            assert_close(white, [1.0; 3], &format!("{color_space:?} white"));
+        }
+    }
+
+    #[test]
+    fn full_range_black_and_white() {
+        for color_space in COLOR_SPACES {
+            let black = to_rgb(color_space, YuvRange::Full, [0, 128, 128]);
+            let white = to_rgb(color_space, YuvRange::Full, [255, 128, 128]);
+            assert_close(black, [0.0; 3], &format!("{color_space:?} black"));
+            assert_close(white, [1.0; 3], &format!("{color_space:?} white"));
+        }
+    }
+
+    #[test]
+    fn primaries() {
+        // Rounded 8-bit encodings of pure red, green and blue from the standards:
+        for (color_space, range, yuv, rgb) in [
+            (
+                YuvColorSpace::Bt601,
+                YuvRange::Limited,
+                [81, 90, 240],
+                [1.0, 0.0, 0.0],
+            ),
+            (
+                YuvColorSpace::Bt601,
+                YuvRange::Limited,
+                [145, 54, 34],
+                [0.0, 1.0, 0.0],
+            ),
+            (
+                YuvColorSpace::Bt601,
+                YuvRange::Limited,
+                [41, 240, 110],
+                [0.0, 0.0, 1.0],
+            ),
+            (
+                YuvColorSpace::Bt709,
+                YuvRange::Limited,
+                [63, 102, 240],
+                [1.0, 0.0, 0.0],
+            ),
+            (
+                YuvColorSpace::Bt709,
+                YuvRange::Limited,
+                [173, 42, 26],
+                [0.0, 1.0, 0.0],
+            ),
+            (
+                YuvColorSpace::Bt709,
+                YuvRange::Limited,
+                [32, 240, 118],
+                [0.0, 0.0, 1.0],
+            ),
+            (
+                YuvColorSpace::Bt601,
+                YuvRange::Full,
+                [76, 85, 255],
+                [1.0, 0.0, 0.0],
+            ),
+        ] {
+            assert_close(
+                to_rgb(color_space, range, yuv),
+                rgb,
+                &format!("{color_space:?} {range:?} {yuv:?}"),
+            );
+        }
+    }
+}